@@ -51,6 +51,7 @@ pub fn Layout(inner: impl Renderable) -> impl Renderable {
                     { inner }
                 </main>
                 {BottomNav()}
+                {FormErrors()}
             </body>
         </html>
     }
@@ -76,16 +77,16 @@ pub fn TopNav() -> impl Renderable {
                             <p class="my-auto pr-3 font-semibold">Div176</p>
                         </a>
                         <div class="flex text-white space-x-0.5 *:px-3 *:py-1 *:my-auto hover:*:bg-green">
-                            <a href="#events" class="bg-green">Events</a>
-                            <a href="#hours" class="">Hours</a>
-                            <a href="#volunteers" class="">Volunteers</a>
-                            <a href="#reports" class="">Reports</a>
+                            <a href="/events" class="bg-green">Events</a>
+                            <a href="/hours" class="">Hours</a>
+                            <a href="/volunteers" class="">Volunteers</a>
+                            <a href="/reports" class="">Reports</a>
                             <a href="#documents" class="">Documents</a>
                             <a href="#map" class="">Map</a>
                         </div>
                     </div>
                     <div class="flex text-white space-x-0.5 *:px-3 *:py-1 *:my-auto hover:*:bg-green">
-                        <a href="#user" class="">Account</a>
+                        <a href="/account" class="">Account</a>
                     </div>
                 </div>
             </div>
@@ -119,15 +120,15 @@ pub fn BottomNav() -> impl Renderable {
                     {HomeIcon()}
                     <p>Home</p>
                 </a>
-                <a href="#" class="">
+                <a href="/events" class="">
                     {CalendarIcon()}
                     <p>Events</p>
                 </a>
-                <a href="#" class="">
+                <a href="/hours" class="">
                     {HoursIcon()}
                     <p>Hours</p>
                 </a>
-                <a href="#" class="">
+                <a href="/account" class="">
                     {ProfileIcon()}
                     <p>Account</p>
                 </a>
@@ -135,6 +136,47 @@ pub fn BottomNav() -> impl Renderable {
     }
 }
 
+pub fn SelectOption(value: String, label: impl Renderable, selected: bool) -> impl Renderable {
+    move |output: &mut String| {
+        if selected {
+            rsx_move! { <option value=value selected>{label}</option> }.render_to(output)
+        } else {
+            rsx_move! { <option value=value>{label}</option> }.render_to(output)
+        }
+    }
+}
+
+/// Shows the field errors of a 422 `Error::UnprocessableEntity` response in
+/// the `[data-error-for=field]` elements of the closest `[data-errors]` scope
+pub fn FormErrors() -> impl Renderable {
+    rsx! {
+        <script>{Raw("
+            document.addEventListener('htmx:beforeRequest', (e) => {
+                const scope = e.detail.elt.closest('[data-errors]');
+                scope?.querySelectorAll('[data-error-for]').forEach((el) => el.textContent = '');
+            });
+
+            document.addEventListener('htmx:responseError', (e) => {
+                const xhr = e.detail.xhr;
+                if (xhr.status !== 422) return;
+
+                let errors;
+                try {
+                    ({ errors } = JSON.parse(xhr.responseText));
+                } catch {
+                    return;
+                }
+
+                const scope = e.detail.elt.closest('[data-errors]') ?? document;
+                for (const [field, messages] of Object.entries(errors)) {
+                    const el = scope.querySelector(`[data-error-for=\"${field}\"]`);
+                    if (el) el.textContent = messages.join(' ');
+                }
+            });
+        ")}</script>
+    }
+}
+
 pub fn PageUnderConstruction() -> impl Renderable {
     rsx! {
         <div class="m-8 flex flex-col items-center justify-center">
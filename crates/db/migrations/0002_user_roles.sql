@@ -0,0 +1,10 @@
+-- Every member is a volunteer, additional roles grant permissions
+CREATE TYPE user_role AS ENUM ('volunteer', 'duty_lead', 'scheduler', 'admin');
+
+CREATE TABLE users_roles (
+    uid INTEGER NOT NULL REFERENCES users(uid),
+    role user_role NOT NULL,
+    PRIMARY KEY (uid, role),
+    -- stats
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
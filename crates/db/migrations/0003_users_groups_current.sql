@@ -0,0 +1,16 @@
+-- The latest issue of each group a user holds, and whether it has expired
+CREATE VIEW users_groups_current AS
+SELECT DISTINCT ON (ug.uid, ug.gid)
+    ug.uid,
+    ug.gid,
+    ug.issued_at,
+    ug.issued_at + make_interval(years => g.qualification_expiration_yrs) AS expires_at,
+    COALESCE(
+        g.qualification_expiration_yrs IS NULL
+        OR ug.issued_at + make_interval(years => g.qualification_expiration_yrs) > CURRENT_TIMESTAMP,
+        FALSE
+    ) AS valid
+FROM users_groups ug
+JOIN groups g ON g.gid = ug.gid
+WHERE g.archived_at IS NULL
+ORDER BY ug.uid, ug.gid, ug.issued_at DESC NULLS LAST;
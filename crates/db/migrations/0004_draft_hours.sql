@@ -0,0 +1,6 @@
+ALTER TABLE shifts ADD COLUMN hours_prefilled_at TIMESTAMPTZ;
+
+-- Only shifts ending from now on get drafts, not the division's whole history
+UPDATE shifts SET hours_prefilled_at = CURRENT_TIMESTAMP WHERE end_time < LOCALTIMESTAMP;
+
+ALTER TABLE user_hours ADD COLUMN draft BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Comments from before authors were recorded have none
+ALTER TABLE comments ADD COLUMN uid INTEGER REFERENCES users(uid);
+
+CREATE INDEX idx_comments_eid ON comments(eid);
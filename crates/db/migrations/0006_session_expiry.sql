@@ -0,0 +1,7 @@
+ALTER TABLE user_sessions
+    ADD COLUMN last_seen_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN expires_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP + INTERVAL '30 days';
+
+ALTER TABLE user_sessions ALTER COLUMN expires_at DROP DEFAULT;
+
+CREATE INDEX idx_user_sessions_expires_at ON user_sessions(expires_at);
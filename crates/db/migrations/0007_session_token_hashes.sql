@@ -0,0 +1,14 @@
+-- Sessions are looked up by a keyed hash of their token, so the plaintext
+-- tokens stored until now can't be carried over
+DELETE FROM user_sessions;
+
+ALTER TABLE user_sessions
+    DROP CONSTRAINT user_sessions_pkey,
+    DROP COLUMN token,
+    ADD COLUMN usid SERIAL PRIMARY KEY,
+    ADD COLUMN token_hash BYTEA NOT NULL UNIQUE,
+    -- Still accepted briefly after a rotation, for requests already in flight
+    ADD COLUMN previous_token_hash BYTEA UNIQUE,
+    ADD COLUMN rotated_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN user_agent TEXT,
+    ADD COLUMN ip_address TEXT;
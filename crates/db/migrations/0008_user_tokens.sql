@@ -0,0 +1,18 @@
+-- Invited members have no password until they accept their invitation
+ALTER TABLE users ALTER COLUMN password_hash DROP NOT NULL;
+
+CREATE TYPE user_token_purpose AS ENUM ('invitation', 'password_reset');
+
+-- Single use links for setting a password, stored as a keyed hash
+CREATE TABLE user_tokens (
+    utid SERIAL PRIMARY KEY,
+    uid INTEGER NOT NULL REFERENCES users(uid),
+    purpose user_token_purpose NOT NULL,
+    token_hash BYTEA NOT NULL UNIQUE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    created_by INTEGER REFERENCES users(uid),
+    -- stats
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_user_tokens_uid ON user_tokens(uid);
@@ -0,0 +1,24 @@
+CREATE TYPE login_outcome AS ENUM ('succeeded', 'bad_password', 'unknown_user', 'locked_out');
+
+-- Every login attempt, for throttling by address and for admins to review
+CREATE TABLE login_attempts (
+    laid SERIAL PRIMARY KEY,
+    username TEXT NOT NULL,
+    uid INTEGER REFERENCES users(uid),
+    outcome login_outcome NOT NULL,
+    ip_address TEXT,
+    user_agent TEXT,
+    -- stats
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_login_attempts_ip_address ON login_attempts(ip_address, created_at);
+CREATE INDEX idx_login_attempts_created_at ON login_attempts(created_at);
+
+-- Consecutive failures per username as typed, so unknown usernames lock out
+-- the same way existing ones do
+CREATE TABLE login_lockouts (
+    username TEXT PRIMARY KEY,
+    failures INTEGER NOT NULL,
+    last_failure_at TIMESTAMPTZ NOT NULL,
+    locked_until TIMESTAMPTZ
+);
@@ -0,0 +1,31 @@
+ALTER TABLE users
+    -- Set when enrolment starts, only in use once totp_enabled_at is
+    ADD COLUMN totp_secret BYTEA,
+    ADD COLUMN totp_enabled_at TIMESTAMPTZ,
+    -- The time step of the last accepted code, so a code can't be replayed
+    ADD COLUMN totp_last_step BIGINT;
+
+-- Single use codes for signing in without the authenticator, stored as a
+-- keyed hash
+CREATE TABLE user_recovery_codes (
+    urcid SERIAL PRIMARY KEY,
+    uid INTEGER NOT NULL REFERENCES users(uid),
+    code_hash BYTEA NOT NULL UNIQUE,
+    used_at TIMESTAMPTZ,
+    -- stats
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+CREATE INDEX idx_user_recovery_codes_uid ON user_recovery_codes(uid);
+
+-- Wrong codes count towards the same lockouts as wrong passwords
+ALTER TYPE login_outcome ADD VALUE 'bad_second_factor';
+
+ALTER TABLE user_sessions
+    -- Signed in with a password but not yet a second factor
+    ADD COLUMN second_factor_pending BOOLEAN NOT NULL DEFAULT FALSE,
+    ADD COLUMN second_factor_failures INTEGER NOT NULL DEFAULT 0;
+
+-- Roles above volunteer now need a second factor, which they'll be asked to
+-- set up when they sign back in
+DELETE FROM user_sessions
+WHERE uid IN (SELECT uid FROM users_roles WHERE role <> 'volunteer');
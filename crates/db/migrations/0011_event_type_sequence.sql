@@ -0,0 +1,3 @@
+-- The seeded event types were inserted with explicit ids, leaving the sequence
+-- behind them
+SELECT setval(pg_get_serial_sequence('event_types', 'etid'), MAX(etid)) FROM event_types;
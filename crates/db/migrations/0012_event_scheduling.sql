@@ -0,0 +1,5 @@
+-- Duty leads of shifts of this type must hold a valid issue of this group
+ALTER TABLE event_types ADD COLUMN duty_lead_group INTEGER REFERENCES groups(gid);
+
+-- Rows from before shifts were edited in the app aren't checked
+ALTER TABLE shifts ADD CONSTRAINT shifts_end_after_start CHECK (end_time > start_time) NOT VALID;
@@ -0,0 +1,44 @@
+CREATE TYPE recurrence_frequency AS ENUM ('weekly', 'biweekly', 'monthly_nth_weekday');
+
+-- A repeating event, materialised into events a number of days ahead. The
+-- first occurrence sets the weekday, and for monthly series which week of the
+-- month it falls in.
+CREATE TABLE event_series (
+    esid SERIAL PRIMARY KEY,
+    etid INTEGER NOT NULL REFERENCES event_types(etid),
+    event_name TEXT NOT NULL,
+    organizer_details TEXT,
+    location TEXT,
+    notes TEXT,
+    frequency recurrence_frequency NOT NULL,
+    starts_on DATE NOT NULL,
+    ends_on DATE,
+    -- A shift ending at or before it starts runs past midnight
+    start_time TIME NOT NULL,
+    end_time TIME NOT NULL,
+    duty_lead INTEGER REFERENCES users(uid),
+    horizon_days INTEGER NOT NULL DEFAULT 90 CHECK (horizon_days BETWEEN 1 AND 365),
+    materialised_until DATE,
+    -- stats
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
+    updated_at TIMESTAMPTZ,
+    archived_at TIMESTAMPTZ,
+    CHECK (ends_on IS NULL OR ends_on >= starts_on)
+);
+
+-- Dates a series skips, either planned or from cancelling an occurrence
+CREATE TABLE event_series_exceptions (
+    esid INTEGER NOT NULL REFERENCES event_series(esid),
+    date DATE NOT NULL,
+    PRIMARY KEY (esid, date),
+    -- stats
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
+
+-- Occurrences edited on their own are detached so series edits leave them be
+ALTER TABLE events
+    ADD COLUMN esid INTEGER REFERENCES event_series(esid),
+    ADD COLUMN occurrence_date DATE,
+    ADD COLUMN detached BOOLEAN NOT NULL DEFAULT FALSE;
+CREATE UNIQUE INDEX idx_events_esid_occurrence_date ON events(esid, occurrence_date)
+    WHERE archived_at IS NULL;
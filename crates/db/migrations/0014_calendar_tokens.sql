@@ -0,0 +1,9 @@
+-- Secret links for subscribing to calendar feeds without signing in, stored
+-- as a keyed hash. Each member has at most one.
+CREATE TABLE calendar_tokens (
+    uid INTEGER PRIMARY KEY REFERENCES users(uid),
+    token_hash BYTEA NOT NULL UNIQUE,
+    last_used_at TIMESTAMPTZ,
+    -- stats
+    created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,12 @@
+-- Hours count once the duty lead of their shift, or an admin, approves them
+CREATE TYPE hours_status AS ENUM ('pending', 'approved', 'rejected');
+
+ALTER TABLE user_hours
+    ADD COLUMN status hours_status NOT NULL DEFAULT 'pending',
+    ADD COLUMN reviewed_by INTEGER REFERENCES users(uid),
+    ADD COLUMN reviewed_at TIMESTAMPTZ;
+
+-- Hours logged before approvals were trusted as entered
+UPDATE user_hours SET status = 'approved' WHERE NOT draft;
+
+CREATE INDEX idx_user_hours_pending ON user_hours(sid) WHERE status = 'pending';
@@ -0,0 +1,106 @@
+-- Who changed what. Rows are written by triggers, so every change is recorded
+-- however it's made. The application sets `app.actor` to the signed in member
+-- on each connection it uses; changes without one were made by the system.
+CREATE TYPE audit_action AS ENUM ('insert', 'update', 'delete');
+
+CREATE TABLE audit_log (
+    alid BIGSERIAL PRIMARY KEY,
+    uid INTEGER REFERENCES users(uid),
+    action audit_action NOT NULL,
+    table_name TEXT NOT NULL,
+    -- The primary key, with the columns of composite ones separated by commas
+    target_id TEXT NOT NULL,
+    -- For updates, only the columns which changed
+    old_values JSONB,
+    new_values JSONB,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX idx_audit_log_created_at ON audit_log(created_at);
+CREATE INDEX idx_audit_log_uid ON audit_log(uid);
+CREATE INDEX idx_audit_log_table ON audit_log(table_name, target_id);
+
+-- Takes the primary key columns as arguments
+CREATE FUNCTION audit_change() RETURNS TRIGGER AS $$
+DECLARE
+    -- Secrets aren't copied into the log, and bookkeeping isn't worth a row
+    ignored TEXT[] := ARRAY[
+        'password_hash', 'totp_secret', 'totp_last_step', 'last_login_at',
+        'updated_at', 'hours_prefilled_at', 'materialised_until'
+    ];
+    old_row JSONB;
+    new_row JSONB;
+    old_values JSONB;
+    new_values JSONB;
+    target TEXT;
+BEGIN
+    IF TG_OP <> 'INSERT' THEN
+        old_row := to_jsonb(OLD) - ignored;
+    END IF;
+    IF TG_OP <> 'DELETE' THEN
+        new_row := to_jsonb(NEW) - ignored;
+    END IF;
+
+    IF TG_OP = 'UPDATE' THEN
+        SELECT jsonb_object_agg(key, value) INTO old_values
+        FROM jsonb_each(old_row)
+        WHERE value IS DISTINCT FROM new_row -> key;
+
+        IF old_values IS NULL THEN
+            RETURN NULL;
+        END IF;
+
+        SELECT jsonb_object_agg(key, value) INTO new_values
+        FROM jsonb_each(new_row)
+        WHERE value IS DISTINCT FROM old_row -> key;
+    ELSE
+        old_values := old_row;
+        new_values := new_row;
+    END IF;
+
+    SELECT string_agg(COALESCE(new_row, old_row) ->> column_name, ',' ORDER BY position)
+    INTO target
+    FROM unnest(TG_ARGV) WITH ORDINALITY AS key_columns(column_name, position);
+
+    INSERT INTO audit_log (uid, action, table_name, target_id, old_values, new_values)
+    VALUES (
+        NULLIF(current_setting('app.actor', TRUE), '')::INTEGER,
+        lower(TG_OP)::audit_action,
+        TG_TABLE_NAME,
+        target,
+        old_values,
+        new_values
+    );
+
+    RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON users
+    FOR EACH ROW EXECUTE FUNCTION audit_change('uid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON users_roles
+    FOR EACH ROW EXECUTE FUNCTION audit_change('uid', 'role');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON groups
+    FOR EACH ROW EXECUTE FUNCTION audit_change('gid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON users_groups
+    FOR EACH ROW EXECUTE FUNCTION audit_change('ug_id');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON event_types
+    FOR EACH ROW EXECUTE FUNCTION audit_change('etid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON event_series
+    FOR EACH ROW EXECUTE FUNCTION audit_change('esid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON event_series_exceptions
+    FOR EACH ROW EXECUTE FUNCTION audit_change('esid', 'date');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON events
+    FOR EACH ROW EXECUTE FUNCTION audit_change('eid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON shifts
+    FOR EACH ROW EXECUTE FUNCTION audit_change('sid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON users_shifts
+    FOR EACH ROW EXECUTE FUNCTION audit_change('uid', 'sid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON vehicles
+    FOR EACH ROW EXECUTE FUNCTION audit_change('vid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON shifts_vehicles
+    FOR EACH ROW EXECUTE FUNCTION audit_change('sid', 'vid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON user_hours
+    FOR EACH ROW EXECUTE FUNCTION audit_change('hid');
+CREATE TRIGGER audit AFTER INSERT OR UPDATE OR DELETE ON comments
+    FOR EACH ROW EXECUTE FUNCTION audit_change('cid');
@@ -0,0 +1,5 @@
+ALTER TYPE user_token_purpose ADD VALUE 'email_change';
+
+-- The new address an email change token confirms, which only replaces the
+-- member's email once they follow the link sent to it
+ALTER TABLE user_tokens ADD COLUMN email TEXT;
@@ -0,0 +1,22 @@
+-- Whether `reviewer` may approve or reject the hours: the duty lead of their
+-- shift can, and admins can when there's no duty lead still around to. No one
+-- reviews their own, unless they're the only admin.
+CREATE FUNCTION can_review_hours(reviewer INTEGER, is_admin BOOLEAN, hours_id INTEGER)
+RETURNS BOOLEAN AS $$
+    SELECT COALESCE(
+        (s.duty_lead = reviewer AND h.uid <> reviewer)
+        OR (is_admin
+            AND (s.duty_lead IS NULL OR s.duty_lead = h.uid OR lead.archived_at IS NOT NULL)
+            AND (h.uid <> reviewer OR NOT EXISTS (
+                SELECT 1
+                FROM users_roles r
+                JOIN users o ON o.uid = r.uid
+                WHERE r.role = 'admin' AND r.uid <> reviewer AND o.archived_at IS NULL
+            ))),
+        FALSE
+    )
+    FROM user_hours h
+    LEFT JOIN shifts s ON s.sid = h.sid
+    LEFT JOIN users lead ON lead.uid = s.duty_lead
+    WHERE h.hid = hours_id
+$$ LANGUAGE sql STABLE;
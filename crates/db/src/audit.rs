@@ -0,0 +1,142 @@
+use std::future::Future;
+
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{PgConnection, PgPool};
+use types::Error;
+
+tokio::task_local! {
+    static ACTOR: i32;
+}
+
+/// Runs `future` with the changes it makes recorded as made by `uid`
+pub async fn acting_as<F: Future>(uid: i32, future: F) -> F::Output {
+    ACTOR.scope(uid, future).await
+}
+
+/// Who is acting, as the audit triggers read it from `app.actor`
+pub(crate) fn actor() -> String {
+    ACTOR.try_with(i32::to_string).unwrap_or_default()
+}
+
+/// Tells the audit triggers who is acting, each time a connection is handed
+/// out of the pool. This is the only round trip made then, as it also shows
+/// the connection is still alive.
+pub(crate) async fn set_actor(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    sqlx::query!("SELECT set_config('app.actor', $1, FALSE)", actor())
+        .fetch_one(conn)
+        .await?;
+
+    Ok(())
+}
+
+/// The record of changes made, written by the `audit_change` triggers
+#[derive(Clone)]
+pub struct AuditLog {
+    pool: PgPool,
+}
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "audit_action", rename_all = "snake_case")]
+pub enum AuditAction {
+    Insert,
+    Update,
+    Delete,
+}
+
+#[derive(Debug)]
+pub struct AuditEntry {
+    pub alid: i64,
+    /// `None` for changes made by the system, like scheduled jobs
+    pub uid: Option<i32>,
+    pub display_name: Option<String>,
+    pub action: AuditAction,
+    pub table_name: String,
+    pub target_id: String,
+    /// JSON objects of the row's columns. Updates only have the ones which
+    /// changed.
+    pub old_values: Option<String>,
+    pub new_values: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug, Default)]
+pub struct AuditFilter {
+    pub uid: Option<i32>,
+    pub table_name: Option<String>,
+    pub from: Option<NaiveDate>,
+    /// Inclusive
+    pub to: Option<NaiveDate>,
+    /// Only entries older than this one, for paging
+    pub before: Option<i64>,
+}
+
+/// A member who has made changes
+#[derive(Debug)]
+pub struct AuditActor {
+    pub uid: i32,
+    pub display_name: String,
+}
+
+impl AuditLog {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Newest first
+    pub async fn list(&self, filter: &AuditFilter, limit: i64) -> Result<Vec<AuditEntry>, Error> {
+        let result = sqlx::query_as!(
+            AuditEntry,
+            r#"SELECT a.alid, a.uid, u.display_name AS "display_name?",
+                a.action AS "action: AuditAction", a.table_name, a.target_id,
+                a.old_values::TEXT, a.new_values::TEXT,
+                a.created_at::TIMESTAMP AS "created_at!"
+            FROM audit_log a
+            LEFT JOIN users u ON u.uid = a.uid
+            WHERE ($1::INTEGER IS NULL OR a.uid = $1)
+                AND ($2::TEXT IS NULL OR a.table_name = $2)
+                AND ($3::DATE IS NULL OR a.created_at >= $3::DATE)
+                AND ($4::DATE IS NULL OR a.created_at < $4::DATE + 1)
+                AND ($5::BIGINT IS NULL OR a.alid < $5)
+            ORDER BY a.alid DESC
+            LIMIT $6"#,
+            filter.uid,
+            filter.table_name,
+            filter.from,
+            filter.to,
+            filter.before,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_actors(&self) -> Result<Vec<AuditActor>, Error> {
+        let result = sqlx::query_as!(
+            AuditActor,
+            "SELECT u.uid, u.display_name
+            FROM users u
+            WHERE EXISTS (SELECT 1 FROM audit_log a WHERE a.uid = u.uid)
+            ORDER BY u.display_name, u.uid"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// The tables changes are recorded for
+    pub async fn list_tables(&self) -> Result<Vec<String>, Error> {
+        let result = sqlx::query_scalar!(
+            r#"SELECT DISTINCT event_object_table::TEXT AS "table_name!"
+            FROM information_schema.triggers
+            WHERE trigger_name = 'audit' AND trigger_schema = current_schema()
+            ORDER BY 1"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use types::Error;
+
+/// Tokens for the calendar feeds, which calendar apps fetch without a session
+#[derive(Clone)]
+pub struct CalendarTokens {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct CalendarToken {
+    pub created_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl CalendarTokens {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, uid: i32) -> Result<Option<CalendarToken>, Error> {
+        let result = sqlx::query_as!(
+            CalendarToken,
+            r#"SELECT created_at AS "created_at!", last_used_at
+            FROM calendar_tokens
+            WHERE uid = $1"#,
+            uid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Issues the user's token, replacing any they had before
+    pub async fn issue(&self, uid: i32, token_hash: &[u8]) -> Result<(), Error> {
+        sqlx::query!(
+            "INSERT INTO calendar_tokens (uid, token_hash)
+            VALUES ($1, $2)
+            ON CONFLICT (uid) DO UPDATE
+            SET token_hash = EXCLUDED.token_hash, last_used_at = NULL,
+                created_at = CURRENT_TIMESTAMP",
+            uid,
+            token_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// The user a token belongs to, noting that it was used. Archived members'
+    /// tokens stop working.
+    pub async fn find(&self, token_hash: &[u8]) -> Result<Option<i32>, Error> {
+        let result = sqlx::query_scalar!(
+            "UPDATE calendar_tokens ct
+            SET last_used_at = CURRENT_TIMESTAMP
+            FROM users u
+            WHERE u.uid = ct.uid AND ct.token_hash = $1 AND u.archived_at IS NULL
+            RETURNING ct.uid",
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn revoke(&self, uid: i32) -> Result<(), Error> {
+        sqlx::query!("DELETE FROM calendar_tokens WHERE uid = $1", uid)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use types::Error;
+
+const MAX_COMMENT_LEN: usize = 2000;
+
+#[derive(Clone)]
+pub struct Comments {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct Comment {
+    pub cid: i32,
+    pub eid: i32,
+    pub sid: Option<i32>,
+    pub shift_start: Option<NaiveDateTime>,
+    /// `None` for comments posted before authors were recorded
+    pub uid: Option<i32>,
+    pub author: Option<String>,
+    pub comment_text: String,
+    pub created_at: NaiveDateTime,
+    pub edited: bool,
+}
+
+impl Comments {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_for_event(&self, eid: i32) -> Result<Vec<Comment>, Error> {
+        let result = sqlx::query_as!(
+            Comment,
+            r#"SELECT c.cid, c.eid, c.sid, s.start_time AS "shift_start?", c.uid,
+                u.display_name AS "author?", c.comment_text,
+                c.created_at::TIMESTAMP AS "created_at!",
+                c.updated_at IS NOT NULL AS "edited!"
+            FROM comments c
+            LEFT JOIN users u ON u.uid = c.uid
+            LEFT JOIN shifts s ON s.sid = c.sid
+            WHERE c.eid = $1 AND c.archived_at IS NULL
+            ORDER BY c.created_at, c.cid"#,
+            eid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Posts a comment on the event, optionally scoped to one of its shifts
+    pub async fn post(
+        &self,
+        uid: i32,
+        eid: i32,
+        sid: Option<i32>,
+        comment_text: &str,
+    ) -> Result<i32, Error> {
+        let comment_text = validate_comment(comment_text)?;
+
+        sqlx::query!(
+            "SELECT eid FROM events WHERE eid = $1 AND archived_at IS NULL",
+            eid
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if let Some(sid) = sid {
+            sqlx::query!(
+                "SELECT sid FROM shifts WHERE sid = $1 AND eid = $2",
+                sid,
+                eid
+            )
+            .fetch_optional(&self.pool)
+            .await?
+            .ok_or_else(|| {
+                Error::unprocessable_entity([("sid", "This shift is not part of the event")])
+            })?;
+        }
+
+        let cid = sqlx::query_scalar!(
+            "INSERT INTO comments (eid, sid, uid, comment_text)
+            VALUES ($1, $2, $3, $4)
+            RETURNING cid",
+            eid,
+            sid,
+            uid,
+            comment_text
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(cid)
+    }
+
+    /// Edits one of the user's own comments, returning the event it is on
+    pub async fn edit(&self, uid: i32, cid: i32, comment_text: &str) -> Result<i32, Error> {
+        let comment_text = validate_comment(comment_text)?;
+
+        let eid = sqlx::query_scalar!(
+            "UPDATE comments
+            SET comment_text = $3, updated_at = CURRENT_TIMESTAMP
+            WHERE cid = $1 AND uid = $2 AND archived_at IS NULL
+            RETURNING eid",
+            cid,
+            uid,
+            comment_text
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        Ok(eid)
+    }
+
+    /// Archives a comment, returning the event it was on. Only the author can
+    /// archive a comment unless `moderator` is set.
+    pub async fn archive(&self, uid: i32, cid: i32, moderator: bool) -> Result<i32, Error> {
+        let eid = sqlx::query_scalar!(
+            "UPDATE comments
+            SET archived_at = CURRENT_TIMESTAMP
+            WHERE cid = $1 AND (uid = $2 OR $3) AND archived_at IS NULL
+            RETURNING eid",
+            cid,
+            uid,
+            moderator
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        Ok(eid)
+    }
+}
+
+fn validate_comment(comment_text: &str) -> Result<&str, Error> {
+    let comment_text = comment_text.trim();
+
+    if comment_text.is_empty() {
+        return Err(Error::unprocessable_entity([(
+            "comment_text",
+            "Comment can't be empty",
+        )]));
+    }
+
+    if comment_text.chars().count() > MAX_COMMENT_LEN {
+        return Err(Error::unprocessable_entity([(
+            "comment_text",
+            format!("Comments are limited to {MAX_COMMENT_LEN} characters"),
+        )]));
+    }
+
+    Ok(comment_text)
+}
@@ -0,0 +1,936 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::{PgPool, Postgres, Transaction};
+use types::Error;
+
+#[derive(Clone)]
+pub struct Events {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct EventType {
+    pub etid: i32,
+    pub name: String,
+    pub colour: Option<String>,
+    pub event_level_signup: bool,
+    pub hours_require_linked_signup: bool,
+    pub description: Option<String>,
+    /// Duty leads of this type's shifts must hold a valid issue of this group
+    pub duty_lead_group: Option<i32>,
+    pub duty_lead_group_name: Option<String>,
+    pub archived: bool,
+}
+
+#[derive(Debug)]
+pub struct NewEventType {
+    pub name: String,
+    pub colour: Option<String>,
+    pub event_level_signup: bool,
+    pub hours_require_linked_signup: bool,
+    pub description: Option<String>,
+    pub duty_lead_group: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct EventSummary {
+    pub eid: i32,
+    pub event_name: String,
+    pub location: Option<String>,
+    pub type_name: String,
+    pub colour: Option<String>,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub shift_count: i64,
+}
+
+#[derive(Debug)]
+pub struct Event {
+    pub eid: i32,
+    pub etid: i32,
+    pub event_name: String,
+    pub organizer_details: Option<String>,
+    pub location: Option<String>,
+    pub notes: Option<String>,
+    pub type_name: String,
+    pub colour: Option<String>,
+    pub event_level_signup: bool,
+    /// The series this is an occurrence of
+    pub esid: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct NewEvent {
+    pub etid: i32,
+    pub event_name: String,
+    pub organizer_details: Option<String>,
+    pub location: Option<String>,
+    pub notes: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct NewShift {
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub duty_lead: Option<i32>,
+}
+
+/// An event to import along with its shifts
+#[derive(Debug)]
+pub struct ImportedEvent {
+    pub event: NewEvent,
+    pub shifts: Vec<NewShift>,
+}
+
+/// A member who can lead shifts of an event type
+#[derive(Debug)]
+pub struct DutyLeadCandidate {
+    pub uid: i32,
+    pub display_name: String,
+}
+
+#[derive(Debug)]
+pub struct Shift {
+    pub sid: i32,
+    pub eid: i32,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub duty_lead: Option<i32>,
+    pub duty_lead_name: Option<String>,
+    pub ended: bool,
+}
+
+/// A shift as it appears in a calendar feed
+#[derive(Debug)]
+pub struct CalendarShift {
+    pub sid: i32,
+    pub eid: i32,
+    pub event_name: String,
+    pub type_name: String,
+    pub location: Option<String>,
+    pub notes: Option<String>,
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    pub duty_lead_name: Option<String>,
+}
+
+impl Events {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_event_types(&self) -> Result<Vec<EventType>, Error> {
+        let result = sqlx::query_as!(
+            EventType,
+            r#"SELECT et.etid, et.name, et.colour, et.event_level_signup, et.hours_require_linked_signup,
+                et.description, et.duty_lead_group, g.name AS "duty_lead_group_name?",
+                et.archived_at IS NOT NULL AS "archived!"
+            FROM event_types et
+            LEFT JOIN groups g ON g.gid = et.duty_lead_group
+            WHERE et.archived_at IS NULL
+            ORDER BY et.etid"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Every event type including archived ones, for managing them
+    pub async fn list_all_event_types(&self) -> Result<Vec<EventType>, Error> {
+        let result = sqlx::query_as!(
+            EventType,
+            r#"SELECT et.etid, et.name, et.colour, et.event_level_signup, et.hours_require_linked_signup,
+                et.description, et.duty_lead_group, g.name AS "duty_lead_group_name?",
+                et.archived_at IS NOT NULL AS "archived!"
+            FROM event_types et
+            LEFT JOIN groups g ON g.gid = et.duty_lead_group
+            ORDER BY et.archived_at IS NOT NULL, et.etid"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn create_event_type(&self, event_type: &NewEventType) -> Result<i32, Error> {
+        let etid = sqlx::query_scalar!(
+            "INSERT INTO event_types
+                (name, colour, event_level_signup, hours_require_linked_signup, description,
+                    duty_lead_group)
+            VALUES ($1, $2, $3, $4, $5, $6)
+            RETURNING etid",
+            event_type.name,
+            event_type.colour,
+            event_type.event_level_signup,
+            event_type.hours_require_linked_signup,
+            event_type.description,
+            event_type.duty_lead_group
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(event_type_constraint_error)?;
+
+        Ok(etid)
+    }
+
+    pub async fn update_event_type(
+        &self,
+        etid: i32,
+        event_type: &NewEventType,
+    ) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE event_types
+            SET name = $2, colour = $3, event_level_signup = $4,
+                hours_require_linked_signup = $5, description = $6, duty_lead_group = $7,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE etid = $1",
+            etid,
+            event_type.name,
+            event_type.colour,
+            event_type.event_level_signup,
+            event_type.hours_require_linked_signup,
+            event_type.description,
+            event_type.duty_lead_group
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(event_type_constraint_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_event_type_archived(&self, etid: i32, archived: bool) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE event_types
+            SET archived_at = CASE WHEN $2 THEN CURRENT_TIMESTAMP END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE etid = $1 AND (archived_at IS NOT NULL) <> $2",
+            etid,
+            archived
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Events with at least one shift that has not yet ended, ordered by their
+    /// next shift
+    pub async fn list_upcoming(&self, etid: Option<i32>) -> Result<Vec<EventSummary>, Error> {
+        let result = sqlx::query_as!(
+            EventSummary,
+            r#"SELECT e.eid, e.event_name, e.location, et.name AS type_name, et.colour,
+                MIN(s.start_time) FILTER (WHERE s.end_time >= LOCALTIMESTAMP) AS "start_time!",
+                MAX(s.end_time) AS "end_time!",
+                COUNT(s.sid) AS "shift_count!"
+            FROM events e
+            JOIN event_types et ON et.etid = e.etid
+            JOIN shifts s ON s.eid = e.eid
+            WHERE e.archived_at IS NULL AND ($1::INTEGER IS NULL OR e.etid = $1)
+            GROUP BY e.eid, et.etid
+            HAVING MAX(s.end_time) >= LOCALTIMESTAMP
+            ORDER BY "start_time!", e.eid"#,
+            etid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Shifts for a calendar feed: those `uid` holds, or every shift if `None`.
+    /// Shifts which ended in the last 30 days are kept so subscribed calendars
+    /// don't lose them as soon as they're over.
+    pub async fn list_calendar_shifts(
+        &self,
+        uid: Option<i32>,
+    ) -> Result<Vec<CalendarShift>, Error> {
+        let result = sqlx::query_as!(
+            CalendarShift,
+            r#"SELECT s.sid, e.eid, e.event_name, et.name AS type_name, e.location, e.notes,
+                s.start_time, s.end_time, u.display_name AS "duty_lead_name?"
+            FROM shifts s
+            JOIN events e ON e.eid = s.eid
+            JOIN event_types et ON et.etid = e.etid
+            LEFT JOIN users u ON u.uid = s.duty_lead
+            WHERE e.archived_at IS NULL
+                AND s.end_time >= LOCALTIMESTAMP - INTERVAL '30 days'
+                AND (
+                    $1::INTEGER IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM users_shifts us
+                        WHERE us.sid = s.sid AND us.uid = $1 AND us.archived_at IS NULL
+                    )
+                )
+            ORDER BY s.start_time, s.sid"#,
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn get_event(&self, eid: i32) -> Result<Option<Event>, Error> {
+        let result = sqlx::query_as!(
+            Event,
+            "SELECT e.eid, e.etid, e.event_name, e.organizer_details, e.location, e.notes,
+                et.name AS type_name, et.colour, et.event_level_signup, e.esid
+            FROM events e
+            JOIN event_types et ON et.etid = e.etid
+            WHERE e.eid = $1 AND e.archived_at IS NULL",
+            eid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn get_shift(&self, sid: i32) -> Result<Option<Shift>, Error> {
+        let result = sqlx::query_as!(
+            Shift,
+            r#"SELECT s.sid, s.eid, s.start_time, s.end_time, s.duty_lead,
+                u.display_name AS "duty_lead_name?",
+                s.end_time < LOCALTIMESTAMP AS "ended!"
+            FROM shifts s
+            LEFT JOIN users u ON u.uid = s.duty_lead
+            WHERE s.sid = $1"#,
+            sid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_shifts(&self, eid: i32) -> Result<Vec<Shift>, Error> {
+        let result = sqlx::query_as!(
+            Shift,
+            r#"SELECT s.sid, s.eid, s.start_time, s.end_time, s.duty_lead,
+                u.display_name AS "duty_lead_name?",
+                s.end_time < LOCALTIMESTAMP AS "ended!"
+            FROM shifts s
+            LEFT JOIN users u ON u.uid = s.duty_lead
+            WHERE s.eid = $1
+            ORDER BY s.start_time, s.sid"#,
+            eid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn create_event(&self, event: &NewEvent) -> Result<i32, Error> {
+        let mut tx = self.pool.begin().await?;
+        active_event_type(&mut tx, event.etid).await?;
+
+        let eid = sqlx::query_scalar!(
+            "INSERT INTO events (etid, event_name, organizer_details, location, notes)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING eid",
+            event.etid,
+            event.event_name,
+            event.organizer_details,
+            event.location,
+            event.notes
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(eid)
+    }
+
+    /// Which of the `(name, date)` pairs already have an event, meaning one of
+    /// the same name, ignoring case, with a shift starting that day
+    pub async fn find_duplicates(
+        &self,
+        events: &[(String, NaiveDate)],
+    ) -> Result<Vec<(String, NaiveDate)>, Error> {
+        let (names, dates): (Vec<_>, Vec<_>) = events.iter().cloned().unzip();
+
+        let result = sqlx::query!(
+            r#"SELECT DISTINCT i.name AS "name!", i.date AS "date!"
+            FROM UNNEST($1::TEXT[], $2::DATE[]) AS i(name, date)
+            JOIN events e ON LOWER(e.event_name) = LOWER(i.name) AND e.archived_at IS NULL
+            JOIN shifts s ON s.eid = e.eid AND s.start_time::DATE = i.date"#,
+            &names,
+            &dates
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result.into_iter().map(|row| (row.name, row.date)).collect())
+    }
+
+    /// Creates the events and their shifts together, so either all of them
+    /// are imported or none are
+    pub async fn import(&self, events: &[ImportedEvent]) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        for ImportedEvent { event, shifts } in events {
+            active_event_type(&mut tx, event.etid).await?;
+
+            let eid = sqlx::query_scalar!(
+                "INSERT INTO events (etid, event_name, organizer_details, location, notes)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING eid",
+                event.etid,
+                event.event_name,
+                event.organizer_details,
+                event.location,
+                event.notes
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            for shift in shifts {
+                check_shift(&mut tx, event.etid, shift, None).await?;
+
+                sqlx::query!(
+                    "INSERT INTO shifts (eid, start_time, end_time, duty_lead)
+                    VALUES ($1, $2, $3, $4)",
+                    eid,
+                    shift.start_time,
+                    shift.end_time,
+                    shift.duty_lead
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(events.len() as u64)
+    }
+
+    /// Changing the type re-checks the duty leads of the event's shifts
+    /// against the new type's requirement. Occurrences of a series edited this
+    /// way are detached from it.
+    pub async fn update_event(&self, eid: i32, event: &NewEvent) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        let current_etid = lock_event(&mut tx, eid).await?;
+        active_event_type(&mut tx, event.etid).await?;
+
+        sqlx::query!(
+            "UPDATE events
+            SET etid = $2, event_name = $3, organizer_details = $4, location = $5, notes = $6,
+                detached = esid IS NOT NULL, updated_at = CURRENT_TIMESTAMP
+            WHERE eid = $1 AND archived_at IS NULL",
+            eid,
+            event.etid,
+            event.event_name,
+            event.organizer_details,
+            event.location,
+            event.notes
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if event.etid != current_etid {
+            let leads = sqlx::query_scalar!(
+                r#"SELECT DISTINCT duty_lead AS "duty_lead!"
+                FROM shifts
+                WHERE eid = $1 AND duty_lead IS NOT NULL AND end_time >= LOCALTIMESTAMP"#,
+                eid
+            )
+            .fetch_all(&mut *tx)
+            .await?;
+
+            for uid in leads {
+                check_duty_lead(&mut tx, event.etid, uid).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// People signed up for an event whose type uses event level signup are
+    /// signed up for the new shifts too, if those don't overlap their others
+    pub async fn add_shifts(&self, eid: i32, shifts: &[NewShift]) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        let etid = lock_event(&mut tx, eid).await?;
+        detach(&mut tx, eid).await?;
+
+        let event_level_signup = sqlx::query_scalar!(
+            "SELECT event_level_signup FROM event_types WHERE etid = $1",
+            etid
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if event_level_signup {
+            // Signing up locks the shifts, so signups in progress are seen
+            sqlx::query!("SELECT sid FROM shifts WHERE eid = $1 FOR UPDATE", eid)
+                .fetch_all(&mut *tx)
+                .await?;
+        }
+
+        for shift in shifts {
+            check_shift(&mut tx, etid, shift, None).await?;
+
+            let sid = sqlx::query_scalar!(
+                "INSERT INTO shifts (eid, start_time, end_time, duty_lead)
+                VALUES ($1, $2, $3, $4)
+                RETURNING sid",
+                eid,
+                shift.start_time,
+                shift.end_time,
+                shift.duty_lead
+            )
+            .fetch_one(&mut *tx)
+            .await?;
+
+            if event_level_signup {
+                sqlx::query!(
+                    "INSERT INTO users_shifts (uid, sid)
+                    SELECT DISTINCT us.uid, $2::INTEGER
+                    FROM users_shifts us
+                    JOIN shifts s ON s.sid = us.sid
+                    WHERE s.eid = $1 AND s.sid <> $2 AND us.archived_at IS NULL",
+                    eid,
+                    sid
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                check_signed_up(&mut tx, sid, shift).await?;
+            }
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// New times are refused if they'd overlap another shift someone signed
+    /// up for this one is on
+    pub async fn update_shift(&self, sid: i32, shift: &NewShift) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let current = sqlx::query!(
+            "SELECT eid, start_time, end_time, duty_lead FROM shifts WHERE sid = $1",
+            sid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+        let etid = lock_event(&mut tx, current.eid).await?;
+        detach(&mut tx, current.eid).await?;
+
+        check_shift(&mut tx, etid, shift, current.duty_lead).await?;
+
+        if (shift.start_time, shift.end_time) != (current.start_time, current.end_time) {
+            check_signed_up(&mut tx, sid, shift).await?;
+        }
+
+        sqlx::query!(
+            "UPDATE shifts
+            SET start_time = $2, end_time = $3, duty_lead = $4, updated_at = CURRENT_TIMESTAMP
+            WHERE sid = $1",
+            sid,
+            shift.start_time,
+            shift.end_time,
+            shift.duty_lead
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Only shifts nobody is signed up for or has logged hours against can be
+    /// deleted
+    pub async fn delete_shift(&self, sid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let shift = sqlx::query!(
+            r#"SELECT eid,
+                EXISTS (
+                    SELECT 1 FROM users_shifts WHERE sid = $1 AND archived_at IS NULL
+                ) AS "signed_up!",
+                EXISTS (SELECT 1 FROM user_hours WHERE sid = $1) AS "hours_logged!"
+            FROM shifts
+            WHERE sid = $1
+            FOR UPDATE"#,
+            sid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        if shift.signed_up {
+            return Err(Error::unprocessable_entity([(
+                "sid",
+                "Volunteers are signed up for this shift",
+            )]));
+        }
+        if shift.hours_logged {
+            return Err(Error::unprocessable_entity([(
+                "sid",
+                "Hours have been logged against this shift",
+            )]));
+        }
+
+        detach(&mut tx, shift.eid).await?;
+
+        sqlx::query!("DELETE FROM users_shifts WHERE sid = $1", sid)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM shifts_vehicles WHERE sid = $1", sid)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("UPDATE comments SET sid = NULL WHERE sid = $1", sid)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("DELETE FROM shifts WHERE sid = $1", sid)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Cancels an event which hasn't ended. An occurrence of a series is also
+    /// recorded as an exception so it isn't materialised again.
+    pub async fn cancel(&self, eid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_event(&mut tx, eid).await?;
+
+        check_no_signups(&mut tx, eid, "eid").await?;
+
+        let event = sqlx::query!(
+            r#"UPDATE events e
+            SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE eid = $1
+                AND NOT EXISTS (
+                    SELECT 1 FROM shifts s WHERE s.eid = e.eid AND s.end_time < LOCALTIMESTAMP
+                )
+            RETURNING esid, occurrence_date"#,
+            eid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| {
+            Error::unprocessable_entity([(
+                "eid",
+                "Events which have already run can't be cancelled",
+            )])
+        })?;
+
+        if let (Some(esid), Some(date)) = (event.esid, event.occurrence_date) {
+            sqlx::query!(
+                "INSERT INTO event_series_exceptions (esid, date)
+                VALUES ($1, $2)
+                ON CONFLICT DO NOTHING",
+                esid,
+                date
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Copies the event and its shifts so the first shift falls on
+    /// `first_day`, keeping their times and spacing. Duty leads aren't copied
+    /// since they signed up for the original dates.
+    pub async fn duplicate_event(&self, eid: i32, first_day: NaiveDate) -> Result<i32, Error> {
+        let mut tx = self.pool.begin().await?;
+        let etid = lock_event(&mut tx, eid).await?;
+        active_event_type(&mut tx, etid).await?;
+
+        let new_eid = sqlx::query_scalar!(
+            "INSERT INTO events (etid, event_name, organizer_details, location, notes)
+            SELECT etid, event_name, organizer_details, location, notes
+            FROM events
+            WHERE eid = $1
+            RETURNING eid",
+            eid
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "WITH offset_days AS (
+                SELECT $3::DATE - MIN(start_time)::DATE AS days FROM shifts WHERE eid = $1
+            )
+            INSERT INTO shifts (eid, start_time, end_time)
+            SELECT $2, s.start_time + o.days * INTERVAL '1 day', s.end_time + o.days * INTERVAL '1 day'
+            FROM shifts s, offset_days o
+            WHERE s.eid = $1
+            ORDER BY s.start_time, s.sid",
+            eid,
+            new_eid,
+            first_day
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(new_eid)
+    }
+
+    /// Active members holding the group duty leads of the event type need, or
+    /// every active member if it doesn't need one
+    pub async fn list_duty_lead_candidates(
+        &self,
+        etid: i32,
+    ) -> Result<Vec<DutyLeadCandidate>, Error> {
+        let result = sqlx::query_as!(
+            DutyLeadCandidate,
+            "SELECT u.uid, u.display_name
+            FROM users u
+            JOIN event_types et ON et.etid = $1
+            WHERE u.archived_at IS NULL
+                AND (
+                    et.duty_lead_group IS NULL
+                    OR EXISTS (
+                        SELECT 1 FROM users_groups_current ugc
+                        WHERE ugc.uid = u.uid AND ugc.gid = et.duty_lead_group AND ugc.valid
+                    )
+                )
+            ORDER BY u.display_name, u.uid",
+            etid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
+
+fn event_type_constraint_error(e: sqlx::Error) -> Error {
+    match e {
+        sqlx::Error::Database(ref db) => match db.constraint() {
+            Some("event_types_name_key") => Error::unprocessable_entity([(
+                "name",
+                "An event type with this name already exists",
+            )]),
+            Some("event_types_colour_check") => {
+                Error::unprocessable_entity([("colour", "Choose a colour")])
+            }
+            Some("event_types_duty_lead_group_fkey") => {
+                Error::unprocessable_entity([("duty_lead_group", "Unknown group")])
+            }
+            _ => e.into(),
+        },
+        e => e.into(),
+    }
+}
+
+/// Stops series edits from overwriting changes made to this occurrence
+async fn detach(tx: &mut Transaction<'_, Postgres>, eid: i32) -> Result<(), Error> {
+    sqlx::query!(
+        "UPDATE events SET detached = TRUE WHERE eid = $1 AND esid IS NOT NULL",
+        eid
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+/// Locks the event against concurrent changes to its shifts, returning its type
+async fn lock_event(tx: &mut Transaction<'_, Postgres>, eid: i32) -> Result<i32, Error> {
+    let etid = sqlx::query_scalar!(
+        "SELECT etid FROM events WHERE eid = $1 AND archived_at IS NULL FOR UPDATE",
+        eid
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    Ok(etid)
+}
+
+pub(crate) async fn active_event_type(
+    tx: &mut Transaction<'_, Postgres>,
+    etid: i32,
+) -> Result<(), Error> {
+    sqlx::query_scalar!(
+        "SELECT etid FROM event_types WHERE etid = $1 AND archived_at IS NULL",
+        etid
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| Error::unprocessable_entity([("etid", "Unknown event type")]))?;
+
+    Ok(())
+}
+
+/// `current_lead` is the shift's lead before the change, who can stay on even
+/// if they're no longer qualified
+async fn check_shift(
+    tx: &mut Transaction<'_, Postgres>,
+    etid: i32,
+    shift: &NewShift,
+    current_lead: Option<i32>,
+) -> Result<(), Error> {
+    if shift.end_time <= shift.start_time {
+        return Err(Error::unprocessable_entity([(
+            "end_time",
+            "The shift must end after it starts",
+        )]));
+    }
+
+    if let Some(uid) = shift.duty_lead
+        && shift.duty_lead != current_lead
+    {
+        check_duty_lead(tx, etid, uid).await?;
+    }
+
+    Ok(())
+}
+
+/// Makes sure moving shift `sid` to the new times doesn't overlap it with
+/// another shift one of its volunteers is signed up for, like signing up does
+async fn check_signed_up(
+    tx: &mut Transaction<'_, Postgres>,
+    sid: i32,
+    shift: &NewShift,
+) -> Result<(), Error> {
+    // Signups lock the member, so nobody signs up for an overlapping shift
+    // while this one moves
+    sqlx::query!(
+        "SELECT u.uid
+        FROM users u
+        JOIN users_shifts us ON us.uid = u.uid
+        WHERE us.sid = $1 AND us.archived_at IS NULL
+        ORDER BY u.uid
+        FOR UPDATE OF u",
+        sid
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let overlap = sqlx::query!(
+        "SELECT u.display_name, e.event_name, s.start_time
+        FROM users_shifts us
+        JOIN users u ON u.uid = us.uid
+        JOIN users_shifts other ON other.uid = us.uid
+            AND other.sid <> us.sid
+            AND other.archived_at IS NULL
+        JOIN shifts s ON s.sid = other.sid
+        JOIN events e ON e.eid = s.eid
+        JOIN shifts t ON t.sid = us.sid
+        JOIN events te ON te.eid = t.eid
+        JOIN event_types tet ON tet.etid = te.etid
+        WHERE us.sid = $1
+            AND us.archived_at IS NULL
+            AND e.archived_at IS NULL
+            AND NOT (s.eid = t.eid AND tet.event_level_signup)
+            AND s.start_time < $3
+            AND $2 < s.end_time
+        ORDER BY s.start_time
+        LIMIT 1",
+        sid,
+        shift.start_time,
+        shift.end_time
+    )
+    .fetch_optional(&mut **tx)
+    .await?;
+
+    if let Some(overlap) = overlap {
+        return Err(Error::unprocessable_entity([(
+            "start_time",
+            format!(
+                "Would overlap {}'s shift at {} on {}",
+                overlap.display_name,
+                overlap.event_name,
+                overlap.start_time.format("%b %-d %H:%M")
+            ),
+        )]));
+    }
+
+    Ok(())
+}
+
+/// Refuses to take away an event people are signed up for, naming them so
+/// they can be asked to withdraw
+pub(crate) async fn check_no_signups(
+    tx: &mut Transaction<'_, Postgres>,
+    eid: i32,
+    field: &'static str,
+) -> Result<(), Error> {
+    let names = sqlx::query_scalar!(
+        "SELECT u.display_name
+        FROM users u
+        WHERE EXISTS (
+            SELECT 1
+            FROM users_shifts us
+            JOIN shifts s ON s.sid = us.sid
+            WHERE us.uid = u.uid AND s.eid = $1 AND us.archived_at IS NULL
+        )
+        ORDER BY u.display_name",
+        eid
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    if !names.is_empty() {
+        return Err(Error::unprocessable_entity([(
+            field,
+            format!(
+                "Signed up: {}. They need to withdraw first.",
+                names.join(", ")
+            ),
+        )]));
+    }
+
+    Ok(())
+}
+
+pub(crate) async fn check_duty_lead(
+    tx: &mut Transaction<'_, Postgres>,
+    etid: i32,
+    uid: i32,
+) -> Result<(), Error> {
+    let lead = sqlx::query!(
+        r#"SELECT u.display_name, g.name AS "group_name?",
+            et.duty_lead_group IS NULL OR COALESCE(ugc.valid, FALSE) AS "qualified!"
+        FROM users u
+        JOIN event_types et ON et.etid = $2
+        LEFT JOIN groups g ON g.gid = et.duty_lead_group
+        LEFT JOIN users_groups_current ugc ON ugc.uid = u.uid AND ugc.gid = et.duty_lead_group
+        WHERE u.uid = $1 AND u.archived_at IS NULL"#,
+        uid,
+        etid
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or_else(|| Error::unprocessable_entity([("duty_lead", "Unknown member")]))?;
+
+    if !lead.qualified {
+        return Err(Error::unprocessable_entity([(
+            "duty_lead",
+            format!(
+                "{} doesn't hold a valid {} to lead this event",
+                lead.display_name,
+                lead.group_name.unwrap_or_default()
+            ),
+        )]));
+    }
+
+    Ok(())
+}
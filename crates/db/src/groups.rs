@@ -0,0 +1,176 @@
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use types::Error;
+
+#[derive(Clone)]
+pub struct Groups {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct Group {
+    pub gid: i32,
+    pub name: String,
+    pub is_qualification: bool,
+    pub qualification_expiration_yrs: Option<i32>,
+    pub archived: bool,
+}
+
+#[derive(Debug)]
+pub struct NewGroup {
+    pub name: String,
+    pub is_qualification: bool,
+    /// Only for qualifications, which otherwise never expire
+    pub qualification_expiration_yrs: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct Qualification {
+    pub gid: i32,
+    pub name: String,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub valid: bool,
+}
+
+#[derive(Debug)]
+pub struct RosterEntry {
+    pub uid: i32,
+    pub display_name: String,
+    pub issued_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub valid: bool,
+    /// Expired, or expiring within the requested number of days
+    pub expiring: bool,
+}
+
+impl Groups {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_qualifications(&self) -> Result<Vec<Group>, Error> {
+        let result = sqlx::query_as!(
+            Group,
+            r#"SELECT gid, name, is_qualification, qualification_expiration_yrs,
+                archived_at IS NOT NULL AS "archived!"
+            FROM groups
+            WHERE is_qualification AND archived_at IS NULL
+            ORDER BY name"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Every group including archived ones, for managing them
+    pub async fn list_all(&self) -> Result<Vec<Group>, Error> {
+        let result = sqlx::query_as!(
+            Group,
+            r#"SELECT gid, name, is_qualification, qualification_expiration_yrs,
+                archived_at IS NOT NULL AS "archived!"
+            FROM groups
+            ORDER BY archived_at IS NOT NULL, name"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn create(&self, group: &NewGroup) -> Result<i32, Error> {
+        let gid = sqlx::query_scalar!(
+            "INSERT INTO groups (name, is_qualification, qualification_expiration_yrs)
+            VALUES ($1, $2, $3)
+            RETURNING gid",
+            group.name,
+            group.is_qualification,
+            group.qualification_expiration_yrs
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(gid)
+    }
+
+    pub async fn update(&self, gid: i32, group: &NewGroup) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE groups
+            SET name = $2, is_qualification = $3, qualification_expiration_yrs = $4,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE gid = $1",
+            gid,
+            group.name,
+            group.is_qualification,
+            group.qualification_expiration_yrs
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    pub async fn set_archived(&self, gid: i32, archived: bool) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE groups
+            SET archived_at = CASE WHEN $2 THEN CURRENT_TIMESTAMP END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE gid = $1 AND (archived_at IS NOT NULL) <> $2",
+            gid,
+            archived
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// The user's current and expired qualifications, using the latest issue
+    /// of each
+    pub async fn qualifications_for_user(&self, uid: i32) -> Result<Vec<Qualification>, Error> {
+        let result = sqlx::query_as!(
+            Qualification,
+            r#"SELECT ugc.gid AS "gid!", g.name, ugc.issued_at, ugc.expires_at, ugc.valid AS "valid!"
+            FROM users_groups_current ugc
+            JOIN groups g ON g.gid = ugc.gid
+            WHERE ugc.uid = $1 AND g.is_qualification
+            ORDER BY ugc.valid DESC, g.name"#,
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Everyone who holds or has held the qualification, soonest expiring first
+    pub async fn roster(&self, gid: i32, within_days: i32) -> Result<Vec<RosterEntry>, Error> {
+        let result = sqlx::query_as!(
+            RosterEntry,
+            r#"SELECT u.uid, u.display_name, ugc.issued_at, ugc.expires_at, ugc.valid AS "valid!",
+                COALESCE(
+                    NOT ugc.valid OR ugc.expires_at < CURRENT_TIMESTAMP + make_interval(days => $2),
+                    FALSE
+                ) AS "expiring!"
+            FROM users_groups_current ugc
+            JOIN users u ON u.uid = ugc.uid
+            WHERE ugc.gid = $1 AND u.archived_at IS NULL
+            ORDER BY ugc.expires_at ASC NULLS LAST, u.display_name"#,
+            gid,
+            within_days
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,426 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use sqlx::PgPool;
+use types::Error;
+
+/// Hours logged by members. They count towards totals once the duty lead of
+/// their shift approves them, or an admin when no one else can.
+#[derive(Clone)]
+pub struct Hours {
+    pool: PgPool,
+}
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "hours_status", rename_all = "snake_case")]
+pub enum HoursStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+#[derive(Debug)]
+pub struct HoursEntry {
+    pub hid: i32,
+    pub etid: i32,
+    pub type_name: String,
+    pub colour: Option<String>,
+    pub sid: Option<i32>,
+    pub event_name: Option<String>,
+    pub date: NaiveDate,
+    pub hours: f64,
+    pub description: Option<String>,
+    pub draft: bool,
+    pub status: HoursStatus,
+}
+
+#[derive(Debug)]
+pub struct NewHours {
+    pub etid: i32,
+    pub sid: Option<i32>,
+    pub date: NaiveDate,
+    pub hours: f64,
+    pub description: Option<String>,
+}
+
+/// Hours waiting for the reviewer's approval
+#[derive(Debug)]
+pub struct PendingHours {
+    pub hid: i32,
+    pub uid: i32,
+    pub display_name: String,
+    pub type_name: String,
+    pub colour: Option<String>,
+    pub event_name: Option<String>,
+    pub shift_start: Option<NaiveDateTime>,
+    pub date: NaiveDate,
+    pub hours: f64,
+    pub description: Option<String>,
+}
+
+/// A user's approved hours of one event type
+#[derive(Debug)]
+pub struct HoursTotal {
+    pub type_name: String,
+    pub colour: Option<String>,
+    pub hours: f64,
+    /// Within the last 12 months
+    pub recent_hours: f64,
+}
+
+/// A shift the user held which hours can be linked to
+#[derive(Debug)]
+pub struct LinkableShift {
+    pub sid: i32,
+    pub etid: i32,
+    pub event_name: String,
+    pub start_time: NaiveDateTime,
+}
+
+impl Hours {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_for_user(&self, uid: i32) -> Result<Vec<HoursEntry>, Error> {
+        let result = sqlx::query_as!(
+            HoursEntry,
+            r#"SELECT h.hid, h.etid, et.name AS type_name, et.colour, h.sid,
+                e.event_name AS "event_name?", h.date, h.hours::FLOAT8 AS "hours!", h.description,
+                h.draft, h.status AS "status: HoursStatus"
+            FROM user_hours h
+            JOIN event_types et ON et.etid = h.etid
+            LEFT JOIN shifts s ON s.sid = h.sid
+            LEFT JOIN events e ON e.eid = s.eid
+            WHERE h.uid = $1
+            ORDER BY h.date DESC, h.hid DESC"#,
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn totals_for_user(&self, uid: i32) -> Result<Vec<HoursTotal>, Error> {
+        let result = sqlx::query_as!(
+            HoursTotal,
+            r#"SELECT et.name AS type_name, et.colour,
+                SUM(h.hours)::FLOAT8 AS "hours!",
+                COALESCE(SUM(h.hours) FILTER (
+                    WHERE h.date > CURRENT_DATE - INTERVAL '1 year'
+                ), 0)::FLOAT8 AS "recent_hours!"
+            FROM user_hours h
+            JOIN event_types et ON et.etid = h.etid
+            WHERE h.uid = $1 AND h.status = 'approved'
+            GROUP BY et.etid
+            ORDER BY et.etid"#,
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_linkable_shifts(&self, uid: i32) -> Result<Vec<LinkableShift>, Error> {
+        let result = sqlx::query_as!(
+            LinkableShift,
+            "SELECT s.sid, e.etid, e.event_name, s.start_time
+            FROM users_shifts us
+            JOIN shifts s ON s.sid = us.sid
+            JOIN events e ON e.eid = s.eid
+            WHERE us.uid = $1
+                AND us.archived_at IS NULL
+                AND e.archived_at IS NULL
+                AND s.start_time <= LOCALTIMESTAMP
+            ORDER BY s.start_time DESC
+            LIMIT 50",
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn log(&self, uid: i32, mut hours: NewHours) -> Result<i32, Error> {
+        hours.hours = validate_hours(hours.hours)?;
+
+        let future =
+            sqlx::query_scalar!(r#"SELECT $1::DATE > CURRENT_DATE AS "future!""#, hours.date)
+                .fetch_one(&self.pool)
+                .await?;
+        if future {
+            return Err(Error::unprocessable_entity([(
+                "date",
+                "Hours can't be logged for a future date",
+            )]));
+        }
+
+        let event_type = sqlx::query!(
+            "SELECT name, hours_require_linked_signup FROM event_types
+            WHERE etid = $1 AND archived_at IS NULL",
+            hours.etid
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| Error::unprocessable_entity([("etid", "Unknown event type")]))?;
+
+        match hours.sid {
+            Some(sid) => {
+                let shift = sqlx::query!(
+                    r#"SELECT e.etid, s.start_time::DATE AS "starts_on!", s.end_time::DATE AS "ends_on!"
+                    FROM users_shifts us
+                    JOIN shifts s ON s.sid = us.sid
+                    JOIN events e ON e.eid = s.eid
+                    WHERE us.uid = $1 AND us.sid = $2 AND us.archived_at IS NULL"#,
+                    uid,
+                    sid
+                )
+                .fetch_optional(&self.pool)
+                .await?
+                .ok_or_else(|| {
+                    Error::unprocessable_entity([("sid", "You are not signed up for this shift")])
+                })?;
+
+                if shift.etid != hours.etid {
+                    return Err(Error::unprocessable_entity([(
+                        "sid",
+                        format!("This shift is not a {} event", event_type.name),
+                    )]));
+                }
+                // Overnight shifts can be logged on either day
+                if hours.date < shift.starts_on || hours.date > shift.ends_on {
+                    return Err(Error::unprocessable_entity([(
+                        "date",
+                        format!("This shift was on {}", shift.starts_on.format("%b %-d, %Y")),
+                    )]));
+                }
+            }
+            None if event_type.hours_require_linked_signup => {
+                return Err(Error::unprocessable_entity([(
+                    "sid",
+                    format!(
+                        "{} hours must be linked to a shift you signed up for",
+                        event_type.name
+                    ),
+                )]));
+            }
+            None => (),
+        }
+
+        // Logging the same day again replaces hours which were rejected
+        let hid = sqlx::query_scalar!(
+            "INSERT INTO user_hours (uid, etid, sid, date, hours, description)
+            VALUES ($1, $2, $3, $4, $5::FLOAT8, $6)
+            ON CONFLICT (uid, etid, date) DO UPDATE
+            SET sid = EXCLUDED.sid, hours = EXCLUDED.hours, description = EXCLUDED.description,
+                status = 'pending', reviewed_by = NULL, reviewed_at = NULL,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE user_hours.status = 'rejected'
+            RETURNING hid",
+            uid,
+            hours.etid,
+            hours.sid,
+            hours.date,
+            hours.hours,
+            hours.description
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        let Some(hid) = hid else {
+            let drafted = sqlx::query_scalar!(
+                "SELECT draft FROM user_hours WHERE uid = $1 AND etid = $2 AND date = $3",
+                uid,
+                hours.etid,
+                hours.date
+            )
+            .fetch_optional(&self.pool)
+            .await?
+            .unwrap_or(false);
+
+            let date = hours.date.format("%b %-d, %Y");
+            return Err(Error::unprocessable_entity([(
+                "date",
+                if drafted {
+                    format!(
+                        "Your {} hours on {date} were filled in from your shifts, confirm them above instead",
+                        event_type.name
+                    )
+                } else {
+                    format!(
+                        "You have already logged {} hours on {date}",
+                        event_type.name
+                    )
+                },
+            )]));
+        };
+
+        Ok(hid)
+    }
+
+    /// Creates draft hours for everyone signed up to shifts which have ended
+    /// since the last run. Shifts of the same type on the same day are added
+    /// up into one draft, even when they end in different runs. Days with hours
+    /// already logged are left untouched.
+    pub async fn prefill_completed_shifts(&self) -> Result<u64, Error> {
+        let result = sqlx::query!(
+            "WITH completed AS (
+                UPDATE shifts
+                SET hours_prefilled_at = CURRENT_TIMESTAMP
+                WHERE end_time < LOCALTIMESTAMP AND hours_prefilled_at IS NULL
+                RETURNING sid, eid, start_time, end_time
+            )
+            INSERT INTO user_hours (uid, etid, sid, date, hours, draft)
+            SELECT us.uid, e.etid, MIN(c.sid), c.start_time::DATE,
+                LEAST(ROUND(SUM(EXTRACT(EPOCH FROM c.end_time - c.start_time)) / 3600, 2), 24),
+                TRUE
+            FROM completed c
+            JOIN events e ON e.eid = c.eid
+            JOIN users_shifts us ON us.sid = c.sid
+            WHERE us.archived_at IS NULL AND e.archived_at IS NULL
+            GROUP BY us.uid, e.etid, c.start_time::DATE
+            ON CONFLICT (uid, etid, date) DO UPDATE
+            SET hours = LEAST(user_hours.hours + EXCLUDED.hours, 24)
+            WHERE user_hours.draft"
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn confirm_draft(
+        &self,
+        uid: i32,
+        hid: i32,
+        hours: f64,
+        description: Option<String>,
+    ) -> Result<(), Error> {
+        let hours = validate_hours(hours)?;
+
+        let result = sqlx::query!(
+            "UPDATE user_hours
+            SET draft = FALSE, hours = $3::FLOAT8, description = $4, updated_at = CURRENT_TIMESTAMP
+            WHERE hid = $1 AND uid = $2 AND draft",
+            hid,
+            uid,
+            hours,
+            description
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    pub async fn reject_draft(&self, uid: i32, hid: i32) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "DELETE FROM user_hours WHERE hid = $1 AND uid = $2 AND draft",
+            hid,
+            uid
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Confirmed hours `reviewer` can approve: those of shifts they're the
+    /// duty lead of, and for admins, those without a duty lead still around to
+    /// review them. No one reviews their own, unless they're the only admin.
+    pub async fn list_pending_review(
+        &self,
+        reviewer: i32,
+        is_admin: bool,
+    ) -> Result<Vec<PendingHours>, Error> {
+        let result = sqlx::query_as!(
+            PendingHours,
+            r#"SELECT h.hid, h.uid, u.display_name, et.name AS type_name, et.colour,
+                e.event_name AS "event_name?", s.start_time AS "shift_start?", h.date,
+                h.hours::FLOAT8 AS "hours!", h.description
+            FROM user_hours h
+            JOIN users u ON u.uid = h.uid
+            JOIN event_types et ON et.etid = h.etid
+            LEFT JOIN shifts s ON s.sid = h.sid
+            LEFT JOIN events e ON e.eid = s.eid
+            WHERE h.status = 'pending' AND NOT h.draft
+                AND can_review_hours($1, $2, h.hid)
+            ORDER BY h.date, u.display_name, h.hid"#,
+            reviewer,
+            is_admin
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// How many entries [`Hours::list_pending_review`] would list
+    pub async fn count_pending_review(&self, reviewer: i32, is_admin: bool) -> Result<i64, Error> {
+        let result = sqlx::query_scalar!(
+            r#"SELECT COUNT(*) AS "count!"
+            FROM user_hours h
+            WHERE h.status = 'pending' AND NOT h.draft
+                AND can_review_hours($1, $2, h.hid)"#,
+            reviewer,
+            is_admin
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Approves or rejects pending hours, if `reviewer` is allowed to per
+    /// [`Hours::list_pending_review`]
+    pub async fn review(
+        &self,
+        reviewer: i32,
+        is_admin: bool,
+        hid: i32,
+        status: HoursStatus,
+    ) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE user_hours h
+            SET status = $4, reviewed_by = $1, reviewed_at = CURRENT_TIMESTAMP
+            WHERE h.hid = $3
+                AND h.status = 'pending' AND NOT h.draft
+                AND can_review_hours($1, $2, h.hid)",
+            reviewer,
+            is_admin,
+            hid,
+            status as HoursStatus
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+}
+
+/// Rounds to the hundredths `user_hours` keeps, so hours which would be stored
+/// as 0 are rejected
+fn validate_hours(hours: f64) -> Result<f64, Error> {
+    let hours = (hours * 100.0).round() / 100.0;
+    if !(hours > 0.0 && hours <= 24.0) {
+        return Err(Error::unprocessable_entity([(
+            "hours",
+            "Hours must be more than 0 and at most 24",
+        )]));
+    }
+
+    Ok(hours)
+}
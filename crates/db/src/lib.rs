@@ -1,12 +1,50 @@
 #![allow(async_fn_in_trait)]
 
-use std::time::Duration;
+use std::{fs, path::Path, time::Duration};
 
-use postgresql_embedded::PostgreSQL;
-use sqlx::{PgPool, postgres::PgPoolOptions};
+use anyhow::Context;
+use postgresql_embedded::{PostgreSQL, Settings};
+use sqlx::{
+    PgPool,
+    migrate::{Migrate, Migrator},
+    postgres::PgPoolOptions,
+};
 
+mod audit;
+mod calendar_tokens;
+mod comments;
+mod events;
+mod groups;
+mod hours;
+mod login_attempts;
+mod reports;
+mod series;
+mod sessions;
+mod signups;
+mod two_factor;
+mod user_tokens;
 mod users;
-use crate::users::Users;
+mod vehicles;
+pub use crate::{
+    audit::{AuditAction, AuditActor, AuditEntry, AuditFilter, AuditLog, acting_as},
+    calendar_tokens::{CalendarToken, CalendarTokens},
+    comments::{Comment, Comments},
+    events::{
+        CalendarShift, DutyLeadCandidate, Event, EventSummary, EventType, Events, ImportedEvent,
+        NewEvent, NewEventType, NewShift, Shift,
+    },
+    groups::{Group, Groups, NewGroup, Qualification, RosterEntry},
+    hours::{Hours, HoursEntry, HoursStatus, HoursTotal, LinkableShift, NewHours, PendingHours},
+    login_attempts::{Lockout, LoginAttempt, LoginAttempts, LoginOutcome},
+    reports::{MemberHours, MemberTypeHours, MonthlyHours, Reports},
+    series::{EventSeries, Frequency, NewSeries, Occurrence, Series},
+    sessions::{Device, Session, Sessions},
+    signups::{Signup, Signups},
+    two_factor::{TwoFactor, TwoFactorStatus},
+    user_tokens::{EmailChange, PendingInvitation, TokenPurpose, TokenUser, UserTokens},
+    users::{MemberSummary, NewUser, Profile, ProfileUpdate, User, UserRoles, Users},
+    vehicles::{NewVehicle, ShiftVehicle, Vehicle, Vehicles},
+};
 
 #[cfg_attr(test, unreachable_macro::with_unreachable_defaults)]
 pub trait Database {
@@ -16,58 +54,166 @@ pub trait Database {
 #[derive(Clone)]
 pub struct DB {
     pool: PgPool,
+    pub audit_log: AuditLog,
+    pub calendar_tokens: CalendarTokens,
+    pub comments: Comments,
+    pub events: Events,
+    pub groups: Groups,
+    pub hours: Hours,
+    pub login_attempts: LoginAttempts,
+    pub reports: Reports,
+    pub series: Series,
+    pub sessions: Sessions,
+    pub signups: Signups,
+    pub two_factor: TwoFactor,
+    pub user_tokens: UserTokens,
     pub users: Users,
+    pub vehicles: Vehicles,
 }
-pub const INIT_SQL: &str = include_str!("../sql/init.sql");
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
 pub const DUMP_SQL: &str = include_str!("../sql/dump.sql");
 
-pub async fn embedded_db() -> (String, PostgreSQL) {
-    let mut postgresql = PostgreSQL::default();
-    postgresql.setup().await.unwrap();
-    postgresql.start().await.unwrap();
+/// Starts a bundled PostgreSQL. With a `data_dir` the cluster and its password
+/// are kept there across restarts, otherwise it is removed when the returned
+/// handle is dropped.
+pub async fn embedded_db(data_dir: Option<&Path>) -> anyhow::Result<(String, PostgreSQL)> {
+    let mut settings = Settings::default();
+    if let Some(dir) = data_dir {
+        fs::create_dir_all(dir).with_context(|| format!("creating {}", dir.display()))?;
+        settings.data_dir = dir.join("data");
+        settings.password_file = dir.join(".pgpass");
+        settings.temporary = false;
+        if let Ok(password) = fs::read_to_string(&settings.password_file) {
+            settings.password = password;
+        }
+    }
+
+    let mut postgresql = PostgreSQL::new(settings);
+    postgresql.setup().await?;
+    postgresql.start().await?;
 
     let database_name = "sja";
-    postgresql.create_database(database_name).await.unwrap();
+    if !postgresql.database_exists(database_name).await? {
+        postgresql.create_database(database_name).await?;
+    }
     let settings = postgresql.settings();
     let database_url = settings.url(database_name);
-    (database_url, postgresql)
+    Ok((database_url, postgresql))
 }
 
 impl DB {
-    pub async fn init(connection_string: &str) -> Result<Self, sqlx::Error> {
+    /// Connects and brings the schema up to date. Startup fails if the
+    /// database has migrations applied which this build doesn't know about.
+    ///
+    /// Sessions use `time_zone`, so `CURRENT_DATE`, `LOCALTIMESTAMP` and
+    /// `::TIMESTAMP` casts are in the division's local time like shift times.
+    ///
+    /// `reset` drops and recreates the schema, seeding it with `DUMP_SQL`. It
+    /// is ignored in release builds.
+    pub async fn init(
+        connection_string: &str,
+        max_connections: u32,
+        time_zone: &str,
+        reset: bool,
+    ) -> Result<Self, sqlx::Error> {
+        let time_zone = time_zone.to_owned();
         let pool = PgPoolOptions::new()
-            .max_connections(50)
+            .max_connections(max_connections)
             .acquire_timeout(Duration::from_secs(3))
+            // Setting the actor already checks the connection
+            .test_before_acquire(false)
+            .after_connect(move |conn, _| {
+                let time_zone = time_zone.clone();
+                Box::pin(async move {
+                    // sqlx asks for UTC when connecting
+                    sqlx::query!(
+                        "SELECT set_config('TimeZone', $1, FALSE) AS time_zone,
+                            set_config('app.actor', $2, FALSE) AS actor",
+                        time_zone,
+                        audit::actor()
+                    )
+                    .fetch_one(conn)
+                    .await?;
+
+                    Ok(())
+                })
+            })
+            .before_acquire(|conn, _| {
+                Box::pin(async move { audit::set_actor(conn).await.map(|()| true) })
+            })
             .connect(connection_string)
             .await?;
 
         let db = DB {
             pool: pool.clone(),
+            audit_log: AuditLog::init(pool.clone()),
+            calendar_tokens: CalendarTokens::init(pool.clone()),
+            comments: Comments::init(pool.clone()),
+            events: Events::init(pool.clone()),
+            groups: Groups::init(pool.clone()),
+            hours: Hours::init(pool.clone()),
+            login_attempts: LoginAttempts::init(pool.clone()),
+            reports: Reports::init(pool.clone()),
+            series: Series::init(pool.clone()),
+            sessions: Sessions::init(pool.clone()),
+            signups: Signups::init(pool.clone()),
+            two_factor: TwoFactor::init(pool.clone()),
+            user_tokens: UserTokens::init(pool.clone()),
             users: Users::init(pool.clone()),
+            vehicles: Vehicles::init(pool.clone()),
         };
 
-        #[cfg(debug_assertions)]
-        {
+        let reset = reset && cfg!(debug_assertions);
+        if reset {
             sqlx::raw_sql("DROP SCHEMA public CASCADE; CREATE SCHEMA public; GRANT ALL ON SCHEMA public TO sja; GRANT ALL ON SCHEMA public TO public;")
                 .execute(&db.pool)
-                .await
-                .expect("to reset database");
+                .await?;
+        }
 
-            sqlx::raw_sql(INIT_SQL)
-                .execute(&db.pool)
-                .await
-                .expect("to init db structure");
+        adopt_unmanaged_schema(&db.pool).await?;
+        MIGRATOR.run(&db.pool).await?;
 
-            sqlx::raw_sql(DUMP_SQL)
-                .execute(&db.pool)
-                .await
-                .expect("to seed db with data");
+        if reset {
+            sqlx::raw_sql(DUMP_SQL).execute(&db.pool).await?;
         }
 
         Ok(db)
     }
 }
 
+/// Databases created from `init.sql` before migrations were introduced already
+/// match the first migration, so it is recorded as applied instead of run
+async fn adopt_unmanaged_schema(pool: &PgPool) -> Result<(), sqlx::Error> {
+    let unmanaged: bool = sqlx::query_scalar(
+        "SELECT to_regclass('_sqlx_migrations') IS NULL AND to_regclass('users') IS NOT NULL",
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !unmanaged {
+        return Ok(());
+    }
+
+    let baseline = MIGRATOR.iter().next().expect("at least one migration");
+
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+        VALUES ($1, $2, TRUE, $3, -1)
+        ON CONFLICT DO NOTHING",
+    )
+    .bind(baseline.version)
+    .bind(&*baseline.description)
+    .bind(&*baseline.checksum)
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(())
+}
+
 impl Database for DB {
     async fn healthcheck(&self) -> Result<(), ()> {
         let row: (i64,) = sqlx::query_as("SELECT 1")
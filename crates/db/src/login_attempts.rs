@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::PgPool;
+use types::Error;
+
+/// Failures in a row before a username is locked out, for a minute at first
+const ACCOUNT_FREE_FAILURES: i32 = 5;
+/// Failures from one address within `IP_WINDOW` before it has to back off,
+/// for a second at first
+const IP_FREE_FAILURES: i64 = 20;
+/// How far back failures from an address count
+const IP_WINDOW: Duration = Duration::from_secs(15 * 60);
+/// Failures in a row older than this are forgotten
+const FAILURE_MEMORY: Duration = Duration::from_secs(24 * 60 * 60);
+/// The longest a username or address is locked out for at once
+const MAX_LOCKOUT: Duration = Duration::from_secs(60 * 60);
+
+/// Throttles password guessing. Each failure past the free ones doubles how
+/// long the username, or the address it came from, has to wait.
+#[derive(Clone)]
+pub struct LoginAttempts {
+    pool: PgPool,
+}
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "login_outcome", rename_all = "snake_case")]
+pub enum LoginOutcome {
+    Succeeded,
+    BadPassword,
+    UnknownUser,
+    LockedOut,
+    /// The password was right but the authenticator or recovery code wasn't
+    BadSecondFactor,
+}
+
+#[derive(Debug)]
+pub struct LoginAttempt {
+    pub username: String,
+    pub uid: Option<i32>,
+    pub display_name: Option<String>,
+    pub outcome: LoginOutcome,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: NaiveDateTime,
+}
+
+#[derive(Debug)]
+pub struct Lockout {
+    pub username: String,
+    pub failures: i32,
+    pub locked_until: NaiveDateTime,
+}
+
+impl LoginAttempts {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// When the next attempt for the username from the address is allowed, if
+    /// not now
+    pub async fn locked_until(
+        &self,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Option<DateTime<Utc>>, Error> {
+        let result = sqlx::query_scalar!(
+            r#"SELECT GREATEST(
+                (SELECT locked_until FROM login_lockouts WHERE username = lower($1)),
+                (SELECT MAX(created_at)
+                    + make_interval(secs => LEAST(POWER(2, COUNT(*) - $3), $5))
+                FROM login_attempts
+                WHERE ip_address = $2
+                    AND outcome IN ('bad_password', 'unknown_user', 'bad_second_factor')
+                    AND created_at > CURRENT_TIMESTAMP - make_interval(secs => $4)
+                HAVING COUNT(*) >= $3)
+            ) AS "locked_until""#,
+            username,
+            ip_address,
+            IP_FREE_FAILURES,
+            IP_WINDOW.as_secs_f64(),
+            MAX_LOCKOUT.as_secs_f64()
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(result.filter(|until| *until > Utc::now()))
+    }
+
+    /// Records an attempt, locking the username out once it has failed too
+    /// many times in a row
+    pub async fn record(
+        &self,
+        username: &str,
+        uid: Option<i32>,
+        outcome: LoginOutcome,
+        ip_address: Option<&str>,
+        user_agent: Option<&str>,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "INSERT INTO login_attempts (username, uid, outcome, ip_address, user_agent)
+            VALUES ($1, $2, $3, $4, $5)",
+            username,
+            uid,
+            outcome as LoginOutcome,
+            ip_address,
+            user_agent
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        match outcome {
+            LoginOutcome::Succeeded => {
+                sqlx::query!(
+                    "DELETE FROM login_lockouts WHERE username = lower($1)",
+                    username
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            LoginOutcome::BadPassword
+            | LoginOutcome::UnknownUser
+            | LoginOutcome::BadSecondFactor => {
+                sqlx::query!(
+                    "INSERT INTO login_lockouts (username, failures, last_failure_at)
+                    VALUES (lower($1), 1, CURRENT_TIMESTAMP)
+                    ON CONFLICT (username) DO UPDATE
+                    SET failures = CASE
+                            WHEN login_lockouts.last_failure_at
+                                < CURRENT_TIMESTAMP - make_interval(secs => $2)
+                            THEN 1
+                            ELSE login_lockouts.failures + 1
+                        END,
+                        last_failure_at = CURRENT_TIMESTAMP",
+                    username,
+                    FAILURE_MEMORY.as_secs_f64()
+                )
+                .execute(&mut *tx)
+                .await?;
+
+                sqlx::query!(
+                    "UPDATE login_lockouts
+                    SET locked_until = CURRENT_TIMESTAMP
+                        + make_interval(secs => LEAST(60 * POWER(2, failures - $2), $3))
+                    WHERE username = lower($1) AND failures >= $2",
+                    username,
+                    ACCOUNT_FREE_FAILURES,
+                    MAX_LOCKOUT.as_secs_f64()
+                )
+                .execute(&mut *tx)
+                .await?;
+            }
+            // Attempts while locked out don't extend the lockout, so someone
+            // else guessing can't keep the owner out indefinitely
+            LoginOutcome::LockedOut => (),
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The most recent failed attempts, newest first
+    pub async fn list_failures(&self, limit: i64) -> Result<Vec<LoginAttempt>, Error> {
+        let result = sqlx::query_as!(
+            LoginAttempt,
+            r#"SELECT la.username, la.uid, u.display_name AS "display_name?",
+                la.outcome AS "outcome: LoginOutcome", la.ip_address, la.user_agent,
+                la.created_at::TIMESTAMP AS "created_at!"
+            FROM login_attempts la
+            LEFT JOIN users u ON u.uid = la.uid
+            WHERE la.outcome <> 'succeeded'
+            ORDER BY la.created_at DESC, la.laid DESC
+            LIMIT $1"#,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_lockouts(&self) -> Result<Vec<Lockout>, Error> {
+        let result = sqlx::query_as!(
+            Lockout,
+            r#"SELECT username, failures, locked_until::TIMESTAMP AS "locked_until!"
+            FROM login_lockouts
+            WHERE locked_until > CURRENT_TIMESTAMP
+            ORDER BY locked_until DESC"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Lifts a lockout early and forgets the failures that led to it
+    pub async fn unlock(&self, username: &str) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "DELETE FROM login_lockouts WHERE username = lower($1)",
+            username
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Forgets attempts older than `keep_for`
+    pub async fn delete_older_than(&self, keep_for: Duration) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            "DELETE FROM login_attempts
+            WHERE created_at < CURRENT_TIMESTAMP - make_interval(secs => $1)",
+            keep_for.as_secs_f64()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "DELETE FROM login_lockouts
+            WHERE last_failure_at < CURRENT_TIMESTAMP - make_interval(secs => $1)
+                AND (locked_until IS NULL OR locked_until < CURRENT_TIMESTAMP)",
+            FAILURE_MEMORY.as_secs_f64()
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+}
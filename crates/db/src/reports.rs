@@ -0,0 +1,146 @@
+use chrono::NaiveDate;
+use sqlx::PgPool;
+use types::Error;
+
+/// Totals of approved hours for reporting to council. Periods include both
+/// their first and last day.
+#[derive(Clone)]
+pub struct Reports {
+    pool: PgPool,
+}
+
+/// A member's hours of one event type
+#[derive(Debug)]
+pub struct MemberTypeHours {
+    pub uid: i32,
+    pub display_name: String,
+    pub etid: i32,
+    pub type_name: String,
+    pub hours: f64,
+}
+
+#[derive(Debug)]
+pub struct MonthlyHours {
+    /// The first of the month
+    pub month: NaiveDate,
+    pub hours: f64,
+    pub volunteers: i64,
+    pub entries: i64,
+}
+
+#[derive(Debug)]
+pub struct MemberHours {
+    pub uid: i32,
+    pub display_name: String,
+    pub hours: f64,
+    pub entries: i64,
+}
+
+impl Reports {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn member_totals(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<MemberTypeHours>, Error> {
+        let result = sqlx::query_as!(
+            MemberTypeHours,
+            r#"SELECT u.uid, u.display_name, et.etid, et.name AS type_name,
+                SUM(h.hours)::FLOAT8 AS "hours!"
+            FROM user_hours h
+            JOIN users u ON u.uid = h.uid
+            JOIN event_types et ON et.etid = h.etid
+            WHERE h.status = 'approved' AND h.date BETWEEN $1 AND $2
+            GROUP BY u.uid, et.etid
+            ORDER BY u.display_name, u.uid, et.etid"#,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn monthly_totals(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<MonthlyHours>, Error> {
+        let result = sqlx::query_as!(
+            MonthlyHours,
+            r#"SELECT DATE_TRUNC('month', h.date)::DATE AS "month!",
+                SUM(h.hours)::FLOAT8 AS "hours!",
+                COUNT(DISTINCT h.uid) AS "volunteers!",
+                COUNT(*) AS "entries!"
+            FROM user_hours h
+            WHERE h.status = 'approved' AND h.date BETWEEN $1 AND $2
+            GROUP BY 1
+            ORDER BY 1"#,
+            from,
+            to
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn top_contributors(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        limit: i64,
+    ) -> Result<Vec<MemberHours>, Error> {
+        let result = sqlx::query_as!(
+            MemberHours,
+            r#"SELECT u.uid, u.display_name, SUM(h.hours)::FLOAT8 AS "hours!",
+                COUNT(*) AS "entries!"
+            FROM user_hours h
+            JOIN users u ON u.uid = h.uid
+            WHERE h.status = 'approved' AND h.date BETWEEN $1 AND $2
+            GROUP BY u.uid
+            ORDER BY "hours!" DESC, u.display_name
+            LIMIT $3"#,
+            from,
+            to,
+            limit
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Current members with fewer than `minimum` hours in the period,
+    /// including those with none
+    pub async fn below_minimum(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+        minimum: f64,
+    ) -> Result<Vec<MemberHours>, Error> {
+        let result = sqlx::query_as!(
+            MemberHours,
+            r#"SELECT u.uid, u.display_name,
+                COALESCE(SUM(h.hours), 0)::FLOAT8 AS "hours!",
+                COUNT(h.hid) AS "entries!"
+            FROM users u
+            LEFT JOIN user_hours h ON h.uid = u.uid AND h.status = 'approved' AND h.date BETWEEN $1 AND $2
+            WHERE u.archived_at IS NULL
+            GROUP BY u.uid
+            HAVING COALESCE(SUM(h.hours), 0) < $3::FLOAT8
+            ORDER BY "hours!", u.display_name"#,
+            from,
+            to,
+            minimum
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
@@ -5,10 +5,10 @@ postgresql_embedded = { version = "0.18.5" }
 tokio = { version = "1.41", features = ["full"] }
 sqlx = { version = "0.8", features = ["chrono", "macros", "postgres", "runtime-tokio"] }
 ---
-use std::fs;
+use std::path::Path;
 
 use postgresql_embedded::PostgreSQL;
-use sqlx::{Executor, postgres::PgPoolOptions};
+use sqlx::{migrate::Migrator, postgres::PgPoolOptions};
 
 #[tokio::main]
 async fn main() {
@@ -35,13 +35,12 @@ async fn main() {
             .await
             .unwrap();
 
-        let sql = fs::read_to_string("./sql/init.sql").expect("Failed to read init.sql");
-        for statement in sql.split(';') {
-            let statement = statement.trim();
-            if !statement.is_empty() {
-                pool.execute(statement).await.unwrap();
-            }
-        }
+        Migrator::new(Path::new("./migrations"))
+            .await
+            .expect("Failed to read migrations")
+            .run(&pool)
+            .await
+            .unwrap();
 
         pool.close().await;
     }
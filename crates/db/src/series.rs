@@ -0,0 +1,562 @@
+use chrono::{NaiveDate, NaiveTime};
+use sqlx::{PgPool, Postgres, Transaction};
+use types::Error;
+
+use crate::events::{NewEvent, active_event_type, check_duty_lead, check_no_signups};
+
+#[derive(Clone)]
+pub struct Series {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "recurrence_frequency", rename_all = "snake_case")]
+pub enum Frequency {
+    Weekly,
+    Biweekly,
+    /// The same week and weekday of every month, such as the second Tuesday
+    MonthlyNthWeekday,
+}
+
+#[derive(Debug)]
+pub struct EventSeries {
+    pub esid: i32,
+    pub etid: i32,
+    pub type_name: String,
+    pub event_name: String,
+    pub organizer_details: Option<String>,
+    pub location: Option<String>,
+    pub notes: Option<String>,
+    pub frequency: Frequency,
+    pub starts_on: NaiveDate,
+    pub ends_on: Option<NaiveDate>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub duty_lead: Option<i32>,
+    pub duty_lead_name: Option<String>,
+    /// How many days ahead occurrences are created
+    pub horizon_days: i32,
+    pub materialised_until: Option<NaiveDate>,
+    pub archived: bool,
+}
+
+#[derive(Debug)]
+pub struct NewSeries {
+    pub event: NewEvent,
+    pub frequency: Frequency,
+    pub starts_on: NaiveDate,
+    pub ends_on: Option<NaiveDate>,
+    pub start_time: NaiveTime,
+    pub end_time: NaiveTime,
+    pub duty_lead: Option<i32>,
+    pub horizon_days: i32,
+}
+
+#[derive(Debug)]
+pub struct Occurrence {
+    pub eid: i32,
+    pub occurrence_date: NaiveDate,
+    /// Edited on its own, so series edits no longer apply to it
+    pub detached: bool,
+    pub signups: i64,
+}
+
+impl Series {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<EventSeries>, Error> {
+        let result = sqlx::query_as!(
+            EventSeries,
+            r#"SELECT s.esid, s.etid, et.name AS type_name, s.event_name, s.organizer_details,
+                s.location, s.notes, s.frequency AS "frequency: Frequency", s.starts_on, s.ends_on,
+                s.start_time, s.end_time, s.duty_lead, u.display_name AS "duty_lead_name?",
+                s.horizon_days, s.materialised_until, s.archived_at IS NOT NULL AS "archived!"
+            FROM event_series s
+            JOIN event_types et ON et.etid = s.etid
+            LEFT JOIN users u ON u.uid = s.duty_lead
+            ORDER BY s.archived_at IS NOT NULL, s.event_name, s.esid"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn get(&self, esid: i32) -> Result<Option<EventSeries>, Error> {
+        let result = sqlx::query_as!(
+            EventSeries,
+            r#"SELECT s.esid, s.etid, et.name AS type_name, s.event_name, s.organizer_details,
+                s.location, s.notes, s.frequency AS "frequency: Frequency", s.starts_on, s.ends_on,
+                s.start_time, s.end_time, s.duty_lead, u.display_name AS "duty_lead_name?",
+                s.horizon_days, s.materialised_until, s.archived_at IS NOT NULL AS "archived!"
+            FROM event_series s
+            JOIN event_types et ON et.etid = s.etid
+            LEFT JOIN users u ON u.uid = s.duty_lead
+            WHERE s.esid = $1"#,
+            esid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Active series which haven't been materialised as far ahead as they
+    /// should be
+    pub async fn list_due(&self) -> Result<Vec<EventSeries>, Error> {
+        let result = sqlx::query_as!(
+            EventSeries,
+            r#"SELECT s.esid, s.etid, et.name AS type_name, s.event_name, s.organizer_details,
+                s.location, s.notes, s.frequency AS "frequency: Frequency", s.starts_on, s.ends_on,
+                s.start_time, s.end_time, s.duty_lead, u.display_name AS "duty_lead_name?",
+                s.horizon_days, s.materialised_until, s.archived_at IS NOT NULL AS "archived!"
+            FROM event_series s
+            JOIN event_types et ON et.etid = s.etid
+            LEFT JOIN users u ON u.uid = s.duty_lead
+            WHERE s.archived_at IS NULL
+                AND (
+                    s.materialised_until IS NULL
+                    OR s.materialised_until < LEAST(CURRENT_DATE + s.horizon_days, s.ends_on)
+                )"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_occurrences(&self, esid: i32) -> Result<Vec<Occurrence>, Error> {
+        let result = sqlx::query_as!(
+            Occurrence,
+            r#"SELECT e.eid, e.occurrence_date AS "occurrence_date!", e.detached,
+                (
+                    SELECT COUNT(DISTINCT us.uid)
+                    FROM users_shifts us
+                    JOIN shifts s ON s.sid = us.sid
+                    WHERE s.eid = e.eid AND us.archived_at IS NULL
+                ) AS "signups!"
+            FROM events e
+            WHERE e.esid = $1 AND e.archived_at IS NULL AND e.occurrence_date >= CURRENT_DATE
+            ORDER BY e.occurrence_date"#,
+            esid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_exceptions(&self, esid: i32) -> Result<Vec<NaiveDate>, Error> {
+        let result = sqlx::query_scalar!(
+            "SELECT date FROM event_series_exceptions WHERE esid = $1 ORDER BY date",
+            esid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn create(&self, series: &NewSeries) -> Result<i32, Error> {
+        let mut tx = self.pool.begin().await?;
+        check_series(&mut tx, series).await?;
+
+        let esid = sqlx::query_scalar!(
+            "INSERT INTO event_series
+                (etid, event_name, organizer_details, location, notes, frequency, starts_on,
+                    ends_on, start_time, end_time, duty_lead, horizon_days)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+            RETURNING esid",
+            series.event.etid,
+            series.event.event_name,
+            series.event.organizer_details,
+            series.event.location,
+            series.event.notes,
+            series.frequency as Frequency,
+            series.starts_on,
+            series.ends_on,
+            series.start_time,
+            series.end_time,
+            series.duty_lead,
+            series.horizon_days
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(esid)
+    }
+
+    /// Applies the series to its upcoming occurrences which haven't been
+    /// edited on their own. `dates` are when the changed series now falls
+    /// through `until`: occurrences on other dates are cancelled, or detached
+    /// if anyone has signed up, and missing ones are created. Occurrences keep
+    /// the times people signed up for, being detached instead of retimed.
+    pub async fn update(
+        &self,
+        esid: i32,
+        series: &NewSeries,
+        dates: &[NaiveDate],
+        until: NaiveDate,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query_scalar!(
+            "SELECT esid FROM event_series WHERE esid = $1 AND archived_at IS NULL FOR UPDATE",
+            esid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+        check_series(&mut tx, series).await?;
+
+        sqlx::query!(
+            "UPDATE event_series
+            SET etid = $2, event_name = $3, organizer_details = $4, location = $5, notes = $6,
+                frequency = $7, starts_on = $8, ends_on = $9, start_time = $10, end_time = $11,
+                duty_lead = $12, horizon_days = $13, updated_at = CURRENT_TIMESTAMP
+            WHERE esid = $1",
+            esid,
+            series.event.etid,
+            series.event.event_name,
+            series.event.organizer_details,
+            series.event.location,
+            series.event.notes,
+            series.frequency as Frequency,
+            series.starts_on,
+            series.ends_on,
+            series.start_time,
+            series.end_time,
+            series.duty_lead,
+            series.horizon_days
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "WITH moved AS (
+                SELECT e.eid, EXISTS (
+                    SELECT 1
+                    FROM users_shifts us
+                    JOIN shifts s ON s.sid = us.sid
+                    WHERE s.eid = e.eid AND us.archived_at IS NULL
+                ) AS signed_up
+                FROM events e
+                WHERE e.esid = $1
+                    AND e.archived_at IS NULL
+                    AND NOT e.detached
+                    AND e.occurrence_date BETWEEN CURRENT_DATE AND $3
+                    AND NOT e.occurrence_date = ANY($2)
+            )
+            UPDATE events e
+            SET archived_at = CASE WHEN m.signed_up THEN NULL ELSE CURRENT_TIMESTAMP END,
+                detached = m.signed_up,
+                updated_at = CURRENT_TIMESTAMP
+            FROM moved m
+            WHERE e.eid = m.eid",
+            esid,
+            dates,
+            until
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Signups wait on the shifts, so none appear once they're checked
+        sqlx::query!(
+            "SELECT sh.sid
+            FROM shifts sh
+            JOIN events e ON e.eid = sh.eid
+            WHERE e.esid = $1
+                AND e.archived_at IS NULL
+                AND NOT e.detached
+                AND e.occurrence_date >= CURRENT_DATE
+            FOR UPDATE OF sh",
+            esid
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Retiming would move people onto times they may have other shifts
+        // at, or fold shifts added to the occurrence into one
+        sqlx::query!(
+            "UPDATE events e
+            SET detached = TRUE, updated_at = CURRENT_TIMESTAMP
+            FROM event_series s
+            WHERE s.esid = $1
+                AND e.esid = s.esid
+                AND e.archived_at IS NULL
+                AND NOT e.detached
+                AND e.occurrence_date >= CURRENT_DATE
+                AND (
+                    (SELECT COUNT(*) FROM shifts sh WHERE sh.eid = e.eid) > 1
+                    OR EXISTS (
+                        SELECT 1
+                        FROM users_shifts us
+                        JOIN shifts sh ON sh.sid = us.sid
+                        WHERE sh.eid = e.eid
+                            AND us.archived_at IS NULL
+                            AND (sh.start_time, sh.end_time) IS DISTINCT FROM (
+                                e.occurrence_date + s.start_time,
+                                e.occurrence_date + s.end_time
+                                    + CASE WHEN s.end_time <= s.start_time THEN INTERVAL '1 day' ELSE INTERVAL '0' END
+                            )
+                    )
+                )",
+            esid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE events e
+            SET etid = s.etid, event_name = s.event_name, organizer_details = s.organizer_details,
+                location = s.location, notes = s.notes, updated_at = CURRENT_TIMESTAMP
+            FROM event_series s
+            WHERE s.esid = $1
+                AND e.esid = s.esid
+                AND e.archived_at IS NULL
+                AND NOT e.detached
+                AND e.occurrence_date >= CURRENT_DATE",
+            esid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE shifts sh
+            SET start_time = e.occurrence_date + s.start_time,
+                end_time = e.occurrence_date + s.end_time
+                    + CASE WHEN s.end_time <= s.start_time THEN INTERVAL '1 day' ELSE INTERVAL '0' END,
+                duty_lead = s.duty_lead,
+                updated_at = CURRENT_TIMESTAMP
+            FROM events e
+            JOIN event_series s ON s.esid = e.esid
+            WHERE sh.eid = e.eid
+                AND e.esid = $1
+                AND e.archived_at IS NULL
+                AND NOT e.detached
+                AND e.occurrence_date >= CURRENT_DATE",
+            esid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        insert_occurrences(&mut tx, esid, dates, until).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Creates the occurrences on `dates` which don't exist yet and aren't
+    /// exceptions, returning how many were created
+    pub async fn materialise(
+        &self,
+        esid: i32,
+        dates: &[NaiveDate],
+        until: NaiveDate,
+    ) -> Result<u64, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query_scalar!(
+            "SELECT esid FROM event_series WHERE esid = $1 AND archived_at IS NULL FOR UPDATE",
+            esid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        let created = insert_occurrences(&mut tx, esid, dates, until).await?;
+
+        tx.commit().await?;
+
+        Ok(created)
+    }
+
+    /// Skips the series on `date`, cancelling the occurrence if it was already
+    /// created
+    pub async fn add_exception(&self, esid: i32, date: NaiveDate) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query_scalar!(
+            "SELECT esid FROM event_series WHERE esid = $1 AND archived_at IS NULL FOR UPDATE",
+            esid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        let occurrence = sqlx::query_scalar!(
+            "SELECT eid FROM events
+            WHERE esid = $1 AND occurrence_date = $2 AND archived_at IS NULL
+                AND occurrence_date >= CURRENT_DATE
+            FOR UPDATE",
+            esid,
+            date
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+        if let Some(eid) = occurrence {
+            check_no_signups(&mut tx, eid, "date").await?;
+        }
+
+        sqlx::query!(
+            "INSERT INTO event_series_exceptions (esid, date)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING",
+            esid,
+            date
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE events
+            SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE esid = $1 AND occurrence_date = $2 AND archived_at IS NULL
+                AND occurrence_date >= CURRENT_DATE",
+            esid,
+            date
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// The occurrence is created again the next time the series is
+    /// materialised
+    pub async fn remove_exception(&self, esid: i32, date: NaiveDate) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "DELETE FROM event_series_exceptions WHERE esid = $1 AND date = $2",
+            esid,
+            date
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        sqlx::query!(
+            "UPDATE event_series SET materialised_until = NULL WHERE esid = $1",
+            esid
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Ends the series, cancelling its upcoming occurrences which haven't
+    /// been edited on their own. Ones people are signed up for are kept,
+    /// detached from the series.
+    pub async fn archive(&self, esid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            "UPDATE event_series
+            SET archived_at = CURRENT_TIMESTAMP, updated_at = CURRENT_TIMESTAMP
+            WHERE esid = $1 AND archived_at IS NULL",
+            esid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        sqlx::query!(
+            "UPDATE events e
+            SET archived_at = CASE WHEN signed_up THEN NULL ELSE CURRENT_TIMESTAMP END,
+                detached = signed_up,
+                updated_at = CURRENT_TIMESTAMP
+            FROM (
+                SELECT eid, EXISTS (
+                    SELECT 1
+                    FROM users_shifts us
+                    JOIN shifts s ON s.sid = us.sid
+                    WHERE s.eid = events.eid AND us.archived_at IS NULL
+                ) AS signed_up
+                FROM events
+                WHERE esid = $1 AND archived_at IS NULL AND NOT detached
+                    AND occurrence_date >= CURRENT_DATE
+            ) upcoming
+            WHERE e.eid = upcoming.eid",
+            esid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+async fn check_series(tx: &mut Transaction<'_, Postgres>, series: &NewSeries) -> Result<(), Error> {
+    active_event_type(tx, series.event.etid).await?;
+
+    if let Some(uid) = series.duty_lead {
+        check_duty_lead(tx, series.event.etid, uid).await?;
+    }
+
+    Ok(())
+}
+
+async fn insert_occurrences(
+    tx: &mut Transaction<'_, Postgres>,
+    esid: i32,
+    dates: &[NaiveDate],
+    until: NaiveDate,
+) -> Result<u64, Error> {
+    let result = sqlx::query!(
+        "WITH new_dates AS (
+            SELECT d.date
+            FROM UNNEST($2::DATE[]) AS d(date)
+            WHERE NOT EXISTS (
+                    SELECT 1 FROM event_series_exceptions x WHERE x.esid = $1 AND x.date = d.date
+                )
+                AND NOT EXISTS (
+                    SELECT 1 FROM events e
+                    WHERE e.esid = $1 AND e.occurrence_date = d.date AND e.archived_at IS NULL
+                )
+        ),
+        new_events AS (
+            INSERT INTO events (etid, event_name, organizer_details, location, notes, esid,
+                occurrence_date)
+            SELECT s.etid, s.event_name, s.organizer_details, s.location, s.notes, s.esid, d.date
+            FROM event_series s, new_dates d
+            WHERE s.esid = $1
+            RETURNING eid, occurrence_date
+        )
+        INSERT INTO shifts (eid, start_time, end_time, duty_lead)
+        SELECT n.eid, n.occurrence_date + s.start_time,
+            n.occurrence_date + s.end_time
+                + CASE WHEN s.end_time <= s.start_time THEN INTERVAL '1 day' ELSE INTERVAL '0' END,
+            s.duty_lead
+        FROM new_events n, event_series s
+        WHERE s.esid = $1",
+        esid,
+        dates
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE event_series SET materialised_until = $2 WHERE esid = $1",
+        esid,
+        until
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(result.rows_affected())
+}
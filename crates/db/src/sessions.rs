@@ -0,0 +1,258 @@
+use std::time::Duration;
+
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+use types::Error;
+
+/// Sessions are stored and looked up by a keyed hash of their token, never the
+/// token itself
+#[derive(Clone)]
+pub struct Sessions {
+    pool: PgPool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub usid: i32,
+    pub uid: i32,
+    /// The token is old enough, or the user's privileges changed, and it
+    /// should be replaced
+    pub rotation_due: bool,
+    /// Signed in with a password but not yet a second factor
+    pub second_factor_pending: bool,
+}
+
+/// Where a session is signed in, for the user to review
+#[derive(Debug)]
+pub struct Device {
+    pub usid: i32,
+    pub user_agent: Option<String>,
+    pub ip_address: Option<String>,
+    pub created_at: NaiveDateTime,
+    pub last_seen_at: NaiveDateTime,
+}
+
+impl Sessions {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Creates a session which expires after `max_age` regardless of activity
+    pub async fn create(
+        &self,
+        uid: i32,
+        token_hash: &[u8],
+        max_age: Duration,
+        second_factor_pending: bool,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<i32, Error> {
+        let usid = sqlx::query_scalar!(
+            "INSERT INTO user_sessions
+                (uid, token_hash, expires_at, second_factor_pending, user_agent, ip_address)
+            VALUES ($1, $2, CURRENT_TIMESTAMP + make_interval(secs => $3), $4, $5, $6)
+            RETURNING usid",
+            uid,
+            token_hash,
+            max_age.as_secs_f64(),
+            second_factor_pending,
+            user_agent,
+            ip_address
+        )
+        .fetch_one(&self.pool)
+        .await?;
+
+        Ok(usid)
+    }
+
+    /// A session which hasn't expired or been idle for longer than
+    /// `idle_timeout`, recording where it was used from
+    pub async fn get(
+        &self,
+        token_hash: &[u8],
+        idle_timeout: Duration,
+        rotate_after: Duration,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<Option<Session>, Error> {
+        // Only write last_seen_at once a minute rather than on every request,
+        // unless the session moved
+        let result = sqlx::query_as!(
+            Session,
+            r#"WITH session AS (
+                SELECT usid, uid, last_seen_at, rotated_at, user_agent, ip_address,
+                    second_factor_pending, token_hash = $1 AS current
+                FROM user_sessions
+                WHERE (
+                        token_hash = $1
+                        OR (previous_token_hash = $1 AND rotated_at > CURRENT_TIMESTAMP - INTERVAL '1 minute')
+                    )
+                    AND expires_at > CURRENT_TIMESTAMP
+                    AND last_seen_at > CURRENT_TIMESTAMP - make_interval(secs => $2)
+            ), touched AS (
+                UPDATE user_sessions us
+                SET last_seen_at = CURRENT_TIMESTAMP, user_agent = $4, ip_address = $5
+                FROM session
+                WHERE us.usid = session.usid
+                    AND (
+                        session.last_seen_at < CURRENT_TIMESTAMP - INTERVAL '1 minute'
+                        OR session.user_agent IS DISTINCT FROM $4
+                        OR session.ip_address IS DISTINCT FROM $5
+                    )
+            )
+            SELECT usid AS "usid!", uid AS "uid!",
+                current AND rotated_at < CURRENT_TIMESTAMP - make_interval(secs => $3) AS "rotation_due!",
+                second_factor_pending AS "second_factor_pending!"
+            FROM session"#,
+            token_hash,
+            idle_timeout.as_secs_f64(),
+            rotate_after.as_secs_f64(),
+            user_agent,
+            ip_address
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Replaces the session's token, returning false if another request
+    /// rotated it first
+    pub async fn rotate(
+        &self,
+        usid: i32,
+        token_hash: &[u8],
+        new_token_hash: &[u8],
+    ) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            "UPDATE user_sessions
+            SET previous_token_hash = token_hash, token_hash = $3, rotated_at = CURRENT_TIMESTAMP
+            WHERE usid = $1 AND token_hash = $2",
+            usid,
+            token_hash,
+            new_token_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Finishes signing in once the second factor is checked, giving the
+    /// session its full lifetime and a new token
+    pub async fn complete_second_factor(&self, usid: i32, max_age: Duration) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE user_sessions
+            SET second_factor_pending = FALSE,
+                expires_at = CURRENT_TIMESTAMP + make_interval(secs => $2),
+                rotated_at = '-infinity'
+            WHERE usid = $1 AND second_factor_pending",
+            usid,
+            max_age.as_secs_f64()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Counts a wrong second factor, ending the session once there have been
+    /// `max_failures`. Returns whether the session is still usable.
+    pub async fn record_second_factor_failure(
+        &self,
+        usid: i32,
+        max_failures: i32,
+    ) -> Result<bool, Error> {
+        let failures = sqlx::query_scalar!(
+            "UPDATE user_sessions
+            SET second_factor_failures = second_factor_failures + 1
+            WHERE usid = $1 AND second_factor_pending
+            RETURNING second_factor_failures",
+            usid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        match failures {
+            Some(failures) if failures < max_failures => Ok(true),
+            _ => {
+                sqlx::query!("DELETE FROM user_sessions WHERE usid = $1", usid)
+                    .execute(&self.pool)
+                    .await?;
+
+                Ok(false)
+            }
+        }
+    }
+
+    pub async fn list_devices(&self, uid: i32) -> Result<Vec<Device>, Error> {
+        let result = sqlx::query_as!(
+            Device,
+            r#"SELECT usid, user_agent, ip_address,
+                created_at::TIMESTAMP AS "created_at!",
+                last_seen_at::TIMESTAMP AS "last_seen_at!"
+            FROM user_sessions
+            WHERE uid = $1 AND expires_at > CURRENT_TIMESTAMP AND NOT second_factor_pending
+            ORDER BY last_seen_at DESC"#,
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn delete(&self, token_hash: &[u8]) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM user_sessions WHERE token_hash = $1 OR previous_token_hash = $1",
+            token_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Signs the user out on one of their devices
+    pub async fn revoke(&self, uid: i32, usid: i32) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "DELETE FROM user_sessions WHERE uid = $1 AND usid = $2",
+            uid,
+            usid
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Signs the user out on every device
+    pub async fn revoke_all(&self, uid: i32) -> Result<u64, Error> {
+        let result = sqlx::query!("DELETE FROM user_sessions WHERE uid = $1", uid)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete_stale(&self, idle_timeout: Duration) -> Result<u64, Error> {
+        let result = sqlx::query!(
+            "DELETE FROM user_sessions
+            WHERE expires_at <= CURRENT_TIMESTAMP
+                OR last_seen_at <= CURRENT_TIMESTAMP - make_interval(secs => $1)",
+            idle_timeout.as_secs_f64()
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected())
+    }
+}
@@ -0,0 +1,214 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use types::Error;
+
+#[derive(Clone)]
+pub struct Signups {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct Signup {
+    pub sid: i32,
+    pub uid: i32,
+    pub display_name: String,
+}
+
+impl Signups {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list_for_event(&self, eid: i32) -> Result<Vec<Signup>, Error> {
+        let result = sqlx::query_as!(
+            Signup,
+            "SELECT us.sid, us.uid, u.display_name
+            FROM users_shifts us
+            JOIN shifts s ON s.sid = us.sid
+            JOIN users u ON u.uid = us.uid
+            WHERE s.eid = $1 AND us.archived_at IS NULL
+            ORDER BY us.created_at, us.uid",
+            eid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Signs the user up for the shift, or for every remaining shift of the
+    /// event if its type uses event level signup
+    pub async fn signup(&self, uid: i32, sid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_user(&mut tx, uid).await?;
+
+        let sids = signup_targets(&mut tx, sid).await?;
+
+        let overlap = sqlx::query!(
+            "SELECT e.event_name, s.start_time
+            FROM users_shifts us
+            JOIN shifts s ON s.sid = us.sid
+            JOIN events e ON e.eid = s.eid
+            JOIN shifts t ON t.sid = ANY($2)
+            WHERE us.uid = $1
+                AND us.archived_at IS NULL
+                AND e.archived_at IS NULL
+                AND NOT us.sid = ANY($2)
+                AND s.start_time < t.end_time
+                AND t.start_time < s.end_time
+            ORDER BY s.start_time
+            LIMIT 1",
+            uid,
+            &sids
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(overlap) = overlap {
+            return Err(Error::unprocessable_entity([(
+                "sid",
+                format!(
+                    "Overlaps with your shift at {} on {}",
+                    overlap.event_name,
+                    overlap.start_time.format("%b %-d %H:%M")
+                ),
+            )]));
+        }
+
+        sqlx::query!(
+            "INSERT INTO users_shifts (uid, sid)
+            SELECT $1, UNNEST($2::INTEGER[])
+            ON CONFLICT (uid, sid) DO UPDATE
+            SET archived_at = NULL, created_at = CURRENT_TIMESTAMP
+            WHERE users_shifts.archived_at IS NOT NULL",
+            uid,
+            &sids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Withdraws the user from the shift, or from every remaining shift of the
+    /// event if its type uses event level signup
+    pub async fn withdraw(&self, uid: i32, sid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        lock_user(&mut tx, uid).await?;
+
+        let sids = signup_targets(&mut tx, sid).await?;
+
+        sqlx::query!(
+            "SELECT sid FROM shifts WHERE sid = ANY($1) FOR UPDATE",
+            &sids
+        )
+        .fetch_all(&mut *tx)
+        .await?;
+
+        // Vehicles the user is the last qualified driver for would be left
+        // without one
+        let stranded = sqlx::query!(
+            "SELECT v.name
+            FROM shifts_vehicles sv
+            JOIN vehicles v ON v.vid = sv.vid
+            JOIN users_shifts us ON us.sid = sv.sid AND us.uid = $1 AND us.archived_at IS NULL
+            JOIN users_groups_current ugc ON ugc.uid = $1 AND ugc.gid = v.requires_group_to_drive
+            WHERE sv.sid = ANY($2)
+                AND ugc.valid
+                AND NOT EXISTS (
+                    SELECT 1
+                    FROM users_shifts other
+                    JOIN users_groups_current other_ugc ON other_ugc.uid = other.uid
+                    WHERE other.sid = sv.sid
+                        AND other.uid <> $1
+                        AND other.archived_at IS NULL
+                        AND other_ugc.gid = v.requires_group_to_drive
+                        AND other_ugc.valid
+                )
+            ORDER BY v.name
+            LIMIT 1",
+            uid,
+            &sids
+        )
+        .fetch_optional(&mut *tx)
+        .await?;
+
+        if let Some(vehicle) = stranded {
+            return Err(Error::unprocessable_entity([(
+                "sid",
+                format!(
+                    "You are the only volunteer on this shift qualified to drive {}",
+                    vehicle.name
+                ),
+            )]));
+        }
+
+        let result = sqlx::query!(
+            "UPDATE users_shifts
+            SET archived_at = CURRENT_TIMESTAMP
+            WHERE uid = $1 AND sid = ANY($2) AND archived_at IS NULL",
+            uid,
+            &sids
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::unprocessable_entity([(
+                "sid",
+                "You are not signed up for this shift",
+            )]));
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Serializes signup changes for a user so concurrent requests can't both pass
+/// the overlap check
+async fn lock_user(tx: &mut Transaction<'_, Postgres>, uid: i32) -> Result<(), Error> {
+    sqlx::query!("SELECT uid FROM users WHERE uid = $1 FOR UPDATE", uid)
+        .fetch_optional(&mut **tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(())
+}
+
+/// The shifts a signup change for `sid` applies to
+async fn signup_targets(tx: &mut Transaction<'_, Postgres>, sid: i32) -> Result<Vec<i32>, Error> {
+    let shift = sqlx::query!(
+        "SELECT s.eid, s.end_time < LOCALTIMESTAMP AS \"ended!\", et.event_level_signup
+        FROM shifts s
+        JOIN events e ON e.eid = s.eid
+        JOIN event_types et ON et.etid = e.etid
+        WHERE s.sid = $1 AND e.archived_at IS NULL",
+        sid
+    )
+    .fetch_optional(&mut **tx)
+    .await?
+    .ok_or(Error::NotFound)?;
+
+    if shift.ended {
+        return Err(Error::unprocessable_entity([(
+            "sid",
+            "This shift has already ended",
+        )]));
+    }
+
+    if !shift.event_level_signup {
+        return Ok(vec![sid]);
+    }
+
+    let sids = sqlx::query_scalar!(
+        "SELECT sid FROM shifts WHERE eid = $1 AND end_time >= LOCALTIMESTAMP ORDER BY sid",
+        shift.eid
+    )
+    .fetch_all(&mut **tx)
+    .await?;
+
+    Ok(sids)
+}
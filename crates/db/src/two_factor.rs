@@ -0,0 +1,184 @@
+use sqlx::{PgPool, Postgres, Transaction};
+use types::Error;
+
+/// TOTP secrets and recovery codes. Codes are checked by the caller, this only
+/// keeps track of which have been used.
+#[derive(Clone)]
+pub struct TwoFactor {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct TwoFactorStatus {
+    /// Present once enrolment has started
+    pub secret: Option<Vec<u8>>,
+    pub enabled: bool,
+    pub recovery_codes_left: i64,
+}
+
+impl TwoFactor {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn get(&self, uid: i32) -> Result<TwoFactorStatus, Error> {
+        let result = sqlx::query_as!(
+            TwoFactorStatus,
+            r#"SELECT totp_secret AS secret, totp_enabled_at IS NOT NULL AS "enabled!",
+                (SELECT COUNT(*) FROM user_recovery_codes
+                    WHERE uid = $1 AND used_at IS NULL) AS "recovery_codes_left!"
+            FROM users
+            WHERE uid = $1"#,
+            uid
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        Ok(result)
+    }
+
+    /// Starts enrolment with `secret`, unless it was already started, and
+    /// returns the secret to enrol with
+    pub async fn start_enrolment(&self, uid: i32, secret: &[u8]) -> Result<Vec<u8>, Error> {
+        let result = sqlx::query_scalar!(
+            r#"UPDATE users
+            SET totp_secret = COALESCE(totp_secret, $2)
+            WHERE uid = $1 AND totp_enabled_at IS NULL
+            RETURNING totp_secret AS "totp_secret!""#,
+            uid,
+            secret
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        .ok_or_else(|| {
+            Error::unprocessable_entity([("code", "Two-factor authentication is already set up")])
+        })?;
+
+        Ok(result)
+    }
+
+    /// Finishes enrolment with the secret a code was just checked against,
+    /// replacing any recovery codes
+    pub async fn enable(
+        &self,
+        uid: i32,
+        secret: &[u8],
+        step: i64,
+        recovery_code_hashes: &[Vec<u8>],
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            "UPDATE users
+            SET totp_enabled_at = CURRENT_TIMESTAMP, totp_last_step = $3
+            WHERE uid = $1 AND totp_secret = $2 AND totp_enabled_at IS NULL",
+            uid,
+            secret,
+            step
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::unprocessable_entity([(
+                "code",
+                "Two-factor authentication is already set up",
+            )]));
+        }
+
+        replace_recovery_codes(&mut tx, uid, recovery_code_hashes).await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Accepts a code for the time step, unless a code for it or a later step
+    /// was already accepted
+    pub async fn accept_step(&self, uid: i32, step: i64) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            "UPDATE users
+            SET totp_last_step = $2
+            WHERE uid = $1
+                AND totp_enabled_at IS NOT NULL
+                AND (totp_last_step IS NULL OR totp_last_step < $2)",
+            uid,
+            step
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    /// Marks the recovery code used, returning false if it isn't one of the
+    /// user's unused codes
+    pub async fn use_recovery_code(&self, uid: i32, code_hash: &[u8]) -> Result<bool, Error> {
+        let result = sqlx::query!(
+            "UPDATE user_recovery_codes
+            SET used_at = CURRENT_TIMESTAMP
+            WHERE uid = $1 AND code_hash = $2 AND used_at IS NULL",
+            uid,
+            code_hash
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(result.rows_affected() == 1)
+    }
+
+    pub async fn replace_recovery_codes(
+        &self,
+        uid: i32,
+        recovery_code_hashes: &[Vec<u8>],
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+        replace_recovery_codes(&mut tx, uid, recovery_code_hashes).await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn disable(&self, uid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "UPDATE users
+            SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL
+            WHERE uid = $1",
+            uid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM user_recovery_codes WHERE uid = $1", uid)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+async fn replace_recovery_codes(
+    tx: &mut Transaction<'_, Postgres>,
+    uid: i32,
+    recovery_code_hashes: &[Vec<u8>],
+) -> Result<(), Error> {
+    sqlx::query!("DELETE FROM user_recovery_codes WHERE uid = $1", uid)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query!(
+        "INSERT INTO user_recovery_codes (uid, code_hash)
+        SELECT $1, UNNEST($2::BYTEA[])",
+        uid,
+        recovery_code_hashes
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
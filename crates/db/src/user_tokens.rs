@@ -0,0 +1,276 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use sqlx::PgPool;
+use types::Error;
+
+#[derive(Clone)]
+pub struct UserTokens {
+    pool: PgPool,
+}
+
+#[derive(sqlx::Type, Debug, Clone, Copy, PartialEq, Eq)]
+#[sqlx(type_name = "user_token_purpose", rename_all = "snake_case")]
+pub enum TokenPurpose {
+    Invitation,
+    PasswordReset,
+    EmailChange,
+}
+
+/// The user a valid token was issued to
+#[derive(Debug)]
+pub struct TokenUser {
+    pub uid: i32,
+    pub username: String,
+    pub display_name: String,
+}
+
+/// A valid email change token, and the address it confirms
+#[derive(Debug)]
+pub struct EmailChange {
+    pub uid: i32,
+    pub display_name: String,
+    pub email: String,
+}
+
+#[derive(Debug)]
+pub struct PendingInvitation {
+    pub uid: i32,
+    pub display_name: String,
+    pub email: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl UserTokens {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    /// Issues a token, replacing any unused one with the same purpose
+    pub async fn issue(
+        &self,
+        uid: i32,
+        purpose: TokenPurpose,
+        token_hash: &[u8],
+        valid_for: Duration,
+        created_by: Option<i32>,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM user_tokens WHERE uid = $1 AND purpose = $2 AND used_at IS NULL",
+            uid,
+            purpose as TokenPurpose
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO user_tokens (uid, purpose, token_hash, expires_at, created_by)
+            VALUES ($1, $2, $3, CURRENT_TIMESTAMP + make_interval(secs => $4), $5)",
+            uid,
+            purpose as TokenPurpose,
+            token_hash,
+            valid_for.as_secs_f64(),
+            created_by
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: &[u8],
+    ) -> Result<Option<TokenUser>, Error> {
+        let result = sqlx::query_as!(
+            TokenUser,
+            "SELECT u.uid, u.username, u.display_name
+            FROM user_tokens ut
+            JOIN users u ON u.uid = ut.uid
+            WHERE ut.token_hash = $1
+                AND ut.purpose = $2
+                AND ut.used_at IS NULL
+                AND ut.expires_at > CURRENT_TIMESTAMP
+                AND u.archived_at IS NULL",
+            token_hash,
+            purpose as TokenPurpose
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Uses up the token to set the user's password, signing them out
+    /// everywhere
+    pub async fn redeem(
+        &self,
+        purpose: TokenPurpose,
+        token_hash: &[u8],
+        password_hash: &str,
+    ) -> Result<i32, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let uid = sqlx::query_scalar!(
+            "UPDATE user_tokens ut
+            SET used_at = CURRENT_TIMESTAMP
+            FROM users u
+            WHERE u.uid = ut.uid
+                AND ut.token_hash = $1
+                AND ut.purpose = $2
+                AND ut.used_at IS NULL
+                AND ut.expires_at > CURRENT_TIMESTAMP
+                AND u.archived_at IS NULL
+            RETURNING ut.uid",
+            token_hash,
+            purpose as TokenPurpose
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        sqlx::query!(
+            "UPDATE users SET password_hash = $2, updated_at = CURRENT_TIMESTAMP WHERE uid = $1",
+            uid,
+            password_hash
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!("DELETE FROM user_sessions WHERE uid = $1", uid)
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+
+        Ok(uid)
+    }
+
+    /// Issues a token confirming `email` as the member's new address,
+    /// replacing any unconfirmed change
+    pub async fn issue_email_change(
+        &self,
+        uid: i32,
+        email: &str,
+        token_hash: &[u8],
+        valid_for: Duration,
+    ) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let taken = sqlx::query_scalar!(
+            r#"SELECT EXISTS (SELECT 1 FROM users WHERE email = $1 AND uid <> $2) AS "taken!""#,
+            email,
+            uid
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+        if taken {
+            return Err(Error::unprocessable_entity([(
+                "email",
+                "A member with this email already exists",
+            )]));
+        }
+
+        sqlx::query!(
+            "DELETE FROM user_tokens
+            WHERE uid = $1 AND purpose = 'email_change' AND used_at IS NULL",
+            uid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO user_tokens (uid, purpose, token_hash, expires_at, created_by, email)
+            VALUES ($1, 'email_change', $2, CURRENT_TIMESTAMP + make_interval(secs => $3), $1, $4)",
+            uid,
+            token_hash,
+            valid_for.as_secs_f64(),
+            email
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn find_email_change(&self, token_hash: &[u8]) -> Result<Option<EmailChange>, Error> {
+        let result = sqlx::query_as!(
+            EmailChange,
+            r#"SELECT u.uid, u.display_name, ut.email AS "email!"
+            FROM user_tokens ut
+            JOIN users u ON u.uid = ut.uid
+            WHERE ut.token_hash = $1
+                AND ut.purpose = 'email_change'
+                AND ut.used_at IS NULL
+                AND ut.expires_at > CURRENT_TIMESTAMP
+                AND u.archived_at IS NULL"#,
+            token_hash
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Uses up the token to replace the member's email with the one it
+    /// confirms
+    pub async fn confirm_email_change(&self, token_hash: &[u8]) -> Result<i32, Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let change = sqlx::query!(
+            r#"UPDATE user_tokens ut
+            SET used_at = CURRENT_TIMESTAMP
+            FROM users u
+            WHERE u.uid = ut.uid
+                AND ut.token_hash = $1
+                AND ut.purpose = 'email_change'
+                AND ut.used_at IS NULL
+                AND ut.expires_at > CURRENT_TIMESTAMP
+                AND u.archived_at IS NULL
+            RETURNING ut.uid, ut.email AS "email!""#,
+            token_hash
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+        sqlx::query!(
+            "UPDATE users SET email = $2, updated_at = CURRENT_TIMESTAMP WHERE uid = $1",
+            change.uid,
+            change.email
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(crate::users::constraint_error)?;
+
+        tx.commit().await?;
+
+        Ok(change.uid)
+    }
+
+    /// Members who haven't accepted their invitation yet
+    pub async fn list_pending_invitations(&self) -> Result<Vec<PendingInvitation>, Error> {
+        let result = sqlx::query_as!(
+            PendingInvitation,
+            "SELECT u.uid, u.display_name, u.email, ut.expires_at
+            FROM user_tokens ut
+            JOIN users u ON u.uid = ut.uid
+            WHERE ut.purpose = 'invitation'
+                AND ut.used_at IS NULL
+                AND u.password_hash IS NULL
+                AND u.archived_at IS NULL
+            ORDER BY ut.created_at DESC"
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+}
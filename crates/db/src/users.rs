@@ -1,22 +1,257 @@
+use chrono::NaiveDateTime;
 use sqlx::PgPool;
-use types::Error;
+use types::{Error, Role};
 
 #[derive(Clone)]
 pub struct Users {
     pool: PgPool,
 }
 
+#[derive(Debug)]
+pub struct User {
+    pub uid: i32,
+    pub username: String,
+    pub email: String,
+    pub phone_number: String,
+    pub display_name: String,
+    pub legal_name: String,
+}
+
+#[derive(Debug)]
+pub struct NewUser {
+    pub username: String,
+    pub email: String,
+    pub phone_number: String,
+    pub display_name: String,
+    pub legal_name: String,
+}
+
+/// A member as listed in the directory
+#[derive(Debug)]
+pub struct MemberSummary {
+    pub uid: i32,
+    pub username: String,
+    pub display_name: String,
+    pub legal_name: String,
+    pub email: String,
+    pub phone_number: String,
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    pub uid: i32,
+    pub username: String,
+    pub email: String,
+    pub phone_number: String,
+    pub display_name: String,
+    pub legal_name: String,
+    /// Invited but hasn't chosen a password yet
+    pub invited: bool,
+    pub created_at: NaiveDateTime,
+    pub archived: bool,
+    /// A new email waiting for the member to confirm it
+    pub pending_email: Option<String>,
+}
+
+/// The email is changed separately, once the new address is confirmed
+#[derive(Debug)]
+pub struct ProfileUpdate {
+    pub display_name: String,
+    pub phone_number: String,
+    /// Left unchanged if `None`
+    pub legal_name: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct UserRoles {
+    pub uid: i32,
+    pub username: String,
+    pub display_name: String,
+    pub roles: Vec<Role>,
+}
+
 impl Users {
     pub(crate) fn init(pool: PgPool) -> Self {
         Self { pool }
     }
 
+    pub async fn get_user(&self, uid: i32) -> Result<Option<User>, Error> {
+        let result = sqlx::query_as!(
+            User,
+            "SELECT uid, username, email, phone_number, display_name, legal_name
+            FROM users
+            WHERE uid = $1",
+            uid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Creates a user without a password, who sets one through an invitation
+    pub async fn create(&self, user: &NewUser) -> Result<i32, Error> {
+        let uid = sqlx::query_scalar!(
+            "INSERT INTO users (username, email, phone_number, display_name, legal_name)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING uid",
+            user.username,
+            user.email,
+            user.phone_number,
+            user.display_name,
+            user.legal_name
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(constraint_error)?;
+
+        Ok(uid)
+    }
+
+    /// Members matching `query`, which also matches their legal name and
+    /// contact details if `search_private` is set
+    pub async fn search(
+        &self,
+        query: Option<&str>,
+        search_private: bool,
+        archived: bool,
+    ) -> Result<Vec<MemberSummary>, Error> {
+        let result = sqlx::query_as!(
+            MemberSummary,
+            "SELECT uid, username, display_name, legal_name, email, phone_number
+            FROM users
+            WHERE (archived_at IS NOT NULL) = $3
+                AND (
+                    $1::TEXT IS NULL
+                    OR strpos(lower(display_name), lower($1)) > 0
+                    OR strpos(lower(username), lower($1)) > 0
+                    OR ($2 AND (
+                        strpos(lower(legal_name), lower($1)) > 0
+                        OR strpos(lower(email), lower($1)) > 0
+                        OR strpos(phone_number, $1) > 0
+                    ))
+                )
+            ORDER BY lower(display_name), uid",
+            query,
+            search_private,
+            archived
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn get_profile(&self, uid: i32) -> Result<Option<Profile>, Error> {
+        let result = sqlx::query_as!(
+            Profile,
+            r#"SELECT uid, username, email, phone_number, display_name, legal_name,
+                password_hash IS NULL AS "invited!",
+                created_at::TIMESTAMP AS "created_at!",
+                archived_at IS NOT NULL AS "archived!",
+                (SELECT ut.email FROM user_tokens ut
+                    WHERE ut.uid = users.uid
+                        AND ut.purpose = 'email_change'
+                        AND ut.used_at IS NULL
+                        AND ut.expires_at > CURRENT_TIMESTAMP
+                ) AS pending_email
+            FROM users
+            WHERE uid = $1"#,
+            uid
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn update_profile(&self, uid: i32, profile: &ProfileUpdate) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE users
+            SET display_name = $2, phone_number = $3,
+                legal_name = COALESCE($4, legal_name), updated_at = CURRENT_TIMESTAMP
+            WHERE uid = $1",
+            uid,
+            profile.display_name,
+            profile.phone_number,
+            profile.legal_name
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(constraint_error)?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Archiving signs the member out everywhere and cancels any pending
+    /// invitation or password reset
+    pub async fn set_archived(&self, uid: i32, archived: bool) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        let result = sqlx::query!(
+            "UPDATE users
+            SET archived_at = CASE WHEN $2 THEN CURRENT_TIMESTAMP END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE uid = $1 AND (archived_at IS NOT NULL) <> $2",
+            uid,
+            archived
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        if archived {
+            sqlx::query!("DELETE FROM user_sessions WHERE uid = $1", uid)
+                .execute(&mut *tx)
+                .await?;
+
+            sqlx::query!(
+                "DELETE FROM user_tokens WHERE uid = $1 AND used_at IS NULL",
+                uid
+            )
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    /// Looks up an active user by username or email, ignoring case, for
+    /// password resets. An exact match wins if the login is ambiguous.
+    pub async fn find_by_login(&self, login: &str) -> Result<Option<User>, Error> {
+        let result = sqlx::query_as!(
+            User,
+            "SELECT uid, username, email, phone_number, display_name, legal_name
+            FROM users
+            WHERE (LOWER(username) = LOWER($1) OR LOWER(email) = LOWER($1))
+                AND archived_at IS NULL
+            ORDER BY (username = $1 OR email = $1) DESC, uid
+            LIMIT 1",
+            login
+        )
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
     pub async fn get_userid_password_from_username(
         &self,
         username: &str,
     ) -> Result<Option<(i32, String)>, Error> {
         let result = sqlx::query!(
-            "SELECT uid, password_hash FROM users WHERE username = $1",
+            r#"SELECT uid, password_hash AS "password_hash!"
+            FROM users
+            WHERE username = $1 AND password_hash IS NOT NULL AND archived_at IS NULL"#,
             username
         )
         .fetch_optional(&self.pool)
@@ -26,11 +261,15 @@ impl Users {
         Ok(result)
     }
 
-    pub async fn create_session(&self, user_id: i32, token: &str) -> Result<(), Error> {
+    /// Records a successful login, replacing the password hash if it was
+    /// rehashed with newer parameters
+    pub async fn record_login(&self, uid: i32, password_hash: Option<&str>) -> Result<(), Error> {
         sqlx::query!(
-            "INSERT INTO user_sessions (uid, token) VALUES ($1, $2)",
-            user_id,
-            token
+            "UPDATE users
+            SET last_login_at = CURRENT_TIMESTAMP, password_hash = COALESCE($2, password_hash)
+            WHERE uid = $1",
+            uid,
+            password_hash
         )
         .execute(&self.pool)
         .await?;
@@ -38,12 +277,124 @@ impl Users {
         Ok(())
     }
 
-    pub async fn get_userid_from_session(&self, token: &str) -> Result<Option<i32>, Error> {
-        let result = sqlx::query!("SELECT uid FROM user_sessions WHERE token = $1", token)
-            .fetch_optional(&self.pool)
-            .await?
-            .map(|f| f.uid);
+    pub async fn get_roles(&self, uid: i32) -> Result<Vec<Role>, Error> {
+        let result = sqlx::query_scalar!(
+            r#"SELECT role AS "role: Role" FROM users_roles WHERE uid = $1 ORDER BY role"#,
+            uid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn list_roles(&self) -> Result<Vec<UserRoles>, Error> {
+        let result = sqlx::query_as!(
+            UserRoles,
+            r#"SELECT u.uid, u.username, u.display_name,
+                ARRAY_REMOVE(ARRAY_AGG(ur.role ORDER BY ur.role), NULL) AS "roles!: Vec<Role>"
+            FROM users u
+            LEFT JOIN users_roles ur ON ur.uid = u.uid
+            WHERE u.archived_at IS NULL
+            GROUP BY u.uid
+            ORDER BY u.display_name, u.uid"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
 
         Ok(result)
     }
+
+    /// Adds `role` to an active member's roles, returning their uid, or `None`
+    /// if there's no such member
+    pub async fn grant_role(&self, username: &str, role: Role) -> Result<Option<i32>, Error> {
+        let Some(uid) = sqlx::query_scalar!(
+            "SELECT uid FROM users WHERE username = $1 AND archived_at IS NULL",
+            username
+        )
+        .fetch_optional(&self.pool)
+        .await?
+        else {
+            return Ok(None);
+        };
+
+        let mut roles = self.get_roles(uid).await?;
+        if !roles.contains(&role) {
+            roles.push(role);
+            self.set_roles(uid, &roles).await?;
+        }
+
+        Ok(Some(uid))
+    }
+
+    pub async fn set_roles(&self, uid: i32, roles: &[Role]) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!(
+            "DELETE FROM users_roles WHERE uid = $1 AND NOT role = ANY($2)",
+            uid,
+            roles as &[Role]
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO users_roles (uid, role)
+            SELECT $1, UNNEST($2::user_role[])
+            ON CONFLICT DO NOTHING",
+            uid,
+            roles as &[Role]
+        )
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => Error::NotFound,
+            e => e.into(),
+        })?;
+
+        // Their sessions get new tokens on their next request
+        sqlx::query!(
+            "UPDATE user_sessions SET rotated_at = '-infinity' WHERE uid = $1",
+            uid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        // Roles above volunteer need a second factor, which they'll be asked
+        // to set up when they sign back in
+        sqlx::query!(
+            "DELETE FROM user_sessions
+            WHERE uid = $1
+                AND EXISTS (SELECT 1 FROM users_roles WHERE uid = $1 AND role <> 'volunteer')
+                AND NOT EXISTS (
+                    SELECT 1 FROM users WHERE uid = $1 AND totp_enabled_at IS NOT NULL
+                )",
+            uid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+}
+
+/// Maps the unique and check constraints on `users` to field errors
+pub(crate) fn constraint_error(e: sqlx::Error) -> Error {
+    match e {
+        sqlx::Error::Database(ref db) => match db.constraint() {
+            Some("users_username_key") => {
+                Error::unprocessable_entity([("username", "This username is taken")])
+            }
+            Some("users_email_key") => {
+                Error::unprocessable_entity([("email", "A member with this email already exists")])
+            }
+            Some("users_phone_number_check") => {
+                Error::unprocessable_entity([("phone_number", "Enter a 10 digit phone number")])
+            }
+            _ => e.into(),
+        },
+        e => e.into(),
+    }
 }
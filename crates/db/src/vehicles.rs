@@ -0,0 +1,234 @@
+use sqlx::PgPool;
+use types::Error;
+
+#[derive(Clone)]
+pub struct Vehicles {
+    pool: PgPool,
+}
+
+#[derive(Debug)]
+pub struct Vehicle {
+    pub vid: i32,
+    pub name: String,
+    pub notes: Option<String>,
+    pub requires_group_to_drive: Option<i32>,
+    pub group_name: Option<String>,
+    pub archived: bool,
+}
+
+#[derive(Debug)]
+pub struct NewVehicle {
+    pub name: String,
+    pub notes: Option<String>,
+    pub requires_group_to_drive: Option<i32>,
+}
+
+#[derive(Debug)]
+pub struct ShiftVehicle {
+    pub sid: i32,
+    pub vid: i32,
+    pub name: String,
+    pub group_name: Option<String>,
+    /// Someone signed up for the shift holds the group required to drive it
+    pub has_driver: bool,
+}
+
+impl Vehicles {
+    pub(crate) fn init(pool: PgPool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn list(&self) -> Result<Vec<Vehicle>, Error> {
+        let result = sqlx::query_as!(
+            Vehicle,
+            r#"SELECT v.vid, v.name, v.notes, v.requires_group_to_drive, g.name AS "group_name?",
+                v.archived_at IS NOT NULL AS "archived!"
+            FROM vehicles v
+            LEFT JOIN groups g ON g.gid = v.requires_group_to_drive
+            WHERE v.archived_at IS NULL
+            ORDER BY v.name"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Every vehicle including archived ones, for managing them
+    pub async fn list_all(&self) -> Result<Vec<Vehicle>, Error> {
+        let result = sqlx::query_as!(
+            Vehicle,
+            r#"SELECT v.vid, v.name, v.notes, v.requires_group_to_drive, g.name AS "group_name?",
+                v.archived_at IS NOT NULL AS "archived!"
+            FROM vehicles v
+            LEFT JOIN groups g ON g.gid = v.requires_group_to_drive
+            ORDER BY v.archived_at IS NOT NULL, v.name"#
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    pub async fn create(&self, vehicle: &NewVehicle) -> Result<i32, Error> {
+        let vid = sqlx::query_scalar!(
+            "INSERT INTO vehicles (name, notes, requires_group_to_drive)
+            VALUES ($1, $2, $3)
+            RETURNING vid",
+            vehicle.name,
+            vehicle.notes,
+            vehicle.requires_group_to_drive
+        )
+        .fetch_one(&self.pool)
+        .await
+        .map_err(unknown_group)?;
+
+        Ok(vid)
+    }
+
+    pub async fn update(&self, vid: i32, vehicle: &NewVehicle) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE vehicles
+            SET name = $2, notes = $3, requires_group_to_drive = $4,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE vid = $1",
+            vid,
+            vehicle.name,
+            vehicle.notes,
+            vehicle.requires_group_to_drive
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(unknown_group)?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    /// Archived vehicles stay on the shifts they were attached to but can't be
+    /// attached to more
+    pub async fn set_archived(&self, vid: i32, archived: bool) -> Result<(), Error> {
+        let result = sqlx::query!(
+            "UPDATE vehicles
+            SET archived_at = CASE WHEN $2 THEN CURRENT_TIMESTAMP END,
+                updated_at = CURRENT_TIMESTAMP
+            WHERE vid = $1 AND (archived_at IS NOT NULL) <> $2",
+            vid,
+            archived
+        )
+        .execute(&self.pool)
+        .await?;
+
+        if result.rows_affected() == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(())
+    }
+
+    pub async fn list_for_event(&self, eid: i32) -> Result<Vec<ShiftVehicle>, Error> {
+        let result = sqlx::query_as!(
+            ShiftVehicle,
+            r#"SELECT sv.sid, v.vid, v.name, g.name AS "group_name?",
+                (v.requires_group_to_drive IS NULL OR EXISTS (
+                    SELECT 1
+                    FROM users_shifts us
+                    JOIN users_groups_current ugc ON ugc.uid = us.uid
+                    WHERE us.sid = sv.sid
+                        AND us.archived_at IS NULL
+                        AND ugc.gid = v.requires_group_to_drive
+                        AND ugc.valid
+                )) AS "has_driver!"
+            FROM shifts_vehicles sv
+            JOIN shifts s ON s.sid = sv.sid
+            JOIN vehicles v ON v.vid = sv.vid
+            LEFT JOIN groups g ON g.gid = v.requires_group_to_drive
+            WHERE s.eid = $1
+            ORDER BY sv.sid, v.name"#,
+            eid
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(result)
+    }
+
+    /// Attaches the vehicle to the shift, provided someone signed up for the
+    /// shift can drive it
+    pub async fn attach(&self, sid: i32, vid: i32) -> Result<(), Error> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query!("SELECT sid FROM shifts WHERE sid = $1 FOR UPDATE", sid)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(Error::NotFound)?;
+
+        let vehicle = sqlx::query!(
+            r#"SELECT v.name, g.name AS "group_name?",
+                (v.requires_group_to_drive IS NULL OR EXISTS (
+                    SELECT 1
+                    FROM users_shifts us
+                    JOIN users_groups_current ugc ON ugc.uid = us.uid
+                    WHERE us.sid = $1
+                        AND us.archived_at IS NULL
+                        AND ugc.gid = v.requires_group_to_drive
+                        AND ugc.valid
+                )) AS "has_driver!"
+            FROM vehicles v
+            LEFT JOIN groups g ON g.gid = v.requires_group_to_drive
+            WHERE v.vid = $2 AND v.archived_at IS NULL"#,
+            sid,
+            vid
+        )
+        .fetch_optional(&mut *tx)
+        .await?
+        .ok_or_else(|| Error::unprocessable_entity([("vid", "Unknown vehicle")]))?;
+
+        if !vehicle.has_driver {
+            return Err(Error::unprocessable_entity([(
+                "vid",
+                format!(
+                    "No one signed up for this shift holds a valid {} to drive {}",
+                    vehicle.group_name.unwrap_or_default(),
+                    vehicle.name
+                ),
+            )]));
+        }
+
+        sqlx::query!(
+            "INSERT INTO shifts_vehicles (sid, vid) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+            sid,
+            vid
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    pub async fn detach(&self, sid: i32, vid: i32) -> Result<(), Error> {
+        sqlx::query!(
+            "DELETE FROM shifts_vehicles WHERE sid = $1 AND vid = $2",
+            sid,
+            vid
+        )
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+}
+
+fn unknown_group(e: sqlx::Error) -> Error {
+    match e {
+        sqlx::Error::Database(ref db) if db.is_foreign_key_violation() => {
+            Error::unprocessable_entity([("requires_group_to_drive", "Unknown group")])
+        }
+        e => e.into(),
+    }
+}
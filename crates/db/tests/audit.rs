@@ -0,0 +1,94 @@
+//! The audit triggers, run against a bundled PostgreSQL with
+//! `cargo test -- --ignored`. Set `TEST_DATABASE_URL` to use another server
+//! instead; its schema is reset.
+
+use db::{AuditAction, AuditEntry, AuditFilter, DB, NewGroup, NewUser, acting_as, embedded_db};
+use types::Role;
+
+async fn entries(db: &DB, table_name: &str) -> Vec<AuditEntry> {
+    let filter = AuditFilter {
+        table_name: Some(table_name.to_owned()),
+        ..Default::default()
+    };
+    let mut entries = db.audit_log.list(&filter, 100).await.unwrap();
+    entries.reverse();
+    entries
+}
+
+#[tokio::test]
+#[ignore = "starts PostgreSQL"]
+async fn records_actor_changes_and_target() {
+    let (url, _pg) = match std::env::var("TEST_DATABASE_URL") {
+        Ok(url) => (url, None),
+        Err(_) => {
+            let (url, pg) = embedded_db(None).await.unwrap();
+            (url, Some(pg))
+        }
+    };
+    // One connection, so an actor left on it would show up in later changes
+    let db = DB::init(&url, 1, "UTC", true).await.unwrap();
+
+    let uid = db
+        .users
+        .create(&NewUser {
+            username: "alice".to_owned(),
+            email: "alice@example.com".to_owned(),
+            phone_number: "6045550100".to_owned(),
+            display_name: "Alice".to_owned(),
+            legal_name: "Alice Example".to_owned(),
+        })
+        .await
+        .unwrap();
+
+    let mut group = NewGroup {
+        name: "First Aid".to_owned(),
+        is_qualification: false,
+        qualification_expiration_yrs: None,
+    };
+    let gid = acting_as(uid, db.groups.create(&group)).await.unwrap();
+    group.name = "Advanced First Aid".to_owned();
+    acting_as(uid, db.groups.update(gid, &group)).await.unwrap();
+    db.groups.set_archived(gid, true).await.unwrap();
+    acting_as(uid, db.users.set_roles(uid, &[Role::Admin]))
+        .await
+        .unwrap();
+
+    let users = entries(&db, "users").await;
+    assert_eq!(users.len(), 1);
+    assert_eq!(users[0].uid, None);
+    assert_eq!(users[0].action, AuditAction::Insert);
+    assert_eq!(users[0].target_id, uid.to_string());
+
+    let groups = entries(&db, "groups").await;
+    assert_eq!(groups.len(), 3);
+    assert!(
+        groups
+            .iter()
+            .all(|entry| entry.target_id == gid.to_string())
+    );
+
+    assert_eq!(groups[0].uid, Some(uid));
+    assert_eq!(groups[0].action, AuditAction::Insert);
+    assert_eq!(groups[0].old_values, None);
+
+    assert_eq!(groups[1].uid, Some(uid));
+    assert_eq!(groups[1].action, AuditAction::Update);
+    assert_eq!(
+        groups[1].old_values.as_deref(),
+        Some(r#"{"name": "First Aid"}"#)
+    );
+    assert_eq!(
+        groups[1].new_values.as_deref(),
+        Some(r#"{"name": "Advanced First Aid"}"#)
+    );
+
+    // Made outside `acting_as`, after the connection was used inside it
+    assert_eq!(groups[2].uid, None);
+    assert_eq!(groups[2].action, AuditAction::Update);
+
+    let roles = entries(&db, "users_roles").await;
+    assert_eq!(roles.len(), 1);
+    assert_eq!(roles[0].uid, Some(uid));
+    assert_eq!(roles[0].action, AuditAction::Insert);
+    assert_eq!(roles[0].target_id, format!("{uid},admin"));
+}
@@ -1,6 +1,6 @@
 pub use hypertext::{
     Attribute, GlobalAttributes, Raw, RenderIterator, Renderable, Rendered, VoidElement,
-    html_elements, rsx, rsx_move, rsx_static,
+    html_elements, maud_move, rsx, rsx_move, rsx_static,
 };
 
 pub trait HtmxAttributes: GlobalAttributes {
@@ -11,6 +11,8 @@ pub trait HtmxAttributes: GlobalAttributes {
     const hx_swap: Attribute = Attribute;
     const hx_push_url: Attribute = Attribute;
     const hx_boost: Attribute = Attribute;
+    const hx_trigger: Attribute = Attribute;
+    const hx_confirm: Attribute = Attribute;
 }
 
 impl<T: GlobalAttributes> HtmxAttributes for T {}
@@ -1,3 +1,4 @@
+mod permissions;
 mod type_alias;
 
 use std::{borrow::Cow, collections::HashMap};
@@ -10,6 +11,8 @@ use axum::{
 };
 use tracing::error;
 
+pub use crate::permissions::{Permission, Role};
+
 #[derive(thiserror::Error, Debug)]
 pub enum Error {
     #[error("Authentication required")]
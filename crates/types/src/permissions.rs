@@ -0,0 +1,54 @@
+#[derive(Debug, Clone, Copy, PartialEq, Eq, sqlx::Type)]
+#[sqlx(type_name = "user_role", rename_all = "snake_case")]
+pub enum Role {
+    Volunteer,
+    DutyLead,
+    Scheduler,
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Permission {
+    /// See members' contact details and qualifications
+    ViewMembers,
+    /// Create and edit events, shifts and vehicle assignments
+    ManageEvents,
+    /// Manage member accounts and their roles
+    ManageMembers,
+    /// Manage groups, event types and vehicles
+    ManageSettings,
+    /// Approve hours which have no duty lead to approve them
+    ApproveHours,
+}
+
+impl Role {
+    pub fn permissions(self) -> &'static [Permission] {
+        match self {
+            Role::Volunteer => &[],
+            Role::DutyLead => &[Permission::ViewMembers],
+            Role::Scheduler => &[Permission::ViewMembers, Permission::ManageEvents],
+            Role::Admin => &[
+                Permission::ViewMembers,
+                Permission::ManageEvents,
+                Permission::ManageMembers,
+                Permission::ManageSettings,
+                Permission::ApproveHours,
+            ],
+        }
+    }
+
+    /// Roles which can see members' personal details need a second factor to
+    /// sign in
+    pub fn requires_second_factor(self) -> bool {
+        self != Role::Volunteer
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Role::Volunteer => "Volunteer",
+            Role::DutyLead => "Duty Lead",
+            Role::Scheduler => "Scheduler",
+            Role::Admin => "Admin",
+        }
+    }
+}
@@ -0,0 +1,212 @@
+#![allow(non_snake_case)]
+
+use axum::{extract::State, response::IntoResponse};
+use components::Layout;
+use db::{Device, Qualification, TwoFactorStatus};
+use hypertext::*;
+use types::{Error, Permission};
+
+use crate::{
+    AppState,
+    auth::{AuthUser, Ctx},
+    calendar::CalendarSection,
+    qualifications::{Date, EXPIRY_WARNING_DAYS, QualificationStatus},
+};
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn account(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let user = state
+        .db
+        .users
+        .get_user(ctx.user_id)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let qualifications = state.db.groups.qualifications_for_user(ctx.user_id).await?;
+    let devices = state.db.sessions.list_devices(ctx.user_id).await?;
+    let two_factor = state.db.two_factor.get(ctx.user_id).await?;
+    let calendar_token = state.db.calendar_tokens.get(ctx.user_id).await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <div>
+                <h1 class="text-2xl font-medium">{&user.display_name}</h1>
+                <p class="text-sm text-neutral">
+                    {&user.username} " · " <a class="text-green font-medium" href=format!("/volunteers/{}", user.uid)>Profile</a>
+                </p>
+            </div>
+            {QualificationsSection(&qualifications)}
+            {Tools(&ctx)}
+            {CalendarSection(&calendar_token)}
+            {TwoFactorSection(&two_factor, &ctx)}
+            {SessionsSection(&devices, ctx.session_id)}
+        </div>
+    })
+    .render())
+}
+
+pub(crate) fn QualificationsSection(qualifications: &[Qualification]) -> impl Renderable {
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Qualifications</h2>
+            <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                {qualifications.iter().map(|qualification| rsx_move! {
+                    <li class="py-2 flex flex-wrap items-center justify-between gap-2">
+                        <div>
+                            <p class="font-medium">{&qualification.name}</p>
+                            <p class="text-sm text-neutral">
+                                {qualification.issued_at.map(|_| rsx_move! { "Issued " {Date(qualification.issued_at)} })}
+                                {qualification.expires_at.map(|_| rsx_move! { " · Expires " {Date(qualification.expires_at)} })}
+                            </p>
+                        </div>
+                        {QualificationStatus(qualification.valid, qualification.expires_at, EXPIRY_WARNING_DAYS)}
+                    </li>
+                }).render_all()}
+                {(qualifications.is_empty()).then(|| rsx! {
+                    <li class="py-2 text-neutral">No qualifications on record</li>
+                })}
+            </ul>
+        </section>
+    }
+}
+
+fn SessionsSection(devices: &[Device], current: i32) -> impl Renderable {
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Sessions</h2>
+            <div class="p-4 rounded-b-lg border border-green-light space-y-2">
+                <ul class="divide-y divide-neutral">
+                    {devices.iter().map(|device| DeviceRow(device, device.usid == current)).render_all()}
+                </ul>
+                <p class="text-sm text-neutral">Signing out everywhere ends your sessions on every device, including this one.</p>
+                <div class="flex flex-wrap gap-2">
+                    <button hx-post="/logout" class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light">Sign out</button>
+                    <button hx-post="/logout/all" hx-confirm="Sign out on every device?" class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white">Sign out everywhere</button>
+                </div>
+            </div>
+        </section>
+    }
+}
+
+fn TwoFactorSection<'a>(status: &'a TwoFactorStatus, ctx: &'a Ctx) -> impl Renderable + 'a {
+    let required = ctx.roles.iter().any(|role| role.requires_second_factor());
+
+    let body = move |output: &mut String| {
+        if status.enabled {
+            rsx_move! {
+                <p>"Signing in needs a code from your authenticator app. " {status.recovery_codes_left} " recovery codes left."</p>
+                <div class="flex flex-wrap gap-2">
+                    <button hx-post="/account/two-factor/recovery-codes" hx-target="#two-factor" hx-confirm="Replace your recovery codes? The current ones will stop working." class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light">New recovery codes</button>
+                </div>
+                {(!required).then(|| rsx! {
+                    <form hx-post="/account/two-factor/disable" hx-confirm="Turn off two-factor authentication?" class="flex flex-wrap items-end gap-2">
+                        <label class="block">
+                            <span class="text-sm font-medium">"Code from your authenticator app, or a recovery code"</span>
+                            <input type="text" name="code" autocomplete="one-time-code" class="w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green" required>
+                        </label>
+                        <input class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white cursor-pointer" type="submit" value="Turn off">
+                    </form>
+                    <p class="text-red text-sm" data-error-for="code"></p>
+                })}
+            }
+            .render_to(output)
+        } else {
+            rsx! {
+                <p>"Protect your account with a code from an authenticator app as well as your password."</p>
+                <a class="inline-block bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light" href="/account/two-factor">Set up</a>
+            }
+            .render_to(output)
+        }
+    };
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Two-Factor Authentication</h2>
+            <div id="two-factor" class="p-4 rounded-b-lg border border-green-light space-y-2" data-errors>
+                {body}
+                <p class="text-red text-sm" data-error-for="two_factor"></p>
+            </div>
+        </section>
+    }
+}
+
+fn DeviceRow(device: &Device, current: bool) -> impl Renderable {
+    let action = move |output: &mut String| {
+        if current {
+            rsx! {
+                <span class="text-xs px-2 py-0.5 rounded-full border border-green text-green">This device</span>
+            }
+            .render_to(output)
+        } else {
+            rsx_move! {
+                <button hx-post=format!("/account/sessions/{}/revoke", device.usid) class="text-sm text-red hover:underline">Revoke</button>
+            }
+            .render_to(output)
+        }
+    };
+
+    rsx_move! {
+        <li class="py-2 flex items-start justify-between gap-2">
+            <div class="min-w-0">
+                <p class="text-sm truncate">
+                    {device.user_agent.as_deref().unwrap_or("Unknown browser")}
+                </p>
+                <p class="text-xs text-neutral">
+                    {device.ip_address.as_ref().map(|ip| rsx_move! { {ip} " · " })}
+                    "Last active " {device.last_seen_at.format("%b %-d %H:%M").to_string()}
+                    " · Signed in " {device.created_at.format("%b %-d, %Y").to_string()}
+                </p>
+            </div>
+            {action}
+        </li>
+    }
+}
+
+/// Links to the pages the user's roles give them access to
+fn Tools(ctx: &Ctx) -> impl Renderable {
+    let tools = [
+        (
+            Permission::ViewMembers,
+            "/qualifications",
+            "Qualification roster",
+        ),
+        (
+            Permission::ManageMembers,
+            "/admin/invitations",
+            "Invitations",
+        ),
+        (
+            Permission::ManageMembers,
+            "/admin/login-attempts",
+            "Login attempts",
+        ),
+        (Permission::ManageMembers, "/admin/roles", "Roles"),
+        (Permission::ManageMembers, "/admin/audit-log", "Audit log"),
+        (Permission::ManageEvents, "/admin/import", "Import events"),
+        (Permission::ManageSettings, "/admin/groups", "Groups"),
+        (
+            Permission::ManageSettings,
+            "/admin/event-types",
+            "Event types",
+        ),
+        (Permission::ManageSettings, "/admin/vehicles", "Vehicles"),
+    ]
+    .into_iter()
+    .filter(|(permission, ..)| ctx.has(*permission))
+    .collect::<Vec<_>>();
+
+    (!tools.is_empty()).then(|| {
+        rsx_move! {
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Tools</h2>
+                <ul class="px-4 py-2 rounded-b-lg border border-green-light space-y-1">
+                    {tools.into_iter().map(|(_, href, label)| rsx_move! {
+                        <li><a class="text-green font-medium" href=href>{label}</a></li>
+                    }).render_all()}
+                </ul>
+            </section>
+        }
+    })
+}
@@ -0,0 +1,26 @@
+use hypertext::*;
+
+pub mod audit_log;
+pub mod event_types;
+pub mod groups;
+pub mod import;
+pub mod invitations;
+pub mod login_attempts;
+pub mod roles;
+pub mod vehicles;
+
+/// Posts to `{path}/archive` or `{path}/unarchive`
+#[allow(non_snake_case)]
+fn ArchiveButton(path: String, archived: bool) -> impl Renderable {
+    let (action, label) = if archived {
+        (format!("{path}/unarchive"), "Unarchive")
+    } else {
+        (format!("{path}/archive"), "Archive")
+    };
+
+    rsx_move! {
+        <form hx-post=action>
+            <input class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white cursor-pointer" type="submit" value=label>
+        </form>
+    }
+}
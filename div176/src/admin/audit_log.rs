@@ -0,0 +1,216 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDate;
+use components::{Layout, SelectOption};
+use db::{AuditAction, AuditActor, AuditEntry, AuditFilter};
+use hypertext::*;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser, forms::empty_as_none};
+
+/// How many changes are shown at once
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Deserialize, Debug)]
+pub struct AuditLogQuery {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    uid: Option<i32>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    table: Option<String>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    from: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    to: Option<NaiveDate>,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    before: Option<i64>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn audit_log_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    let filter = AuditFilter {
+        uid: query.uid,
+        table_name: query.table,
+        from: query.from,
+        to: query.to,
+        before: query.before,
+    };
+    let entries = state.db.audit_log.list(&filter, PAGE_SIZE).await?;
+    let actors = state.db.audit_log.list_actors().await?;
+    let tables = state.db.audit_log.list_tables().await?;
+
+    let older = (entries.len() as i64 == PAGE_SIZE)
+        .then(|| entries.last().map(|entry| older_link(&filter, entry.alid)))
+        .flatten();
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Audit Log</h1>
+            {FilterForm(&filter, &actors, &tables)}
+            {EntriesTable(&entries)}
+            {older.map(|href| rsx_move! {
+                <a class="text-green font-medium" href=href>"Older changes"</a>
+            })}
+        </div>
+    })
+    .render())
+}
+
+/// The same filter, continuing after `alid`
+fn older_link(filter: &AuditFilter, alid: i64) -> String {
+    let mut params = vec![format!("before={alid}")];
+    if let Some(uid) = filter.uid {
+        params.push(format!("uid={uid}"));
+    }
+    if let Some(table) = &filter.table_name {
+        params.push(format!("table={table}"));
+    }
+    if let Some(from) = filter.from {
+        params.push(format!("from={}", from.format("%Y-%m-%d")));
+    }
+    if let Some(to) = filter.to {
+        params.push(format!("to={}", to.format("%Y-%m-%d")));
+    }
+
+    format!("/admin/audit-log?{}", params.join("&"))
+}
+
+/// Each changed column with its old and new value
+fn changes(entry: &AuditEntry) -> Vec<(String, Option<String>, Option<String>)> {
+    let parse = |values: &Option<String>| {
+        values
+            .as_deref()
+            .and_then(|values| serde_json::from_str::<Map<String, Value>>(values).ok())
+            .unwrap_or_default()
+    };
+    let (old, new) = (parse(&entry.old_values), parse(&entry.new_values));
+
+    let mut columns: Vec<&String> = old.keys().collect();
+    columns.extend(new.keys().filter(|column| !old.contains_key(*column)));
+
+    columns
+        .into_iter()
+        .map(|column| {
+            (
+                column.clone(),
+                old.get(column).map(display),
+                new.get(column).map(display),
+            )
+        })
+        .collect()
+}
+
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("—"),
+        Value::String(value) => value.clone(),
+        value => value.to_string(),
+    }
+}
+
+fn FilterForm<'a>(
+    filter: &'a AuditFilter,
+    actors: &'a [AuditActor],
+    tables: &'a [String],
+) -> impl Renderable + 'a {
+    let input = "px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let date = |date: Option<NaiveDate>| {
+        date.map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+
+    rsx_move! {
+        <form method="get" action="/admin/audit-log" class="flex flex-wrap items-end gap-2">
+            <label class="block">
+                <span class="text-sm font-medium">Member</span>
+                <select name="uid" class=input>
+                    <option value="">Anyone</option>
+                    {actors.iter().map(|actor| {
+                        SelectOption(actor.uid.to_string(), &actor.display_name, Some(actor.uid) == filter.uid)
+                    }).render_all()}
+                </select>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Table</span>
+                <select name="table" class=input>
+                    <option value="">All</option>
+                    {tables.iter().map(|table| {
+                        SelectOption(table.clone(), table, Some(table) == filter.table_name.as_ref())
+                    }).render_all()}
+                </select>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">From</span>
+                <input type="date" name="from" value=date(filter.from) class=input>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">To</span>
+                <input type="date" name="to" value=date(filter.to) class=input>
+            </label>
+            <input class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Show">
+        </form>
+    }
+}
+
+fn EntriesTable(entries: &[AuditEntry]) -> impl Renderable {
+    rsx_move! {
+        <section class="overflow-x-auto">
+            <table class="w-full text-left text-sm">
+                <thead class="border-b border-neutral">
+                    <tr>
+                        <th class="py-1 pr-2">Time</th>
+                        <th class="py-1 pr-2">Member</th>
+                        <th class="py-1 pr-2">Change</th>
+                        <th class="py-1">Values</th>
+                    </tr>
+                </thead>
+                <tbody class="divide-y divide-neutral">
+                    {entries.iter().map(|entry| rsx_move! {
+                        <tr class="align-top">
+                            <td class="py-1 pr-2 whitespace-nowrap">{entry.created_at.format("%b %-d, %Y %H:%M:%S").to_string()}</td>
+                            <td class="py-1 pr-2">{entry.display_name.as_deref().unwrap_or("System")}</td>
+                            <td class="py-1 pr-2 whitespace-nowrap">
+                                {action_name(entry.action)} " "
+                                <span class="font-mono">{&entry.table_name} " " {&entry.target_id}</span>
+                            </td>
+                            <td class="py-1">
+                                <ul>
+                                    {changes(entry).into_iter().map(|(column, old, new)| rsx_move! {
+                                        <li>
+                                            <span class="font-mono">{column}</span> ": "
+                                            {old.map(|old| rsx_move! { <span class="text-red">{old}</span> })}
+                                            {(entry.action == AuditAction::Update).then_some(" → ")}
+                                            {new.map(|new| rsx_move! { <span class="text-green">{new}</span> })}
+                                        </li>
+                                    }).render_all()}
+                                </ul>
+                            </td>
+                        </tr>
+                    }).render_all()}
+                </tbody>
+            </table>
+            {entries.is_empty().then(|| rsx! {
+                <p class="text-neutral mt-2">No changes match</p>
+            })}
+        </section>
+    }
+}
+
+fn action_name(action: AuditAction) -> &'static str {
+    match action {
+        AuditAction::Insert => "Added",
+        AuditAction::Update => "Changed",
+        AuditAction::Delete => "Removed",
+    }
+}
@@ -0,0 +1,275 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::{Layout, SelectOption};
+use db::{EventType, Group, NewEventType};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use super::ArchiveButton;
+use crate::{AppState, auth::AuthUser};
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn event_types_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    let event_types = state.db.events.list_all_event_types().await?;
+    let qualifications = state.db.groups.list_qualifications().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Event Types</h1>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">New Event Type</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {EventTypeForm(None, &qualifications)}
+                </div>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Event Types</h2>
+                <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                    {event_types.iter().map(|event_type| EventTypeRow(event_type, &qualifications)).render_all()}
+                </ul>
+            </section>
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventTypeModel {
+    name: String,
+    colour: String,
+    event_level_signup: Option<String>,
+    hours_require_linked_signup: Option<String>,
+    description: String,
+    duty_lead_group: String,
+}
+
+impl EventTypeModel {
+    fn validate(self) -> Result<NewEventType, Error> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(Error::unprocessable_entity([("name", "Name is required")]));
+        }
+
+        // Colour inputs submit `#rrggbb`, stored without the `#`
+        let colour = self
+            .colour
+            .trim()
+            .trim_start_matches('#')
+            .to_ascii_uppercase();
+        let description = self.description.trim();
+        let duty_lead_group = match self.duty_lead_group.as_str() {
+            "" => None,
+            gid => Some(gid.parse().map_err(|_| {
+                Error::unprocessable_entity([("duty_lead_group", "Unknown group")])
+            })?),
+        };
+
+        Ok(NewEventType {
+            name: name.to_owned(),
+            colour: (!colour.is_empty()).then_some(colour),
+            event_level_signup: self.event_level_signup.is_some(),
+            hours_require_linked_signup: self.hours_require_linked_signup.is_some(),
+            description: (!description.is_empty()).then(|| description.to_owned()),
+            duty_lead_group,
+        })
+    }
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn create_event_type(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<EventTypeModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.events.create_event_type(&body.validate()?).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/event-types")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn update_event_type(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(etid): Path<i32>,
+    Form(body): Form<EventTypeModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state
+        .db
+        .events
+        .update_event_type(etid, &body.validate()?)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/event-types")]),
+    ))
+}
+
+/// Archived event types can't be used for new events or hours, but existing
+/// ones keep theirs
+#[tracing::instrument(skip(ctx, state))]
+pub async fn archive_event_type(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(etid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.events.set_event_type_archived(etid, true).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/event-types")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn unarchive_event_type(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(etid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.events.set_event_type_archived(etid, false).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/event-types")]),
+    ))
+}
+
+fn EventTypeRow<'a>(
+    event_type: &'a EventType,
+    qualifications: &'a [Group],
+) -> impl Renderable + 'a {
+    let colour = format!(
+        "background-color: #{}",
+        event_type.colour.as_deref().unwrap_or("FFFFFF")
+    );
+    let settings = [
+        (event_type.event_level_signup, "Sign up for the whole event"),
+        (
+            event_type.hours_require_linked_signup,
+            "Hours must be linked to a shift",
+        ),
+    ]
+    .into_iter()
+    .filter(|(set, _)| *set)
+    .map(|(_, label)| label.to_owned())
+    .chain(
+        event_type
+            .duty_lead_group_name
+            .as_ref()
+            .map(|group| format!("Duty leads need {group}")),
+    )
+    .collect::<Vec<_>>()
+    .join(" · ");
+
+    rsx_move! {
+        <li class="py-2 space-y-2">
+            <div class="flex items-center justify-between gap-2">
+                <div class="flex items-center gap-2">
+                    <span class="inline-block w-4 h-4 rounded border border-neutral" style=colour></span>
+                    <div>
+                        <p class="font-medium">
+                            {&event_type.name}
+                            {event_type.archived.then(|| rsx! { <span class="text-sm text-neutral">" (archived)"</span> })}
+                        </p>
+                        <p class="text-sm text-neutral">{settings}</p>
+                    </div>
+                </div>
+                {ArchiveButton(format!("/admin/event-types/{}", event_type.etid), event_type.archived)}
+            </div>
+            <details>
+                <summary class="text-green font-medium cursor-pointer">Edit</summary>
+                <div class="pt-2">{EventTypeForm(Some(event_type), qualifications)}</div>
+            </details>
+        </li>
+    }
+}
+
+fn EventTypeForm<'a>(
+    event_type: Option<&'a EventType>,
+    qualifications: &'a [Group],
+) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = match event_type {
+        Some(event_type) => format!("/admin/event-types/{}", event_type.etid),
+        None => String::from("/admin/event-types"),
+    };
+    let name = event_type.map_or("", |event_type| event_type.name.as_str());
+    let colour = format!(
+        "#{}",
+        event_type
+            .and_then(|event_type| event_type.colour.as_deref())
+            .unwrap_or("FFFFFF")
+            .to_ascii_lowercase()
+    );
+    let description = event_type
+        .and_then(|event_type| event_type.description.as_deref())
+        .unwrap_or_default();
+    let duty_lead_group = event_type.and_then(|event_type| event_type.duty_lead_group);
+    let event_level_signup = event_type.is_some_and(|event_type| event_type.event_level_signup);
+    let hours_require_linked_signup =
+        event_type.is_some_and(|event_type| event_type.hours_require_linked_signup);
+
+    rsx_move! {
+        <form hx-post=action class="grid desktop:grid-cols-2 gap-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">Name</span>
+                <input type="text" name="name" value=name class=input required>
+                <p class="text-red text-sm" data-error-for="name"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Colour</span>
+                <input type="color" name="colour" value=colour class="block h-8 w-16 border border-neutral rounded">
+                <p class="text-red text-sm" data-error-for="colour"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Duty leads need</span>
+                <select name="duty_lead_group" class=input>
+                    {SelectOption(String::new(), "Nothing", duty_lead_group.is_none())}
+                    {qualifications.iter().map(|group| SelectOption(group.gid.to_string(), &group.name, Some(group.gid) == duty_lead_group)).render_all()}
+                </select>
+                <p class="text-red text-sm" data-error-for="duty_lead_group"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Description</span>
+                <textarea name="description" rows="2" class=input>{description}</textarea>
+            </label>
+            <label class="flex items-center gap-2">
+                {event_level_signup.then(|| rsx! { <input type="checkbox" name="event_level_signup" checked> })}
+                {(!event_level_signup).then(|| rsx! { <input type="checkbox" name="event_level_signup"> })}
+                <span class="text-sm font-medium">Volunteers sign up for the whole event</span>
+            </label>
+            <label class="flex items-center gap-2">
+                {hours_require_linked_signup.then(|| rsx! { <input type="checkbox" name="hours_require_linked_signup" checked> })}
+                {(!hours_require_linked_signup).then(|| rsx! { <input type="checkbox" name="hours_require_linked_signup"> })}
+                <span class="text-sm font-medium">Hours must be linked to a shift</span>
+            </label>
+            <div class="desktop:col-span-2">
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value=if event_type.is_some() { "Save" } else { "Create" }>
+            </div>
+        </form>
+    }
+}
@@ -0,0 +1,217 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::Layout;
+use db::{Group, NewGroup};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use super::ArchiveButton;
+use crate::{AppState, auth::AuthUser};
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn groups_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    let groups = state.db.groups.list_all().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Groups</h1>
+            <p class="text-sm text-neutral">"Qualifications are groups members hold for a number of years, such as first aid or a driving endorsement."</p>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">New Group</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {GroupForm(None)}
+                </div>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Groups</h2>
+                <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                    {groups.iter().map(GroupRow).render_all()}
+                    {groups.is_empty().then(|| rsx! {
+                        <li class="py-2 text-sm text-neutral">No groups</li>
+                    })}
+                </ul>
+            </section>
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct GroupModel {
+    name: String,
+    is_qualification: Option<String>,
+    qualification_expiration_yrs: String,
+}
+
+impl GroupModel {
+    fn validate(self) -> Result<NewGroup, Error> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(Error::unprocessable_entity([("name", "Name is required")]));
+        }
+
+        let is_qualification = self.is_qualification.is_some();
+        let qualification_expiration_yrs = match self.qualification_expiration_yrs.trim() {
+            "" => None,
+            _ if !is_qualification => None,
+            years => match years.parse::<i32>() {
+                Ok(years @ 1..=100) => Some(years),
+                _ => {
+                    return Err(Error::unprocessable_entity([(
+                        "qualification_expiration_yrs",
+                        "Enter a number of years from 1 to 100, or leave empty if it never expires",
+                    )]));
+                }
+            },
+        };
+
+        Ok(NewGroup {
+            name: name.to_owned(),
+            is_qualification,
+            qualification_expiration_yrs,
+        })
+    }
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn create_group(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<GroupModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.groups.create(&body.validate()?).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/groups")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn update_group(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(gid): Path<i32>,
+    Form(body): Form<GroupModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.groups.update(gid, &body.validate()?).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/groups")]),
+    ))
+}
+
+/// Archived groups can no longer be granted or required for driving, but
+/// members keep them
+#[tracing::instrument(skip(ctx, state))]
+pub async fn archive_group(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(gid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.groups.set_archived(gid, true).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/groups")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn unarchive_group(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(gid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.groups.set_archived(gid, false).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/groups")]),
+    ))
+}
+
+fn GroupRow(group: &Group) -> impl Renderable + '_ {
+    rsx_move! {
+        <li class="py-2 space-y-2">
+            <div class="flex items-center justify-between gap-2">
+                <div>
+                    <p class="font-medium">
+                        {&group.name}
+                        {group.archived.then(|| rsx! { <span class="text-sm text-neutral">" (archived)"</span> })}
+                    </p>
+                    <p class="text-sm text-neutral">
+                        {match (group.is_qualification, group.qualification_expiration_yrs) {
+                            (false, _) => String::from("Group"),
+                            (true, None) => String::from("Qualification, never expires"),
+                            (true, Some(years)) => format!("Qualification, expires after {years} years"),
+                        }}
+                    </p>
+                </div>
+                {ArchiveButton(format!("/admin/groups/{}", group.gid), group.archived)}
+            </div>
+            <details>
+                <summary class="text-green font-medium cursor-pointer">Edit</summary>
+                <div class="pt-2">{GroupForm(Some(group))}</div>
+            </details>
+        </li>
+    }
+}
+
+fn GroupForm(group: Option<&Group>) -> impl Renderable + '_ {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = match group {
+        Some(group) => format!("/admin/groups/{}", group.gid),
+        None => String::from("/admin/groups"),
+    };
+    let name = group.map_or("", |group| group.name.as_str());
+    let is_qualification = group.is_some_and(|group| group.is_qualification);
+    let expiration = group
+        .and_then(|group| group.qualification_expiration_yrs)
+        .map(|years| years.to_string())
+        .unwrap_or_default();
+
+    rsx_move! {
+        <form hx-post=action class="grid desktop:grid-cols-2 gap-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">Name</span>
+                <input type="text" name="name" value=name class=input required>
+                <p class="text-red text-sm" data-error-for="name"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">"Expires after (years)"</span>
+                <input type="number" name="qualification_expiration_yrs" min="1" max="100" value=expiration placeholder="Never" class=input>
+                <p class="text-red text-sm" data-error-for="qualification_expiration_yrs"></p>
+            </label>
+            <label class="flex items-center gap-2 desktop:col-span-2">
+                {is_qualification.then(|| rsx! { <input type="checkbox" name="is_qualification" checked> })}
+                {(!is_qualification).then(|| rsx! { <input type="checkbox" name="is_qualification"> })}
+                <span class="text-sm font-medium">Qualification</span>
+            </label>
+            <div class="desktop:col-span-2">
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value=if group.is_some() { "Save" } else { "Create" }>
+            </div>
+        </form>
+    }
+}
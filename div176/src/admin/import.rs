@@ -0,0 +1,811 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Multipart, State, multipart::MultipartError},
+    response::{IntoResponse, Response},
+};
+use chrono::{Days, NaiveDate, NaiveDateTime, NaiveTime};
+use chrono_tz::Tz;
+use components::{Layout, SelectOption};
+use db::{EventType, ImportedEvent, NewEvent, NewShift};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser, forms::HxRequest, ical, scheduling::optional};
+
+const MAX_ROWS: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Csv,
+    Ical,
+}
+
+impl Format {
+    fn detect(file_name: Option<&str>, content: &str) -> Self {
+        let ics = file_name.is_some_and(|name| name.to_ascii_lowercase().ends_with(".ics"));
+        if ics || content.trim_start().starts_with("BEGIN:VCALENDAR") {
+            Self::Ical
+        } else {
+            Self::Csv
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Ical => "ics",
+        }
+    }
+}
+
+/// A row of the file, or an event of a calendar, as a shift to import
+#[derive(Debug)]
+struct Row {
+    line: usize,
+    event_name: String,
+    event_type: Option<(i32, String)>,
+    start_time: Option<NaiveDateTime>,
+    end_time: Option<NaiveDateTime>,
+    location: Option<String>,
+    organizer_details: Option<String>,
+    notes: Option<String>,
+    errors: Vec<String>,
+    /// An event of the same name already exists that day
+    duplicate: bool,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn import_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let event_types = state.db.events.list_event_types().await?;
+
+    Ok(Layout(ImportPage(&event_types, None, None, None)).render())
+}
+
+/// Shows what the uploaded file would import, carrying its contents along to
+/// the import itself so it needn't be uploaded again
+#[tracing::instrument(skip(ctx, state, multipart))]
+pub async fn preview_import(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    mut multipart: Multipart,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let event_types = state.db.events.list_event_types().await?;
+
+    let (upload, etid, preview) = match read_upload(&mut multipart).await {
+        Ok((upload, etid)) => {
+            let preview = match &upload {
+                Some((format, content)) if !content.trim().is_empty() => {
+                    rows(&state, *format, content, etid, &event_types).await?
+                }
+                _ => Err(String::from("Choose a CSV or iCalendar file")),
+            };
+            (upload, etid, preview)
+        }
+        Err(e) => (
+            None,
+            None,
+            Err(format!("The file couldn't be uploaded: {}", e.body_text())),
+        ),
+    };
+
+    Ok(Layout(match preview {
+        Ok(rows) => ImportPage(&event_types, etid, None, Some((upload, rows))),
+        Err(error) => ImportPage(&event_types, etid, Some(error), None),
+    })
+    .render())
+}
+
+/// The uploaded file and the event type chosen for it
+async fn read_upload(
+    multipart: &mut Multipart,
+) -> Result<(Option<(Format, String)>, Option<i32>), MultipartError> {
+    let mut upload = None;
+    let mut etid = None;
+    while let Some(field) = multipart.next_field().await? {
+        match field.name().map(str::to_owned).as_deref() {
+            Some("file") => {
+                let file_name = field.file_name().map(str::to_owned);
+                let content = field.text().await?;
+                let format = Format::detect(file_name.as_deref(), &content);
+                upload = Some((format, content));
+            }
+            Some("etid") => etid = field.text().await?.parse().ok(),
+            _ => (),
+        }
+    }
+
+    Ok((upload, etid))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ImportModel {
+    format: String,
+    etid: String,
+    content: String,
+}
+
+#[tracing::instrument(skip(ctx, state, body))]
+pub async fn import_events(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Form(body): Form<ImportModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let event_types = state.db.events.list_event_types().await?;
+        let format = match body.format.as_str() {
+            "ics" => Format::Ical,
+            _ => Format::Csv,
+        };
+        let rows = rows(
+            &state,
+            format,
+            &body.content,
+            body.etid.parse().ok(),
+            &event_types,
+        )
+        .await?
+        .map_err(|error| Error::unprocessable_entity([("import", error)]))?;
+
+        if rows.iter().any(|row| !row.errors.is_empty()) {
+            return Err(Error::unprocessable_entity([(
+                "import",
+                "Some rows have errors. Fix them in the file and preview it again.",
+            )]));
+        }
+        let events = events(&rows);
+        if events.is_empty() {
+            return Err(Error::unprocessable_entity([(
+                "import",
+                "There's nothing new to import",
+            )]));
+        }
+
+        state.db.events.import(&events).await?;
+
+        Ok(String::from("/events"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+/// Reads and checks the rows of a file. Problems with the file as a whole are
+/// the inner error.
+async fn rows(
+    state: &AppState,
+    format: Format,
+    content: &str,
+    etid: Option<i32>,
+    event_types: &[EventType],
+) -> Result<Result<Vec<Row>, String>, Error> {
+    let default = etid.and_then(|etid| event_types.iter().find(|t| t.etid == etid));
+    let rows = match format {
+        Format::Csv => csv_rows(content, default, event_types),
+        Format::Ical => ical_rows(content, default, event_types, state.config.time_zone),
+    };
+    let mut rows = match rows {
+        Ok(rows) if rows.is_empty() => {
+            return Ok(Err(String::from("The file has no events in it")));
+        }
+        Ok(rows) if rows.len() > MAX_ROWS => {
+            return Ok(Err(format!(
+                "Import at most {MAX_ROWS} events or shifts at a time"
+            )));
+        }
+        Ok(rows) => rows,
+        Err(error) => return Ok(Err(String::from(error))),
+    };
+
+    // The same shift twice in one file is almost certainly a mistake
+    for i in 0..rows.len() {
+        let repeat = rows[..i]
+            .iter()
+            .find(|earlier| {
+                earlier.event_name.eq_ignore_ascii_case(&rows[i].event_name)
+                    && earlier.start_time.is_some()
+                    && earlier.start_time == rows[i].start_time
+            })
+            .map(|earlier| earlier.line);
+        if let Some(line) = repeat {
+            rows[i].errors.push(format!("Repeats line {line}"));
+        }
+    }
+
+    let mut keys = rows.iter().filter_map(key).collect::<Vec<_>>();
+    keys.sort();
+    keys.dedup();
+    let duplicates = state.db.events.find_duplicates(&keys).await?;
+    for row in &mut rows {
+        row.duplicate = key(row).is_some_and(|key| {
+            duplicates
+                .iter()
+                .any(|(name, date)| name.eq_ignore_ascii_case(&key.0) && *date == key.1)
+        });
+    }
+
+    Ok(Ok(rows))
+}
+
+fn key(row: &Row) -> Option<(String, NaiveDate)> {
+    let date = row.start_time?.date();
+    (!row.event_name.is_empty()).then(|| (row.event_name.clone(), date))
+}
+
+/// Rows with the same name, date and type become shifts of one event. Rows
+/// with errors and duplicates of existing events are left out.
+fn events(rows: &[Row]) -> Vec<ImportedEvent> {
+    let mut events: Vec<(String, NaiveDate, ImportedEvent)> = Vec::new();
+
+    for row in rows
+        .iter()
+        .filter(|row| row.errors.is_empty() && !row.duplicate)
+    {
+        let (Some((etid, _)), Some(start_time), Some(end_time)) =
+            (&row.event_type, row.start_time, row.end_time)
+        else {
+            continue;
+        };
+        let shift = NewShift {
+            start_time,
+            end_time,
+            duty_lead: None,
+        };
+
+        let existing = events.iter_mut().find(|(name, date, imported)| {
+            name.eq_ignore_ascii_case(&row.event_name)
+                && *date == start_time.date()
+                && imported.event.etid == *etid
+        });
+        match existing {
+            Some((_, _, imported)) => imported.shifts.push(shift),
+            None => events.push((
+                row.event_name.clone(),
+                start_time.date(),
+                ImportedEvent {
+                    event: NewEvent {
+                        etid: *etid,
+                        event_name: row.event_name.clone(),
+                        organizer_details: row.organizer_details.clone(),
+                        location: row.location.clone(),
+                        notes: row.notes.clone(),
+                    },
+                    shifts: vec![shift],
+                },
+            )),
+        }
+    }
+
+    events.into_iter().map(|(.., imported)| imported).collect()
+}
+
+/// Needs a header row naming at least the name, date, start and end columns
+fn csv_rows(
+    content: &str,
+    default: Option<&EventType>,
+    event_types: &[EventType],
+) -> Result<Vec<Row>, &'static str> {
+    let mut records = csv(content).into_iter();
+    let (_, header) = records.next().ok_or("The file is empty")?;
+    let columns = header
+        .iter()
+        .map(|column| column.trim().to_ascii_lowercase().replace(' ', "_"))
+        .collect::<Vec<_>>();
+    let column = |names: &[&str]| columns.iter().position(|c| names.contains(&c.as_str()));
+
+    let name = column(&["name", "event", "event_name"]).ok_or("The file needs a name column")?;
+    let date = column(&["date"]).ok_or("The file needs a date column")?;
+    let start = column(&["start", "start_time"]).ok_or("The file needs a start column")?;
+    let end = column(&["end", "end_time"]).ok_or("The file needs an end column")?;
+    let type_name = column(&["type", "event_type"]);
+    let location = column(&["location"]);
+    let organizer = column(&["organizer", "organiser", "organizer_details"]);
+    let notes = column(&["notes", "description"]);
+
+    let rows = records
+        .filter(|(_, fields)| fields.iter().any(|field| !field.trim().is_empty()))
+        .map(|(line, fields)| {
+            let get = |column: Option<usize>| {
+                column
+                    .and_then(|column| fields.get(column))
+                    .and_then(|field| optional(field))
+            };
+            let mut errors = Vec::new();
+
+            let event_name = get(Some(name)).unwrap_or_default();
+            if event_name.is_empty() {
+                errors.push(String::from("Name is required"));
+            }
+            let event_type =
+                event_type(get(type_name).as_deref(), default, event_types, &mut errors);
+
+            let date =
+                get(Some(date)).and_then(|date| NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok());
+            if date.is_none() {
+                errors.push(String::from("Date must look like 2025-07-01"));
+            }
+            let start = get(Some(start)).and_then(|time| parse_time(&time));
+            let end = get(Some(end)).and_then(|time| parse_time(&time));
+            if start.is_none() || end.is_none() {
+                errors.push(String::from("Start and end must be times like 19:00"));
+            }
+
+            let (start_time, end_time) = match (date, start, end) {
+                (Some(date), Some(start), Some(end)) => {
+                    let start_time = date.and_time(start);
+                    let mut end_time = date.and_time(end);
+                    // Shifts ending earlier in the day run past midnight
+                    if end < start {
+                        end_time = end_time + Days::new(1);
+                    }
+                    (Some(start_time), Some(end_time))
+                }
+                _ => (None, None),
+            };
+            if start.is_some() && start == end {
+                errors.push(String::from("The shift must end after it starts"));
+            }
+
+            Row {
+                line,
+                event_name,
+                event_type,
+                start_time,
+                end_time,
+                location: get(location),
+                organizer_details: get(organizer),
+                notes: get(notes),
+                errors,
+                duplicate: false,
+            }
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+/// Each event in the calendar is a shift. Its categories can name the event
+/// type, and its times are moved into the division's `zone`.
+fn ical_rows(
+    content: &str,
+    default: Option<&EventType>,
+    event_types: &[EventType],
+    zone: Tz,
+) -> Result<Vec<Row>, &'static str> {
+    if !content.trim_start().starts_with("BEGIN:VCALENDAR") {
+        return Err("The file isn't an iCalendar file");
+    }
+
+    let rows = ical::parse(content)
+        .into_iter()
+        .map(|event| {
+            let mut errors = Vec::new();
+
+            let event_name = event.text("SUMMARY").unwrap_or_default().trim().to_owned();
+            if event_name.is_empty() {
+                errors.push(String::from("Name (SUMMARY) is required"));
+            }
+            let category = event.text("CATEGORIES").and_then(|categories| {
+                categories
+                    .split(',')
+                    .map(str::trim)
+                    .find(|category| {
+                        event_types
+                            .iter()
+                            .any(|t| t.name.eq_ignore_ascii_case(category))
+                    })
+                    .map(str::to_owned)
+            });
+            let event_type = event_type(category.as_deref(), default, event_types, &mut errors);
+
+            let mut date_time = |name, missing: &str| match event.date_time(name, zone) {
+                Ok(Some(time)) => Some(time),
+                Ok(None) => {
+                    errors.push(String::from(missing));
+                    None
+                }
+                Err(error) => {
+                    errors.push(error);
+                    None
+                }
+            };
+            let start_time = date_time("DTSTART", "Needs a start time, not just a date");
+            let end_time = date_time("DTEND", "Needs an end time, not just a date");
+            if let (Some(start_time), Some(end_time)) = (start_time, end_time)
+                && end_time <= start_time
+            {
+                errors.push(String::from("The shift must end after it starts"));
+            }
+
+            let organizer_details = event.get("ORGANIZER").and_then(|organizer| {
+                let address = organizer.value().trim();
+                let address = address
+                    .strip_prefix("mailto:")
+                    .or_else(|| address.strip_prefix("MAILTO:"))
+                    .unwrap_or(address);
+                match organizer.param("CN") {
+                    Some(name) if !address.is_empty() => Some(format!("{name} <{address}>")),
+                    Some(name) => Some(name.to_owned()),
+                    None => optional(address),
+                }
+            });
+
+            Row {
+                line: event.line,
+                event_name,
+                event_type,
+                start_time,
+                end_time,
+                location: event
+                    .text("LOCATION")
+                    .and_then(|location| optional(&location)),
+                organizer_details,
+                notes: event.text("DESCRIPTION").and_then(|notes| optional(&notes)),
+                errors,
+                duplicate: false,
+            }
+        })
+        .collect();
+
+    Ok(rows)
+}
+
+fn event_type(
+    name: Option<&str>,
+    default: Option<&EventType>,
+    event_types: &[EventType],
+    errors: &mut Vec<String>,
+) -> Option<(i32, String)> {
+    let event_type = match name {
+        Some(name) => {
+            let found = event_types
+                .iter()
+                .find(|t| t.name.eq_ignore_ascii_case(name));
+            if found.is_none() {
+                errors.push(format!("Unknown event type {name}"));
+            }
+            found
+        }
+        None => {
+            if default.is_none() {
+                errors.push(String::from("Choose an event type"));
+            }
+            default
+        }
+    };
+
+    event_type.map(|t| (t.etid, t.name.clone()))
+}
+
+fn parse_time(value: &str) -> Option<NaiveTime> {
+    ["%H:%M", "%H:%M:%S", "%I:%M %p", "%I:%M%p"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value.trim(), format).ok())
+}
+
+/// Splits CSV (RFC 4180) into records, each with the line it starts on.
+/// Quoted fields may contain commas, newlines and doubled quotes.
+fn csv(content: &str) -> Vec<(usize, Vec<String>)> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut line = 1;
+    let mut start = 1;
+    let mut chars = content.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => record.push(std::mem::take(&mut field)),
+            ('\r', false) => (),
+            ('\n', false) => {
+                record.push(std::mem::take(&mut field));
+                records.push((start, std::mem::take(&mut record)));
+                line += 1;
+                start = line;
+            }
+            (c, _) => {
+                if c == '\n' {
+                    line += 1;
+                }
+                field.push(c);
+            }
+        }
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push((start, record));
+    }
+
+    records
+}
+
+type Preview = (Option<(Format, String)>, Vec<Row>);
+
+fn ImportPage<'a>(
+    event_types: &'a [EventType],
+    etid: Option<i32>,
+    error: Option<String>,
+    preview: Option<Preview>,
+) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Import Events</h1>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Upload</h2>
+                <div class="p-4 rounded-b-lg border border-green-light space-y-3">
+                    <p class="text-sm">
+                        "Import coverage requests from a CSV file with name, date, start and end columns, and optionally type, location, organizer and notes. "
+                        "Dates look like 2025-07-01 and times like 19:00, and a shift ending before it starts runs past midnight. "
+                        "Rows with the same name and date become shifts of one event."
+                    </p>
+                    <p class="text-sm">"iCalendar (.ics) files work too, with each calendar event becoming a shift."</p>
+                    <form method="post" action="/admin/import/preview" enctype="multipart/form-data" class="grid desktop:grid-cols-2 gap-3">
+                        <label class="block">
+                            <span class="text-sm font-medium">File</span>
+                            <input type="file" name="file" accept=".csv,.ics,text/csv,text/calendar" class=input required>
+                            {error.map(|error| rsx_move! { <p class="text-red text-sm">{error}</p> })}
+                        </label>
+                        <label class="block">
+                            <span class="text-sm font-medium">Event type</span>
+                            <select name="etid" class=input>
+                                {SelectOption(String::new(), "As set in the file", etid.is_none())}
+                                {event_types.iter().map(|event_type| SelectOption(event_type.etid.to_string(), &event_type.name, Some(event_type.etid) == etid)).render_all()}
+                            </select>
+                        </label>
+                        <div class="desktop:col-span-2">
+                            <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Preview">
+                        </div>
+                    </form>
+                </div>
+            </section>
+            {preview.map(|(upload, rows)| PreviewSection(upload, rows, etid))}
+        </div>
+    }
+}
+
+fn PreviewSection(
+    upload: Option<(Format, String)>,
+    rows: Vec<Row>,
+    etid: Option<i32>,
+) -> impl Renderable {
+    let events = events(&rows);
+    let shifts = events.iter().map(|event| event.shifts.len()).sum::<usize>();
+    let duplicates = rows.iter().filter(|row| row.duplicate).count();
+    let invalid = rows.iter().filter(|row| !row.errors.is_empty()).count();
+    let (format, content) = upload.unwrap_or((Format::Csv, String::new()));
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Preview</h2>
+            <div class="p-4 rounded-b-lg border border-green-light space-y-3">
+                <p>
+                    {events.len()} {if events.len() == 1 { " event with " } else { " events with " }}
+                    {shifts} {if shifts == 1 { " shift to import" } else { " shifts to import" }}
+                    {(duplicates > 0).then(|| rsx_move! { " · " {duplicates} " skipped as already scheduled" })}
+                    {(invalid > 0).then(|| rsx_move! { " · " <span class="text-red">{invalid} {if invalid == 1 { " row" } else { " rows" }} " with errors"</span> })}
+                </p>
+                <div class="overflow-x-auto">
+                    <table class="w-full text-left text-sm">
+                        <thead class="border-b border-neutral">
+                            <tr>
+                                <th class="py-1 pr-2">Line</th>
+                                <th class="py-1 pr-2">Event</th>
+                                <th class="py-1 pr-2">Type</th>
+                                <th class="py-1 pr-2">Shift</th>
+                                <th class="py-1 pr-2">Location</th>
+                                <th class="py-1">Status</th>
+                            </tr>
+                        </thead>
+                        <tbody class="divide-y divide-neutral">
+                            {rows.iter().map(PreviewRow).render_all()}
+                        </tbody>
+                    </table>
+                </div>
+                {(invalid > 0).then(|| rsx! {
+                    <p class="text-red">"Fix the rows with errors in the file and preview it again."</p>
+                })}
+                {(invalid == 0 && !events.is_empty()).then(|| rsx_move! {
+                    <form method="post" action="/admin/import" hx-post="/admin/import" data-errors>
+                        <input type="hidden" name="format" value=format.as_str()>
+                        <input type="hidden" name="etid" value=etid.map(|etid| etid.to_string()).unwrap_or_default()>
+                        <textarea name="content" class="hidden">{content}</textarea>
+                        <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Import">
+                        <p class="text-red text-sm" data-error-for="import"></p>
+                    </form>
+                })}
+            </div>
+        </section>
+    }
+}
+
+fn PreviewRow(row: &Row) -> impl Renderable + '_ {
+    let shift = match (row.start_time, row.end_time) {
+        (Some(start), Some(end)) => format!(
+            "{} {}–{}",
+            start.format("%a, %b %-d, %Y"),
+            start.format("%H:%M"),
+            end.format("%H:%M")
+        ),
+        _ => String::new(),
+    };
+
+    rsx_move! {
+        <tr>
+            <td class="py-1 pr-2 text-neutral">{row.line}</td>
+            <td class="py-1 pr-2">{&row.event_name}</td>
+            <td class="py-1 pr-2">{row.event_type.as_ref().map(|(_, name)| name)}</td>
+            <td class="py-1 pr-2 whitespace-nowrap">{shift}</td>
+            <td class="py-1 pr-2">{row.location.as_ref()}</td>
+            <td class="py-1">
+                {(!row.errors.is_empty()).then(|| rsx_move! {
+                    <ul class="text-red">
+                        {row.errors.iter().map(|error| rsx_move! { <li>{error}</li> }).render_all()}
+                    </ul>
+                })}
+                {(row.errors.is_empty() && row.duplicate).then(|| rsx! {
+                    <span class="text-neutral">Already exists</span>
+                })}
+                {(row.errors.is_empty() && !row.duplicate).then(|| rsx! {
+                    <span class="text-green">Ready</span>
+                })}
+            </td>
+        </tr>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event_types() -> Vec<EventType> {
+        ["Duty", "Training"]
+            .into_iter()
+            .zip(1..)
+            .map(|(name, etid)| EventType {
+                etid,
+                name: String::from(name),
+                colour: None,
+                event_level_signup: false,
+                hours_require_linked_signup: true,
+                description: None,
+                duty_lead_group: None,
+                duty_lead_group_name: None,
+                archived: false,
+            })
+            .collect()
+    }
+
+    fn at(date: &str, time: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(&format!("{date} {time}"), "%Y-%m-%d %H:%M").ok()
+    }
+
+    #[test]
+    fn reads_csv_shifts_past_midnight() {
+        let event_types = event_types();
+        let rows = csv_rows(
+            "Name,Date,Start,End,Type\nNew Year's Eve,2026-12-31,20:00,02:00,duty\n",
+            None,
+            &event_types,
+        )
+        .unwrap();
+
+        assert_eq!(rows.len(), 1);
+        assert!(rows[0].errors.is_empty(), "{:?}", rows[0].errors);
+        assert_eq!(rows[0].line, 2);
+        assert_eq!(rows[0].event_type, Some((1, String::from("Duty"))));
+        assert_eq!(rows[0].start_time, at("2026-12-31", "20:00"));
+        assert_eq!(rows[0].end_time, at("2027-01-01", "02:00"));
+    }
+
+    #[test]
+    fn needs_csv_columns_and_values() {
+        let event_types = event_types();
+
+        assert_eq!(
+            csv_rows(
+                "name,date,start\nFun Run,2026-07-01,09:00\n",
+                None,
+                &event_types
+            )
+            .err(),
+            Some("The file needs an end column")
+        );
+
+        let rows = csv_rows(
+            "name,date,start,end\n,July 1,9am,12:00\n",
+            None,
+            &event_types,
+        )
+        .unwrap();
+        assert_eq!(
+            rows[0].errors,
+            [
+                "Name is required",
+                "Choose an event type",
+                "Date must look like 2025-07-01",
+                "Start and end must be times like 19:00",
+            ]
+        );
+    }
+
+    #[test]
+    fn groups_rows_into_events_by_name_date_and_type() {
+        let event_types = event_types();
+        let mut rows = csv_rows(
+            "name,date,start,end,type\n\
+            Marathon,2026-10-04,06:00,10:00,Duty\n\
+            marathon,2026-10-04,09:00,13:00,Duty\n\
+            Marathon,2026-10-05,06:00,10:00,Duty\n\
+            Marathon,2026-10-04,06:00,10:00,Training\n\
+            Marathon,2026-10-06,06:00,10:00,Duty\n\
+            Marathon,2026-10-07,06:00,10:00,Unknown\n",
+            None,
+            &event_types,
+        )
+        .unwrap();
+        rows[4].duplicate = true;
+
+        let events = events(&rows);
+        let summary = events
+            .iter()
+            .map(|imported| {
+                (
+                    imported.event.event_name.as_str(),
+                    imported.event.etid,
+                    imported.shifts.len(),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            summary,
+            [("Marathon", 1, 2), ("Marathon", 1, 1), ("Marathon", 2, 1)]
+        );
+        assert_eq!(
+            events[0].shifts[1].start_time,
+            at("2026-10-04", "09:00").unwrap()
+        );
+    }
+
+    #[test]
+    fn splits_quoted_csv_fields() {
+        assert_eq!(
+            csv(
+                "\u{feff}name,notes\r\n\"Swim, Bike, Run\",\"Say \"\"hi\"\"\nat the gate\"\r\nFun Run,\n"
+            ),
+            [
+                (1, vec![String::from("name"), String::from("notes")]),
+                (
+                    2,
+                    vec![
+                        String::from("Swim, Bike, Run"),
+                        String::from("Say \"hi\"\nat the gate")
+                    ]
+                ),
+                (4, vec![String::from("Fun Run"), String::new()]),
+            ]
+        );
+    }
+}
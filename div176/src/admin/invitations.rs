@@ -0,0 +1,160 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::Layout;
+use db::{NewUser, PendingInvitation};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser, credentials::send_invitation, volunteers::validate_contact};
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn invitations_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    let pending = state.db.user_tokens.list_pending_invitations().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Invitations</h1>
+            {InviteForm()}
+            {PendingSection(&pending)}
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct InviteModel {
+    username: String,
+    display_name: String,
+    legal_name: String,
+    email: String,
+    phone_number: String,
+}
+
+/// Creates the member without a password and emails them a link to choose one
+#[tracing::instrument(skip(ctx, state))]
+pub async fn invite(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<InviteModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    let (display_name, email, phone_number) =
+        validate_contact(&body.display_name, &body.email, &body.phone_number)?;
+    let user = NewUser {
+        username: body.username.trim().to_owned(),
+        display_name,
+        legal_name: body.legal_name.trim().to_owned(),
+        email,
+        phone_number,
+    };
+
+    let uid = state.db.users.create(&user).await?;
+    send_invitation(&state, uid, ctx.user_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/invitations")]),
+    ))
+}
+
+/// Replaces the member's invitation with a fresh one
+#[tracing::instrument(skip(ctx, state))]
+pub async fn resend(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(uid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    send_invitation(&state, uid, ctx.user_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/invitations")]),
+    ))
+}
+
+fn InviteForm() -> impl Renderable {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Invite a Member</h2>
+            <form hx-post="/admin/invitations" class="p-4 rounded-b-lg border border-green-light grid desktop:grid-cols-2 gap-3" data-errors>
+                <label class="block">
+                    <span class="text-sm font-medium">Username</span>
+                    <input type="text" name="username" class=input required>
+                    <p class="text-red text-sm" data-error-for="username"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Display name</span>
+                    <input type="text" name="display_name" class=input required>
+                    <p class="text-red text-sm" data-error-for="display_name"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Legal name</span>
+                    <input type="text" name="legal_name" class=input required>
+                    <p class="text-red text-sm" data-error-for="legal_name"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Email</span>
+                    <input type="email" name="email" class=input required>
+                    <p class="text-red text-sm" data-error-for="email"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Phone number</span>
+                    <input type="tel" name="phone_number" placeholder="250 555 0123" class=input required>
+                    <p class="text-red text-sm" data-error-for="phone_number"></p>
+                </label>
+                <div class="desktop:col-span-2">
+                    <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Send Invitation">
+                </div>
+            </form>
+        </section>
+    }
+}
+
+fn PendingSection(pending: &[PendingInvitation]) -> impl Renderable {
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Pending</h2>
+            <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                {pending.iter().map(|invitation| rsx_move! {
+                    <li class="py-2 flex items-center justify-between gap-2">
+                        <div>
+                            <p class="font-medium">{&invitation.display_name}</p>
+                            <p class="text-sm text-neutral">
+                                {&invitation.email} " · "
+                                {if invitation.expires_at < chrono::Utc::now() {
+                                    String::from("expired")
+                                } else {
+                                    format!("expires {}", invitation.expires_at.format("%b %-d"))
+                                }}
+                            </p>
+                        </div>
+                        <form hx-post=format!("/admin/invitations/{}/resend", invitation.uid) data-errors>
+                            <input class="bg-white-true text-green border border-green px-2 py-0.5 shadow-sm rounded hover:bg-green hover:text-white cursor-pointer" type="submit" value="Resend">
+                            <p class="text-red text-sm" data-error-for="email"></p>
+                        </form>
+                    </li>
+                }).render_all()}
+                {pending.is_empty().then(|| rsx! {
+                    <li class="py-2 text-sm text-neutral">No pending invitations</li>
+                })}
+            </ul>
+        </section>
+    }
+}
@@ -0,0 +1,141 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::State,
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::Layout;
+use db::{Lockout, LoginAttempt, LoginOutcome};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser};
+
+/// How many failed attempts the page shows
+const SHOWN_FAILURES: i64 = 200;
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn login_attempts_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    let lockouts = state.db.login_attempts.list_lockouts().await?;
+    let failures = state
+        .db
+        .login_attempts
+        .list_failures(SHOWN_FAILURES)
+        .await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Login Attempts</h1>
+            {LockoutsSection(&lockouts)}
+            {FailuresTable(&failures)}
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct UnlockModel {
+    username: String,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn unlock(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<UnlockModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    state.db.login_attempts.unlock(&body.username).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(
+            HeaderName::from_static("hx-redirect"),
+            "/admin/login-attempts",
+        )]),
+    ))
+}
+
+fn LockoutsSection(lockouts: &[Lockout]) -> impl Renderable {
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Locked Out</h2>
+            <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                {lockouts.iter().map(|lockout| rsx_move! {
+                    <li class="py-2 flex items-center justify-between gap-2">
+                        <div>
+                            <p class="font-medium">{&lockout.username}</p>
+                            <p class="text-sm text-neutral">
+                                {lockout.failures} " failures · until " {lockout.locked_until.format("%b %-d %H:%M").to_string()}
+                            </p>
+                        </div>
+                        <form hx-post="/admin/login-attempts/unlock">
+                            <input type="hidden" name="username" value=&lockout.username>
+                            <input class="bg-white-true text-green border border-green px-2 py-0.5 shadow-sm rounded hover:bg-green hover:text-white cursor-pointer" type="submit" value="Unlock">
+                        </form>
+                    </li>
+                }).render_all()}
+                {lockouts.is_empty().then(|| rsx! {
+                    <li class="py-2 text-sm text-neutral">Nobody is locked out</li>
+                })}
+            </ul>
+        </section>
+    }
+}
+
+fn FailuresTable(failures: &[LoginAttempt]) -> impl Renderable {
+    rsx_move! {
+        <section class="overflow-x-auto">
+            <h2 class="font-medium mb-1">Recent Failures</h2>
+            <table class="w-full text-left text-sm">
+                <thead class="border-b border-neutral">
+                    <tr>
+                        <th class="py-1 pr-2">Time</th>
+                        <th class="py-1 pr-2">Username</th>
+                        <th class="py-1 pr-2">Reason</th>
+                        <th class="py-1 pr-2">Address</th>
+                        <th class="py-1">Device</th>
+                    </tr>
+                </thead>
+                <tbody class="divide-y divide-neutral">
+                    {failures.iter().map(|attempt| rsx_move! {
+                        <tr>
+                            <td class="py-1 pr-2 whitespace-nowrap">{attempt.created_at.format("%b %-d %H:%M:%S").to_string()}</td>
+                            <td class="py-1 pr-2">
+                                {&attempt.username}
+                                {attempt.display_name.as_ref().map(|name| rsx_move! {
+                                    <span class="text-neutral">" (" {name} ")"</span>
+                                })}
+                            </td>
+                            <td class="py-1 pr-2">{outcome_name(attempt.outcome)}</td>
+                            <td class="py-1 pr-2">{attempt.ip_address.as_ref()}</td>
+                            <td class="py-1 truncate max-w-xs">{attempt.user_agent.as_ref()}</td>
+                        </tr>
+                    }).render_all()}
+                </tbody>
+            </table>
+            {failures.is_empty().then(|| rsx! {
+                <p class="text-neutral mt-2">No failed logins</p>
+            })}
+        </section>
+    }
+}
+
+fn outcome_name(outcome: LoginOutcome) -> &'static str {
+    match outcome {
+        LoginOutcome::Succeeded => "Succeeded",
+        LoginOutcome::BadPassword => "Wrong password",
+        LoginOutcome::UnknownUser => "Unknown username",
+        LoginOutcome::LockedOut => "Locked out",
+        LoginOutcome::BadSecondFactor => "Wrong two-factor code",
+    }
+}
@@ -0,0 +1,107 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::StatusCode,
+    response::IntoResponse,
+};
+use components::Layout;
+use db::UserRoles;
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission, Role};
+
+use crate::{AppState, auth::AuthUser};
+
+/// The roles that can be granted, and their form field names
+const GRANTABLE: [(Role, &str); 3] = [
+    (Role::DutyLead, "duty_lead"),
+    (Role::Scheduler, "scheduler"),
+    (Role::Admin, "admin"),
+];
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn roles_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    let users = state.db.users.list_roles().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Roles</h1>
+            <p class="text-sm text-neutral">Every member is a volunteer. Changes are saved as soon as a role is toggled.</p>
+            <ul class="divide-y divide-neutral">
+                {users.iter().map(UserRolesRow).render_all()}
+            </ul>
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct RolesModel {
+    duty_lead: Option<String>,
+    scheduler: Option<String>,
+    admin: Option<String>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn set_roles(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(uid): Path<i32>,
+    Form(body): Form<RolesModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    if uid == ctx.user_id && body.admin.is_none() {
+        return Err(Error::unprocessable_entity([(
+            "roles",
+            "You can't remove your own admin role",
+        )]));
+    }
+
+    let roles: Vec<Role> = [
+        (Role::DutyLead, body.duty_lead),
+        (Role::Scheduler, body.scheduler),
+        (Role::Admin, body.admin),
+    ]
+    .into_iter()
+    .filter_map(|(role, checked)| checked.map(|_| role))
+    .collect();
+
+    state.db.users.set_roles(uid, &roles).await?;
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+fn UserRolesRow(user: &UserRoles) -> impl Renderable {
+    rsx_move! {
+        <li class="py-2">
+            <form hx-post=format!("/admin/roles/{}", user.uid) hx-trigger="change" hx-swap="none" class="flex flex-wrap items-center justify-between gap-2" data-errors>
+                <div>
+                    <p class="font-medium">{&user.display_name}</p>
+                    <p class="text-sm text-neutral">{&user.username}</p>
+                </div>
+                <div class="flex gap-4 text-sm">
+                    {GRANTABLE.iter().map(|&(role, name)| {
+                        let checked = user.roles.contains(&role);
+
+                        rsx_move! {
+                            <label class="flex items-center gap-1">
+                                // rsx can't leave out an attribute, maud can
+                                {maud_move! { input type="checkbox" name=(name) checked[checked]; }}
+                                {role.name()}
+                            </label>
+                        }
+                    }).render_all()}
+                </div>
+                <p class="w-full text-red text-sm" data-error-for="roles"></p>
+            </form>
+        </li>
+    }
+}
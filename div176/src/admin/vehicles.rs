@@ -0,0 +1,214 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::{Layout, SelectOption};
+use db::{Group, NewVehicle, Vehicle};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use super::ArchiveButton;
+use crate::{AppState, auth::AuthUser};
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn vehicles_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    let vehicles = state.db.vehicles.list_all().await?;
+    let groups = state.db.groups.list_all().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Vehicles</h1>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">New Vehicle</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {VehicleForm(None, &groups)}
+                </div>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Vehicles</h2>
+                <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                    {vehicles.iter().map(|vehicle| VehicleRow(vehicle, &groups)).render_all()}
+                    {vehicles.is_empty().then(|| rsx! {
+                        <li class="py-2 text-sm text-neutral">No vehicles</li>
+                    })}
+                </ul>
+            </section>
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct VehicleModel {
+    name: String,
+    notes: String,
+    requires_group_to_drive: String,
+}
+
+impl VehicleModel {
+    fn validate(self) -> Result<NewVehicle, Error> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err(Error::unprocessable_entity([("name", "Name is required")]));
+        }
+
+        let requires_group_to_drive = match self.requires_group_to_drive.as_str() {
+            "" => None,
+            gid => Some(gid.parse().map_err(|_| {
+                Error::unprocessable_entity([("requires_group_to_drive", "Unknown group")])
+            })?),
+        };
+        let notes = self.notes.trim();
+
+        Ok(NewVehicle {
+            name: name.to_owned(),
+            notes: (!notes.is_empty()).then(|| notes.to_owned()),
+            requires_group_to_drive,
+        })
+    }
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn create_vehicle(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<VehicleModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.vehicles.create(&body.validate()?).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/vehicles")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn update_vehicle(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(vid): Path<i32>,
+    Form(body): Form<VehicleModel>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.vehicles.update(vid, &body.validate()?).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/vehicles")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn archive_vehicle(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(vid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.vehicles.set_archived(vid, true).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/vehicles")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn unarchive_vehicle(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(vid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageSettings)?;
+
+    state.db.vehicles.set_archived(vid, false).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/admin/vehicles")]),
+    ))
+}
+
+fn VehicleRow<'a>(vehicle: &'a Vehicle, groups: &'a [Group]) -> impl Renderable + 'a {
+    rsx_move! {
+        <li class="py-2 space-y-2">
+            <div class="flex items-center justify-between gap-2">
+                <div>
+                    <p class="font-medium">
+                        {&vehicle.name}
+                        {vehicle.archived.then(|| rsx! { <span class="text-sm text-neutral">" (archived)"</span> })}
+                    </p>
+                    <p class="text-sm text-neutral">
+                        {match &vehicle.group_name {
+                            Some(group) => format!("Drivers need {group}"),
+                            None => String::from("Anyone can drive"),
+                        }}
+                    </p>
+                </div>
+                {ArchiveButton(format!("/admin/vehicles/{}", vehicle.vid), vehicle.archived)}
+            </div>
+            <details>
+                <summary class="text-green font-medium cursor-pointer">Edit</summary>
+                <div class="pt-2">{VehicleForm(Some(vehicle), groups)}</div>
+            </details>
+        </li>
+    }
+}
+
+fn VehicleForm<'a>(vehicle: Option<&'a Vehicle>, groups: &'a [Group]) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = match vehicle {
+        Some(vehicle) => format!("/admin/vehicles/{}", vehicle.vid),
+        None => String::from("/admin/vehicles"),
+    };
+    let name = vehicle.map_or("", |vehicle| vehicle.name.as_str());
+    let notes = vehicle
+        .and_then(|vehicle| vehicle.notes.as_deref())
+        .unwrap_or_default();
+    let selected = vehicle.and_then(|vehicle| vehicle.requires_group_to_drive);
+    // Archived groups are only offered to vehicles which still require them
+    let groups = groups
+        .iter()
+        .filter(move |group| !group.archived || Some(group.gid) == selected);
+
+    rsx_move! {
+        <form hx-post=action class="grid desktop:grid-cols-2 gap-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">Name</span>
+                <input type="text" name="name" value=name class=input required>
+                <p class="text-red text-sm" data-error-for="name"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Required to drive</span>
+                <select name="requires_group_to_drive" class=input>
+                    {SelectOption(String::new(), "Nothing", selected.is_none())}
+                    {groups
+                        .map(|group| SelectOption(group.gid.to_string(), &group.name, Some(group.gid) == selected))
+                        .render_all()}
+                </select>
+                <p class="text-red text-sm" data-error-for="requires_group_to_drive"></p>
+            </label>
+            <label class="block desktop:col-span-2">
+                <span class="text-sm font-medium">Notes</span>
+                <textarea name="notes" rows="2" class=input>{notes}</textarea>
+            </label>
+            <div class="desktop:col-span-2">
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value=if vehicle.is_some() { "Save" } else { "Create" }>
+            </div>
+        </form>
+    }
+}
@@ -1,30 +1,48 @@
-use anyhow::anyhow;
-use argon2::{
-    Algorithm, Argon2, Params,
-    password_hash::{self, PasswordHash, PasswordVerifier},
-};
 use axum::{
     Form, async_trait,
-    extract::{FromRef, FromRequestParts, State},
-    http::{HeaderName, StatusCode, header, request::Parts},
+    extract::{FromRef, FromRequestParts, Path, State},
+    http::{HeaderName, StatusCode, request::Parts},
     response::{AppendHeaders, IntoResponse},
 };
-use axum_extra::extract::CookieJar;
+use axum_extra::extract::{CookieJar, cookie::Cookie};
+use chrono::Utc;
 use components::Layout;
+use db::{LoginOutcome, Session};
 use hypertext::*;
-use rand::{Rng, distr::Alphanumeric};
 use serde::{Deserialize, Serialize};
 use tracing::{Instrument, info_span};
-use types::Error;
-
-use crate::AppState;
+use types::{Error, Permission, Role};
 
-pub const AUTH_COOKIE: &str = "authorization";
+use crate::{
+    AppState,
+    client::Client,
+    passwords,
+    sessions::{AUTH_COOKIE, new_token, session_cookie, token_hash},
+    two_factor,
+};
 
 pub struct Ctx {
     pub user_id: i32,
     //pub user: User,
-    //pub permissions,
+    pub roles: Vec<Role>,
+    pub session_id: i32,
+}
+
+impl Ctx {
+    pub fn has(&self, permission: Permission) -> bool {
+        self.roles
+            .iter()
+            .any(|role| role.permissions().contains(&permission))
+    }
+
+    /// Guards a handler behind a permission
+    pub fn require(&self, permission: Permission) -> Result<(), Error> {
+        if self.has(permission) {
+            Ok(())
+        } else {
+            Err(Error::Forbidden)
+        }
+    }
 }
 
 pub struct AuthUser(pub Ctx);
@@ -38,28 +56,54 @@ where
     type Rejection = Error;
 
     async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let user_id = async move {
-            let auth_token = CookieJar::from_headers(&parts.headers)
-                .get(AUTH_COOKIE)
-                .ok_or(Error::Unauthorized)?
-                .value_trimmed()
-                .to_string();
+        let ctx = async move {
+            // Resolved from the cookie by `sessions::middleware`
+            let session = parts
+                .extensions
+                .get::<Session>()
+                .filter(|session| !session.second_factor_pending)
+                .ok_or(Error::Unauthorized)?;
 
             let db = AppState::from_ref(state).db;
+            let roles = db.users.get_roles(session.uid).await?;
 
-            let user_id = db
-                .users
-                .get_userid_from_session(&auth_token)
-                .await?
-                .ok_or(Error::Unauthorized)?;
-            Ok::<i32, Error>(user_id)
+            Ok::<Ctx, Error>(Ctx {
+                user_id: session.uid,
+                roles,
+                session_id: session.usid,
+            })
         }
         .instrument(info_span!("AuthUser Extractor"))
         .await?;
 
-        tracing::Span::current().record("user.id", user_id);
+        tracing::Span::current().record("user.id", ctx.user_id);
+
+        Ok(AuthUser(ctx))
+    }
+}
+
+/// A session which has passed the password step of signing in but still needs
+/// a second factor. Only the second factor pages accept it.
+pub struct PendingUser(pub Session);
 
-        Ok(AuthUser(Ctx { user_id }))
+#[async_trait]
+impl<S> FromRequestParts<S> for PendingUser
+where
+    S: Send + Sync,
+{
+    type Rejection = Error;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let session = parts
+            .extensions
+            .get::<Session>()
+            .filter(|session| session.second_factor_pending)
+            .cloned()
+            .ok_or(Error::Unauthorized)?;
+
+        tracing::Span::current().record("user.id", session.uid);
+
+        Ok(PendingUser(session))
     }
 }
 
@@ -69,53 +113,157 @@ pub struct LoginModel {
     password: String,
 }
 
-#[tracing::instrument(skip(state, body))]
+#[tracing::instrument(skip(state, jar, client, body))]
 pub async fn login(
     State(state): State<AppState>,
+    jar: CookieJar,
+    client: Client,
     Form(body): Form<LoginModel>,
 ) -> Result<impl IntoResponse, Error> {
-    let (uid, password_hash) = state
+    let ip_address = client.ip.map(|ip| ip.to_string());
+    let attempts = &state.db.login_attempts;
+    let record = |uid, outcome| {
+        attempts.record(
+            &body.username,
+            uid,
+            outcome,
+            ip_address.as_deref(),
+            client.user_agent.as_deref(),
+        )
+    };
+
+    if let Some(until) = attempts
+        .locked_until(&body.username, ip_address.as_deref())
+        .await?
+    {
+        record(None, LoginOutcome::LockedOut).await?;
+
+        // The lockout may have run out since it was looked up
+        let seconds = (until - Utc::now()).num_seconds().max(1);
+        let minutes = (seconds as u64).div_ceil(60);
+        return Err(Error::unprocessable_entity([(
+            "login",
+            format!(
+                "Too many failed attempts, try again in {minutes} minute{}",
+                if minutes == 1 { "" } else { "s" }
+            ),
+        )]));
+    }
+
+    let Some((uid, password_hash)) = state
         .db
         .users
         .get_userid_password_from_username(&body.username)
         .await?
-        .ok_or(Error::Unauthorized)?;
+    else {
+        passwords::verify_dummy(&state.config.password_params, body.password).await?;
+        record(None, LoginOutcome::UnknownUser).await?;
+        return Err(Error::Unauthorized);
+    };
 
-    hasher()
-        .verify_password(
-            body.password.as_bytes(),
-            &PasswordHash::new(&password_hash)
-                .map_err(|e| anyhow!("stored password hash to be valid: {}", e))?,
+    let rehashed = match passwords::verify(
+        &state.config.password_params,
+        body.password,
+        password_hash,
+    )
+    .await
+    {
+        Err(Error::Unauthorized) => {
+            record(Some(uid), LoginOutcome::BadPassword).await?;
+            return Err(Error::Unauthorized);
+        }
+        rehashed => rehashed?,
+    };
+
+    db::acting_as(uid, state.db.users.record_login(uid, rehashed.as_deref())).await?;
+
+    // Never carry a session over a login
+    if let Some(cookie) = jar.get(AUTH_COOKIE) {
+        let hash = token_hash(&state, cookie.value_trimmed());
+        state.db.sessions.delete(&hash).await?;
+    }
+
+    let roles = state.db.users.get_roles(uid).await?;
+    let second_factor = state.db.two_factor.get(uid).await?.enabled
+        || roles.iter().any(|role| role.requires_second_factor());
+
+    // Otherwise the login succeeds once the second factor passes
+    if !second_factor {
+        record(Some(uid), LoginOutcome::Succeeded).await?;
+    }
+
+    // Until the second factor is checked the session only gets as long as it
+    // takes to enter it
+    let (max_age, redirect) = if second_factor {
+        (two_factor::PENDING_MAX_AGE, "/login/two-factor")
+    } else {
+        (state.config.session_max_age, "/")
+    };
+
+    let token = new_token();
+    state
+        .db
+        .sessions
+        .create(
+            uid,
+            &token_hash(&state, &token),
+            max_age,
+            second_factor,
+            client.user_agent.as_deref(),
+            ip_address.as_deref(),
         )
-        .map_err(|e| match e {
-            password_hash::Error::Password => Error::Unauthorized,
-            _ => anyhow!("verifying password failed: {}", e).into(),
-        })?;
+        .await?;
 
-    let token: String = rand::rng()
-        .sample_iter(&Alphanumeric)
-        .take(32)
-        .map(char::from)
-        .collect();
+    Ok((
+        StatusCode::OK,
+        jar.add(session_cookie(&state, token)),
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), redirect)]),
+    ))
+}
 
-    state.db.users.create_session(uid, &token).await?;
+#[tracing::instrument(skip(state, jar))]
+pub async fn logout(
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, Error> {
+    if let Some(cookie) = jar.get(AUTH_COOKIE) {
+        let hash = token_hash(&state, cookie.value_trimmed());
+        state.db.sessions.delete(&hash).await?;
+    }
+
+    Ok(signed_out(jar))
+}
+
+#[tracing::instrument(skip(ctx, state, jar))]
+pub async fn logout_everywhere(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    jar: CookieJar,
+) -> Result<impl IntoResponse, Error> {
+    state.db.sessions.revoke_all(ctx.user_id).await?;
+
+    Ok(signed_out(jar))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn revoke_session(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(usid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    state.db.sessions.revoke(ctx.user_id, usid).await?;
 
     Ok((
         StatusCode::OK,
-        AppendHeaders([
-            // TODO: Add HttpOnly/security flags
-            (header::SET_COOKIE, format!("{}={}", AUTH_COOKIE, token)),
-            (HeaderName::from_static("hx-redirect"), String::from("/")),
-        ]),
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/account")]),
     ))
 }
 
-pub fn hasher() -> Argon2<'static> {
-    // HACK: These values are insecure
-    Argon2::new(
-        Algorithm::Argon2id,
-        argon2::Version::V0x13,
-        Params::new(1024, 1, 1, Some(Params::DEFAULT_OUTPUT_LEN)).expect("correct Argon2 params"),
+fn signed_out(jar: CookieJar) -> impl IntoResponse {
+    (
+        StatusCode::OK,
+        jar.remove(Cookie::build(AUTH_COOKIE).path("/")),
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/login")]),
     )
 }
 
@@ -126,7 +274,7 @@ pub async fn login_page() -> impl IntoResponse {
                 <h1 class="desktop:hidden text-xl font-medium text-center mb-4">Division 176 (Victoria)</h1>
                 <div id="login" class="flex-none desktop:w-64 mobile:w-full">
                     <h2 class="text-white text-center font-medium p-2 bg-green-light rounded-t-lg">Volunteer Login</h2>
-                    <form hx-post="/login" class="p-4 rounded-b-lg space-y-4 border border-green-light">
+                    <form hx-post="/login" class="p-4 rounded-b-lg space-y-4 border border-green-light" data-errors>
                         <label
                             for="Username"
                             class="relative block py-1 pl-3 border border-neutral rounded shadow-sm focus-within:border-green focus-within:ring-1 focus-within:ring-green"
@@ -159,9 +307,11 @@ pub async fn login_page() -> impl IntoResponse {
                                 Password
                             </span>
                         </label>
+                        <p class="text-red text-sm" data-error-for="login"></p>
                         <div>
                             <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Login">
                         </div>
+                        <a class="block text-sm text-green font-medium" href="/password-reset">Forgot your password?</a>
                     </form>
                 </div>
                 <div>
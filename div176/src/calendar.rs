@@ -0,0 +1,162 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    extract::{Path, State},
+    http::{HeaderName, StatusCode, header},
+    response::{AppendHeaders, IntoResponse},
+};
+use chrono::Utc;
+use db::CalendarToken;
+use hypertext::*;
+use types::Error;
+
+use crate::{
+    AppState,
+    auth::AuthUser,
+    ical,
+    sessions::{new_token, token_hash},
+};
+
+/// Issues a new calendar link, replacing the user's old one, and shows it
+/// once
+#[tracing::instrument(skip(ctx, state))]
+pub async fn create_link(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let token = new_token();
+    state
+        .db
+        .calendar_tokens
+        .issue(ctx.user_id, &token_hash(&state, &token))
+        .await?;
+
+    Ok(CalendarLinks(&state.config.base_url, &token).render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn revoke_link(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    state.db.calendar_tokens.revoke(ctx.user_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/account")]),
+    ))
+}
+
+/// The shifts the link's owner has signed up for. Calendar apps can't sign
+/// in, so the token in the path stands in for the session.
+#[tracing::instrument(skip_all)]
+pub async fn shifts_feed(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let uid = find_token(&state, &token).await?;
+    let shifts = state.db.events.list_calendar_shifts(Some(uid)).await?;
+
+    Ok(feed(ical::calendar(
+        "My Division 176 shifts",
+        &shifts,
+        &state.config.base_url,
+        state.config.time_zone,
+        Utc::now(),
+    )))
+}
+
+/// Every shift of the division's events
+#[tracing::instrument(skip_all)]
+pub async fn events_feed(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    find_token(&state, &token).await?;
+    let shifts = state.db.events.list_calendar_shifts(None).await?;
+
+    Ok(feed(ical::calendar(
+        "Division 176 events",
+        &shifts,
+        &state.config.base_url,
+        state.config.time_zone,
+        Utc::now(),
+    )))
+}
+
+async fn find_token(state: &AppState, token: &str) -> Result<i32, Error> {
+    state
+        .db
+        .calendar_tokens
+        .find(&token_hash(state, token))
+        .await?
+        .ok_or(Error::NotFound)
+}
+
+fn feed(calendar: String) -> impl IntoResponse {
+    (
+        [
+            (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+            (header::CACHE_CONTROL, "private, no-cache"),
+        ],
+        calendar,
+    )
+}
+
+pub(crate) fn CalendarSection(token: &Option<CalendarToken>) -> impl Renderable + '_ {
+    let body = move |output: &mut String| {
+        if let Some(token) = token {
+            rsx_move! {
+                <p>
+                    "Your calendar link was created " {token.created_at.format("%b %-d, %Y").to_string()}
+                    {match token.last_used_at {
+                        Some(used) => format!(" and last fetched {}.", used.format("%b %-d %H:%M")),
+                        None => String::from(" and hasn't been fetched yet."),
+                    }}
+                </p>
+                <div class="flex flex-wrap gap-2">
+                    <button hx-post="/account/calendar" hx-target="#calendar" hx-confirm="Replace your calendar link? Calendars using the current one will stop updating." class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light">New link</button>
+                    <button hx-post="/account/calendar/revoke" hx-confirm="Turn off your calendar link?" class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white">Turn off</button>
+                </div>
+            }
+            .render_to(output)
+        } else {
+            rsx! {
+                <p>"Subscribe to your shifts and the division's events from your phone or calendar app with a private link."</p>
+                <button hx-post="/account/calendar" hx-target="#calendar" class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light">Create link</button>
+            }
+            .render_to(output)
+        }
+    };
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Calendar</h2>
+            <div id="calendar" class="p-4 rounded-b-lg border border-green-light space-y-2">
+                {body}
+            </div>
+        </section>
+    }
+}
+
+fn CalendarLinks<'a>(base_url: &'a str, token: &'a str) -> impl Renderable + 'a {
+    let input =
+        "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true font-mono text-sm";
+    let shifts = format!("{base_url}/calendar/{token}/shifts.ics");
+    let events = format!("{base_url}/calendar/{token}/events.ics");
+
+    rsx_move! {
+        <div id="calendar" class="space-y-3">
+            <p>"Add these links to your calendar app as subscriptions. Anyone with them can see the shifts, so keep them private. They won't be shown again."</p>
+            <label class="block">
+                <span class="text-sm font-medium">Your shifts</span>
+                <input type="text" value=shifts class=input readonly>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">All division events</span>
+                <input type="text" value=events class=input readonly>
+            </label>
+            <a class="inline-block bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light" href="/account">Done</a>
+        </div>
+    }
+}
@@ -0,0 +1,159 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::IntoResponse,
+};
+use components::SelectOption;
+use db::{Comment, Shift};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{
+    AppState,
+    auth::{AuthUser, Ctx},
+    events::TimeRange,
+    forms::empty_as_none,
+};
+
+#[derive(Deserialize, Debug)]
+pub struct CommentModel {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    sid: Option<i32>,
+    comment_text: String,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn post_comment(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+    Form(body): Form<CommentModel>,
+) -> Result<impl IntoResponse, Error> {
+    state
+        .db
+        .comments
+        .post(ctx.user_id, eid, body.sid, &body.comment_text)
+        .await?;
+
+    render_comments(&state, eid, &ctx).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EditCommentModel {
+    comment_text: String,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn edit_comment(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(cid): Path<i32>,
+    Form(body): Form<EditCommentModel>,
+) -> Result<impl IntoResponse, Error> {
+    let eid = state
+        .db
+        .comments
+        .edit(ctx.user_id, cid, &body.comment_text)
+        .await?;
+
+    render_comments(&state, eid, &ctx).await
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn delete_comment(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(cid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let eid = state
+        .db
+        .comments
+        .archive(ctx.user_id, cid, ctx.has(Permission::ManageEvents))
+        .await?;
+
+    render_comments(&state, eid, &ctx).await
+}
+
+async fn render_comments(state: &AppState, eid: i32, ctx: &Ctx) -> Result<Rendered<String>, Error> {
+    let comments = state.db.comments.list_for_event(eid).await?;
+    let shifts = state.db.events.list_shifts(eid).await?;
+
+    Ok(CommentsSection(eid, &comments, &shifts, ctx).render())
+}
+
+pub fn CommentsSection<'a>(
+    eid: i32,
+    comments: &'a [Comment],
+    shifts: &'a [Shift],
+    ctx: &'a Ctx,
+) -> impl Renderable + 'a {
+    let moderator = ctx.has(Permission::ManageEvents);
+
+    rsx_move! {
+        <section id="comments" class="space-y-2">
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Comments</h2>
+            {(comments.is_empty()).then(|| rsx! {
+                <p class="text-neutral">No comments yet</p>
+            })}
+            <ul class="divide-y divide-neutral">
+                {comments.iter().map(|comment| {
+                    let own = comment.uid == Some(ctx.user_id);
+                    CommentRow(comment, own, own || moderator)
+                }).render_all()}
+            </ul>
+            <form hx-post=format!("/events/{eid}/comments") hx-target="#comments" hx-swap="outerHTML" class="space-y-2" data-errors>
+                <textarea name="comment_text" rows="3" required class="w-full border border-neutral rounded p-2" placeholder="Parking, radio channels, gear…"></textarea>
+                <p class="text-red text-sm" data-error-for="comment_text"></p>
+                <div class="flex gap-2 items-center">
+                    <select name="sid" class="border border-neutral rounded p-1 text-sm">
+                        <option value="">Whole event</option>
+                        {shifts.iter().map(|shift| SelectOption(
+                            shift.sid.to_string(),
+                            rsx_move! { {shift.start_time.format("%a, %b %-d ").to_string()} {TimeRange(shift.start_time, shift.end_time)} },
+                            false,
+                        )).render_all()}
+                    </select>
+                    <button class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light">Post</button>
+                </div>
+                <p class="text-red text-sm" data-error-for="sid"></p>
+            </form>
+        </section>
+    }
+}
+
+fn CommentRow(comment: &Comment, editable: bool, deletable: bool) -> impl Renderable {
+    rsx_move! {
+        <li class="py-2 space-y-1">
+            <p class="text-sm">
+                <span class="font-medium">{comment.author.as_deref().unwrap_or("Unknown member")}</span>
+                <span class="text-neutral">
+                    " · " {comment.created_at.format("%b %-d %H:%M").to_string()}
+                    {comment.edited.then(|| rsx! { " (edited)" })}
+                </span>
+                {comment.shift_start.map(|start| rsx_move! {
+                    " "
+                    <span class="text-xs px-2 py-0.5 rounded-full border border-neutral">"Shift " {start.format("%a %H:%M").to_string()}</span>
+                })}
+            </p>
+            <p class="whitespace-pre-line">{&comment.comment_text}</p>
+            <div class="flex gap-3 text-sm">
+                {editable.then(|| rsx_move! {
+                    <details class="w-full">
+                        <summary class="cursor-pointer text-neutral hover:text-green">Edit</summary>
+                        <form hx-post=format!("/comments/{}", comment.cid) hx-target="#comments" hx-swap="outerHTML" class="space-y-2 mt-1" data-errors>
+                            <textarea name="comment_text" rows="3" required class="w-full border border-neutral rounded p-2">{&comment.comment_text}</textarea>
+                            <p class="text-red text-sm" data-error-for="comment_text"></p>
+                            <button class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light">Save</button>
+                        </form>
+                    </details>
+                })}
+                {deletable.then(|| rsx_move! {
+                    <button hx-post=format!("/comments/{}/delete", comment.cid) hx-target="#comments" hx-swap="outerHTML" hx-confirm="Delete this comment?" class="text-neutral hover:text-red self-start">Delete</button>
+                })}
+            </div>
+        </li>
+    }
+}
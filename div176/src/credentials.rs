@@ -0,0 +1,401 @@
+#![allow(non_snake_case)]
+
+use std::time::Duration;
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::Layout;
+use db::TokenPurpose;
+use hypertext::*;
+use mail::Email;
+use serde::Deserialize;
+use tracing::error;
+use types::Error;
+
+use crate::{
+    AppState, passwords,
+    sessions::{new_token, token_hash},
+};
+
+pub const INVITATION_VALID_FOR: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const PASSWORD_RESET_VALID_FOR: Duration = Duration::from_secs(60 * 60);
+const EMAIL_CHANGE_VALID_FOR: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Issues an invitation and emails its link to the new member
+pub async fn send_invitation(state: &AppState, uid: i32, created_by: i32) -> Result<(), Error> {
+    let user = state.db.users.get_user(uid).await?.ok_or(Error::NotFound)?;
+
+    let token = new_token();
+    state
+        .db
+        .user_tokens
+        .issue(
+            uid,
+            TokenPurpose::Invitation,
+            &token_hash(state, &token),
+            INVITATION_VALID_FOR,
+            Some(created_by),
+        )
+        .await?;
+
+    let email = Email {
+        to: user.email,
+        subject: String::from("Your Division 176 volunteer account"),
+        body: format!(
+            "Hi {},\n\n\
+            You've been invited to the Division 176 volunteer portal. Your username is {}.\n\n\
+            Choose a password within 7 days to finish setting up your account:\n\n\
+            {}/invite/{token}\n",
+            user.display_name, user.username, state.config.base_url
+        ),
+    };
+
+    state.mailer.send(email).await.map_err(|e| {
+        error!("sending invitation failed: {:?}", e);
+        Error::unprocessable_entity([(
+            "email",
+            "The invitation was created but couldn't be emailed, try resending it",
+        )])
+    })
+}
+
+/// Emails a link to the new address, which only becomes the member's email once
+/// it's followed
+pub async fn send_email_change(state: &AppState, uid: i32, email: &str) -> Result<(), Error> {
+    let user = state.db.users.get_user(uid).await?.ok_or(Error::NotFound)?;
+
+    let token = new_token();
+    state
+        .db
+        .user_tokens
+        .issue_email_change(
+            uid,
+            email,
+            &token_hash(state, &token),
+            EMAIL_CHANGE_VALID_FOR,
+        )
+        .await?;
+
+    let email = Email {
+        to: email.to_owned(),
+        subject: String::from("Confirm your new Division 176 email"),
+        body: format!(
+            "Hi {},\n\n\
+            Someone asked to change the email for {} to this address. If that was you, confirm it \
+            within a day:\n\n\
+            {}/email-change/{token}\n\n\
+            If it wasn't, you can ignore this email.\n",
+            user.display_name, user.username, state.config.base_url
+        ),
+    };
+
+    state.mailer.send(email).await.map_err(|e| {
+        error!("sending email change confirmation failed: {:?}", e);
+        Error::unprocessable_entity([("email", "The confirmation couldn't be emailed, try again")])
+    })
+}
+
+#[tracing::instrument(skip(state, token))]
+pub async fn email_change_page(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let change = state
+        .db
+        .user_tokens
+        .find_email_change(&token_hash(&state, &token))
+        .await?;
+
+    Ok(Layout(rsx_move! {
+        <div class="max-w-sm mx-auto space-y-4">
+            <h1 class="text-2xl font-medium">Confirm Your Email</h1>
+            {move |output: &mut String| match change {
+                Some(change) => rsx_move! {
+                    <p>"Hi " {change.display_name} ", confirm " <span class="font-medium">{change.email}</span> " as your new email."</p>
+                    <form hx-post=format!("/email-change/{token}") class="space-y-3" data-errors>
+                        <p class="text-red text-sm" data-error-for="token"></p>
+                        <p class="text-red text-sm" data-error-for="email"></p>
+                        <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Confirm">
+                    </form>
+                }
+                .render_to(output),
+                None => rsx! {
+                    <p>"This link has expired or was already used. Change your email from your profile to get a new one."</p>
+                }
+                .render_to(output),
+            }}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(state, token))]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    let expired =
+        || Error::unprocessable_entity([("token", "This link has expired or was already used")]);
+    let hash = token_hash(&state, &token);
+
+    // There's no session, so the change is recorded as made by the member
+    // whose link it is
+    let change = state
+        .db
+        .user_tokens
+        .find_email_change(&hash)
+        .await?
+        .ok_or_else(expired)?;
+    let uid = db::acting_as(change.uid, state.db.user_tokens.confirm_email_change(&hash))
+        .await
+        .map_err(|e| match e {
+            Error::NotFound => expired(),
+            e => e,
+        })?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(
+            HeaderName::from_static("hx-redirect"),
+            format!("/volunteers/{uid}"),
+        )]),
+    ))
+}
+
+#[tracing::instrument]
+pub async fn password_reset_page() -> impl IntoResponse {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    Layout(rsx_move! {
+        <div class="max-w-sm mx-auto space-y-4">
+            <h1 class="text-2xl font-medium">Reset Password</h1>
+            <form hx-post="/password-reset" hx-swap="outerHTML" class="space-y-3" data-errors>
+                <label class="block">
+                    <span class="text-sm font-medium">Username or email</span>
+                    <input type="text" name="login" class=input required>
+                </label>
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Email me a link">
+            </form>
+        </div>
+    })
+    .render()
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PasswordResetModel {
+    login: String,
+}
+
+/// Responds the same whether or not the account exists, and emails the link in
+/// the background so the timing doesn't give it away either
+#[tracing::instrument(skip(state))]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    Form(body): Form<PasswordResetModel>,
+) -> impl IntoResponse {
+    tokio::spawn(async move {
+        if let Err(e) = email_password_reset(&state, body.login.trim()).await {
+            error!("emailing password reset failed: {:?}", e);
+        }
+    });
+
+    rsx! {
+        <p>"If an account matches, we've emailed it a link to choose a new password. The link expires in an hour."</p>
+    }
+    .render()
+}
+
+async fn email_password_reset(state: &AppState, login: &str) -> Result<(), Error> {
+    let Some(user) = state.db.users.find_by_login(login).await? else {
+        return Ok(());
+    };
+
+    let token = new_token();
+    state
+        .db
+        .user_tokens
+        .issue(
+            user.uid,
+            TokenPurpose::PasswordReset,
+            &token_hash(state, &token),
+            PASSWORD_RESET_VALID_FOR,
+            None,
+        )
+        .await?;
+
+    let email = Email {
+        to: user.email,
+        subject: String::from("Reset your Division 176 password"),
+        body: format!(
+            "Hi {},\n\n\
+            Someone asked to reset the password for {}. If that was you, choose a new password \
+            within an hour:\n\n\
+            {}/password-reset/{token}\n\n\
+            If it wasn't, you can ignore this email.\n",
+            user.display_name, user.username, state.config.base_url
+        ),
+    };
+
+    state.mailer.send(email).await?;
+
+    Ok(())
+}
+
+#[tracing::instrument(skip(state, token))]
+pub async fn invitation_page(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    set_password_page(&state, TokenPurpose::Invitation, &token).await
+}
+
+#[tracing::instrument(skip(state, token))]
+pub async fn password_reset_token_page(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+) -> Result<impl IntoResponse, Error> {
+    set_password_page(&state, TokenPurpose::PasswordReset, &token).await
+}
+
+#[derive(Deserialize)]
+pub struct SetPasswordModel {
+    password: String,
+    confirm: String,
+}
+
+#[tracing::instrument(skip(state, token, body))]
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Form(body): Form<SetPasswordModel>,
+) -> Result<impl IntoResponse, Error> {
+    set_password(&state, TokenPurpose::Invitation, &token, body).await
+}
+
+#[tracing::instrument(skip(state, token, body))]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    Path(token): Path<String>,
+    Form(body): Form<SetPasswordModel>,
+) -> Result<impl IntoResponse, Error> {
+    set_password(&state, TokenPurpose::PasswordReset, &token, body).await
+}
+
+async fn set_password_page(
+    state: &AppState,
+    purpose: TokenPurpose,
+    token: &str,
+) -> Result<Rendered<String>, Error> {
+    let user = state
+        .db
+        .user_tokens
+        .find(purpose, &token_hash(state, token))
+        .await?;
+
+    let (title, action) = match purpose {
+        TokenPurpose::Invitation => ("Welcome", format!("/invite/{token}")),
+        TokenPurpose::PasswordReset => {
+            ("Choose a New Password", format!("/password-reset/{token}"))
+        }
+        TokenPurpose::EmailChange => return Err(Error::NotFound),
+    };
+
+    Ok(Layout(rsx_move! {
+        <div class="max-w-sm mx-auto space-y-4">
+            <h1 class="text-2xl font-medium">{title}</h1>
+            {move |output: &mut String| match user {
+                Some(user) => {
+                    SetPasswordForm(user.display_name, user.username, action).render_to(output)
+                }
+                None => rsx! {
+                    <p>"This link has expired or was already used."</p>
+                    <a class="text-green font-medium" href="/password-reset">Request a new link</a>
+                }
+                .render_to(output),
+            }}
+        </div>
+    })
+    .render())
+}
+
+fn SetPasswordForm(display_name: String, username: String, action: String) -> impl Renderable {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <p>"Hi " {display_name} ", your username is " <span class="font-medium">{username}</span> "."</p>
+        <form hx-post=action class="space-y-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">Password</span>
+                <input type="password" name="password" autocomplete="new-password" minlength=passwords::MIN_LEN class=input required>
+                <p class="text-red text-sm" data-error-for="password"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Confirm password</span>
+                <input type="password" name="confirm" autocomplete="new-password" class=input required>
+                <p class="text-red text-sm" data-error-for="confirm"></p>
+            </label>
+            <p class="text-red text-sm" data-error-for="token"></p>
+            <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Set password">
+        </form>
+    }
+}
+
+async fn set_password(
+    state: &AppState,
+    purpose: TokenPurpose,
+    token: &str,
+    body: SetPasswordModel,
+) -> Result<impl IntoResponse + use<>, Error> {
+    let len = body.password.chars().count();
+    if len < passwords::MIN_LEN {
+        return Err(Error::unprocessable_entity([(
+            "password",
+            format!("Use at least {} characters", passwords::MIN_LEN),
+        )]));
+    }
+    if len > passwords::MAX_LEN {
+        return Err(Error::unprocessable_entity([(
+            "password",
+            format!("Use at most {} characters", passwords::MAX_LEN),
+        )]));
+    }
+    if body.password != body.confirm {
+        return Err(Error::unprocessable_entity([(
+            "confirm",
+            "Passwords don't match",
+        )]));
+    }
+
+    let expired =
+        || Error::unprocessable_entity([("token", "This link has expired or was already used")]);
+    let hash = token_hash(state, token);
+    let user = state
+        .db
+        .user_tokens
+        .find(purpose, &hash)
+        .await?
+        .ok_or_else(expired)?;
+
+    let password_hash = passwords::hash(&state.config.password_params, body.password).await?;
+
+    // There's no session, so the change is recorded as made by the member
+    // whose link it is
+    db::acting_as(
+        user.uid,
+        state.db.user_tokens.redeem(purpose, &hash, &password_hash),
+    )
+    .await
+    .map_err(|e| match e {
+        Error::NotFound => expired(),
+        e => e,
+    })?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/login")]),
+    ))
+}
@@ -0,0 +1,407 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, Query, State},
+    response::IntoResponse,
+};
+use chrono::NaiveDateTime;
+use components::{Layout, SelectOption};
+use db::{Event, EventSummary, EventType, Shift, ShiftVehicle, Signup, Vehicle};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{
+    AppState,
+    auth::{AuthUser, Ctx},
+    comments::CommentsSection,
+};
+
+pub(crate) const DEFAULT_COLOUR: &str = "A3A3A3";
+
+#[derive(Deserialize, Debug)]
+pub struct EventsFilter {
+    etid: Option<i32>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn events(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Query(filter): Query<EventsFilter>,
+) -> Result<impl IntoResponse, Error> {
+    let event_types = state.db.events.list_event_types().await?;
+    let events = state.db.events.list_upcoming(filter.etid).await?;
+
+    let days = events.chunk_by(|a, b| a.start_time.date() == b.start_time.date());
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <div class="flex items-center justify-between gap-2">
+                <h1 class="text-2xl font-medium">Upcoming Events</h1>
+                {ctx.has(Permission::ManageEvents).then(|| rsx! {
+                    <div class="flex items-center gap-2">
+                        <a class="text-green font-medium" href="/series">Recurring</a>
+                        <a class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light" href="/events/new">New event</a>
+                    </div>
+                })}
+            </div>
+            {EventTypeFilter(&event_types, filter.etid)}
+            {(events.is_empty()).then(|| rsx! {
+                <p class="text-neutral">No upcoming events</p>
+            })}
+            {days.map(|day| rsx_move! {
+                <section class="space-y-2">
+                    <h2 class="font-medium border-b border-neutral">{day[0].start_time.format("%A, %B %-d").to_string()}</h2>
+                    {day.iter().map(EventCard).render_all()}
+                </section>
+            }).render_all()}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn event(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let event = state
+        .db
+        .events
+        .get_event(eid)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let shifts = EventShifts::load(&state, eid, &ctx).await?;
+    let comments = state.db.comments.list_for_event(eid).await?;
+
+    let colour = event.colour.as_deref().unwrap_or(DEFAULT_COLOUR);
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <div class="flex items-start justify-between gap-2">
+                <div class="border-l-8 pl-3" style=format!("border-color: #{colour}")>
+                    <h1 class="text-2xl font-medium">{&event.event_name}</h1>
+                    <p class="text-sm text-neutral">{&event.type_name}</p>
+                </div>
+                {ctx.has(Permission::ManageEvents).then(|| rsx_move! {
+                    <a class="text-green font-medium" href=format!("/events/{eid}/edit")>Edit</a>
+                })}
+            </div>
+            <dl class="grid grid-cols-[max-content_1fr] gap-x-4 gap-y-1">
+                {event.location.as_ref().map(|location| rsx_move! {
+                    <dt class="font-medium">Location</dt>
+                    <dd>{location}</dd>
+                })}
+                {event.organizer_details.as_ref().map(|organizer| rsx_move! {
+                    <dt class="font-medium">Organizer</dt>
+                    <dd class="whitespace-pre-line">{organizer}</dd>
+                })}
+                {event.notes.as_ref().map(|notes| rsx_move! {
+                    <dt class="font-medium">Notes</dt>
+                    <dd class="whitespace-pre-line">{notes}</dd>
+                })}
+            </dl>
+            {ShiftsSection(&event, &shifts, ctx.user_id)}
+            {CommentsSection(eid, &comments, &shifts.shifts, &ctx)}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn signup(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(sid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    state.db.signups.signup(ctx.user_id, sid).await?;
+
+    render_shifts(&state, sid, &ctx).await
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn withdraw(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(sid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    state.db.signups.withdraw(ctx.user_id, sid).await?;
+
+    render_shifts(&state, sid, &ctx).await
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AttachVehicle {
+    vid: i32,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn attach_vehicle(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(sid): Path<i32>,
+    Form(form): Form<AttachVehicle>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    state.db.vehicles.attach(sid, form.vid).await?;
+
+    render_shifts(&state, sid, &ctx).await
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn detach_vehicle(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path((sid, vid)): Path<(i32, i32)>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    state.db.vehicles.detach(sid, vid).await?;
+
+    render_shifts(&state, sid, &ctx).await
+}
+
+/// Everything the shifts section of an event page shows
+struct EventShifts {
+    shifts: Vec<Shift>,
+    signups: Vec<Signup>,
+    vehicles: Vec<ShiftVehicle>,
+    /// Vehicles which can be attached, if the user manages events
+    fleet: Option<Vec<Vehicle>>,
+}
+
+impl EventShifts {
+    async fn load(state: &AppState, eid: i32, ctx: &Ctx) -> Result<Self, Error> {
+        let fleet = if ctx.has(Permission::ManageEvents) {
+            Some(state.db.vehicles.list().await?)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            shifts: state.db.events.list_shifts(eid).await?,
+            signups: state.db.signups.list_for_event(eid).await?,
+            vehicles: state.db.vehicles.list_for_event(eid).await?,
+            fleet,
+        })
+    }
+}
+
+/// Re-renders the shifts section of the event that `sid` belongs to
+async fn render_shifts(state: &AppState, sid: i32, ctx: &Ctx) -> Result<Rendered<String>, Error> {
+    let eid = state
+        .db
+        .events
+        .get_shift(sid)
+        .await?
+        .ok_or(Error::NotFound)?
+        .eid;
+    let event = state
+        .db
+        .events
+        .get_event(eid)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let shifts = EventShifts::load(state, eid, ctx).await?;
+
+    Ok(ShiftsSection(&event, &shifts, ctx.user_id).render())
+}
+
+fn EventTypeFilter(event_types: &[EventType], selected: Option<i32>) -> impl Renderable {
+    let chip = |active: bool| {
+        if active {
+            "px-2 py-0.5 rounded-full border border-green bg-green text-white"
+        } else {
+            "px-2 py-0.5 rounded-full border border-neutral hover:border-green"
+        }
+    };
+
+    rsx_move! {
+        <nav class="flex flex-wrap gap-2 text-sm">
+            <a href="/events" class=chip(selected.is_none())>All</a>
+            {event_types.iter().map(|et| rsx_move! {
+                <a href=format!("/events?etid={}", et.etid) class=chip(selected == Some(et.etid))>
+                    <span class="inline-block w-2 h-2 mr-1 rounded-full" style=format!("background-color: #{}", et.colour.as_deref().unwrap_or(DEFAULT_COLOUR))></span>
+                    {&et.name}
+                </a>
+            }).render_all()}
+        </nav>
+    }
+}
+
+fn EventCard(event: &EventSummary) -> impl Renderable {
+    let colour = event.colour.as_deref().unwrap_or(DEFAULT_COLOUR);
+
+    rsx_move! {
+        <a href=format!("/events/{}", event.eid) class="block border-l-8 pl-3 py-1 shadow-sm rounded hover:bg-white-true" style=format!("border-color: #{colour}")>
+            <p class="font-medium">{&event.event_name}</p>
+            <p class="text-sm">
+                {TimeRange(event.start_time, event.end_time)}
+                {event.location.as_ref().map(|location| rsx_move! { " · " {location} })}
+            </p>
+            <p class="text-xs text-neutral">
+                {&event.type_name} " · " {event.shift_count} {if event.shift_count == 1 { " shift" } else { " shifts" }}
+            </p>
+        </a>
+    }
+}
+
+fn ShiftsSection<'a>(event: &'a Event, data: &'a EventShifts, uid: i32) -> impl Renderable + 'a {
+    let EventShifts {
+        shifts,
+        signups,
+        vehicles,
+        fleet,
+    } = data;
+    let signed_up = move |sid: i32| signups.iter().any(|s| s.sid == sid && s.uid == uid);
+
+    // Event level signups apply to every remaining shift, so a single button
+    // acting on the next one stands in for the whole event
+    let event_button = event
+        .event_level_signup
+        .then(|| shifts.iter().find(|shift| !shift.ended))
+        .flatten()
+        .map(|shift| SignupButton(shift.sid, signed_up(shift.sid)));
+
+    rsx_move! {
+        <section id="shifts" class="space-y-2" data-errors>
+            <div class="flex items-center justify-between text-white font-medium p-2 bg-green-light rounded-t-lg">
+                <h2>Shifts</h2>
+                {event_button}
+            </div>
+            <p class="text-red text-sm" data-error-for="sid"></p>
+            <p class="text-red text-sm" data-error-for="vid"></p>
+            {(shifts.is_empty()).then(|| rsx! {
+                <p class="text-neutral">No shifts have been scheduled</p>
+            })}
+            <ul class="divide-y divide-neutral">
+                {shifts.iter().map(|shift| {
+                    let button = (!event.event_level_signup && !shift.ended)
+                        .then(|| SignupButton(shift.sid, signed_up(shift.sid)));
+                    let volunteers = signups.iter().filter(move |s| s.sid == shift.sid);
+                    let shift_vehicles = vehicles.iter().filter(move |v| v.sid == shift.sid);
+                    let attach = fleet
+                        .as_deref()
+                        .filter(|_| !shift.ended)
+                        .and_then(|fleet| AttachVehicleForm(shift.sid, fleet, shift_vehicles.clone()));
+
+                    ShiftRow(
+                        shift,
+                        volunteers,
+                        VehicleBadges(shift_vehicles, fleet.is_some()),
+                        attach,
+                        button,
+                    )
+                }).render_all()}
+            </ul>
+        </section>
+    }
+}
+
+fn ShiftRow<'a>(
+    shift: &'a Shift,
+    volunteers: impl Iterator<Item = &'a Signup> + Clone + 'a,
+    vehicles: impl Renderable + 'a,
+    attach: Option<impl Renderable + 'a>,
+    button: Option<impl Renderable + 'a>,
+) -> impl Renderable + 'a {
+    let count = volunteers.clone().count();
+
+    rsx_move! {
+        <li class="py-2 flex justify-between items-start gap-2">
+            <div>
+                <p class="font-medium">{shift.start_time.format("%a, %b %-d").to_string()}</p>
+                <p class="text-sm">{TimeRange(shift.start_time, shift.end_time)}</p>
+                {shift.duty_lead_name.as_ref().map(|name| rsx_move! {
+                    <p class="text-sm text-neutral">"Duty lead: " {name}</p>
+                })}
+                <p class="text-sm text-neutral">
+                    {count} {if count == 1 { " volunteer" } else { " volunteers" }}
+                    {(count > 0).then(|| rsx_move! {
+                        ": " {volunteers.map(|s| s.display_name.as_str()).collect::<Vec<_>>().join(", ")}
+                    })}
+                </p>
+                {vehicles}
+                {attach}
+            </div>
+            {button}
+        </li>
+    }
+}
+
+fn VehicleBadges<'a>(
+    vehicles: impl Iterator<Item = &'a ShiftVehicle> + 'a,
+    manage: bool,
+) -> impl Renderable + 'a {
+    rsx_move! {
+        <div class="flex flex-wrap gap-1 mt-1">
+            {vehicles.map(|vehicle| rsx_move! {
+                <span class="inline-flex items-center gap-1 text-xs px-2 py-0.5 rounded-full border border-neutral">
+                    {&vehicle.name}
+                    {(!vehicle.has_driver).then(|| rsx_move! {
+                        <span class="text-red font-medium" title=format!("No one signed up holds a valid {}", vehicle.group_name.as_deref().unwrap_or_default())>"⚠ No qualified driver"</span>
+                    })}
+                    {manage.then(|| rsx_move! {
+                        <button hx-post=format!("/shifts/{}/vehicles/{}/detach", vehicle.sid, vehicle.vid) hx-target="#shifts" hx-swap="outerHTML" class="text-neutral hover:text-red" title="Remove vehicle">"×"</button>
+                    })}
+                </span>
+            }).render_all()}
+        </div>
+    }
+}
+
+fn AttachVehicleForm<'a>(
+    sid: i32,
+    fleet: &'a [Vehicle],
+    attached: impl Iterator<Item = &'a ShiftVehicle> + Clone + 'a,
+) -> Option<impl Renderable + 'a> {
+    let available: Vec<_> = fleet
+        .iter()
+        .filter(|vehicle| !attached.clone().any(|a| a.vid == vehicle.vid))
+        .collect();
+
+    (!available.is_empty()).then(|| rsx_move! {
+        <form hx-post=format!("/shifts/{sid}/vehicles") hx-target="#shifts" hx-swap="outerHTML" class="flex gap-1 mt-1 text-xs">
+            <select name="vid" class="border border-neutral rounded px-1">
+                {available.iter().map(|vehicle| SelectOption(vehicle.vid.to_string(), &vehicle.name, false)).render_all()}
+            </select>
+            <button class="border border-green text-green px-2 rounded hover:bg-green hover:text-white">Attach vehicle</button>
+        </form>
+    })
+}
+
+fn SignupButton(sid: i32, signed_up: bool) -> impl Renderable {
+    let (action, label, class) = if signed_up {
+        (
+            "withdraw",
+            "Withdraw",
+            "bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white",
+        )
+    } else {
+        (
+            "signup",
+            "Sign up",
+            "bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light",
+        )
+    };
+
+    rsx_move! {
+        <button hx-post=format!("/shifts/{sid}/{action}") hx-target="#shifts" hx-swap="outerHTML" class=class>{label}</button>
+    }
+}
+
+pub fn TimeRange(start: NaiveDateTime, end: NaiveDateTime) -> impl Renderable {
+    let end_format = if start.date() == end.date() {
+        "%H:%M"
+    } else {
+        "%a %H:%M"
+    };
+
+    rsx_move! {
+        {start.format("%H:%M").to_string()} " – " {end.format(end_format).to_string()}
+    }
+}
@@ -0,0 +1,120 @@
+#![allow(non_snake_case)]
+
+use std::{borrow::Cow, collections::HashMap, convert::Infallible, fmt::Display, str::FromStr};
+
+use axum::{
+    async_trait,
+    extract::FromRequestParts,
+    http::{HeaderName, StatusCode, request::Parts},
+    response::{AppendHeaders, IntoResponse, Redirect, Response},
+};
+use chrono::NaiveDateTime;
+use components::Layout;
+use hypertext::*;
+use serde::{Deserialize, Deserializer, de};
+use types::Error;
+
+/// Whether a form was submitted by htmx, or by the browser itself with
+/// JavaScript turned off
+#[derive(Debug, Clone, Copy)]
+pub struct HxRequest(pub bool);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for HxRequest
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self(parts.headers.contains_key("hx-request")))
+    }
+}
+
+impl HxRequest {
+    /// Sends the browser on to the returned path once the form is handled.
+    /// Validation errors go back as JSON for htmx to show beside their fields,
+    /// or as a page listing them when the form was submitted without htmx.
+    pub fn finish(self, result: Result<String, Error>) -> Response {
+        match result {
+            Ok(to) if self.0 => (
+                StatusCode::OK,
+                AppendHeaders([(HeaderName::from_static("hx-redirect"), to)]),
+            )
+                .into_response(),
+            Ok(to) => Redirect::to(&to).into_response(),
+            Err(Error::UnprocessableEntity { errors }) if !self.0 => (
+                StatusCode::UNPROCESSABLE_ENTITY,
+                Layout(FormErrorsPage(errors)).render(),
+            )
+                .into_response(),
+            Err(e) => e.into_response(),
+        }
+    }
+}
+
+fn FormErrorsPage(errors: HashMap<Cow<'static, str>, Vec<Cow<'static, str>>>) -> impl Renderable {
+    rsx_move! {
+        <div class="space-y-2">
+            <h1 class="text-2xl font-medium">"That couldn't be saved"</h1>
+            <ul class="list-disc pl-6 text-red">
+                {errors.values().flatten().map(|message| rsx_move! { <li>{message.as_ref()}</li> }).render_all()}
+            </ul>
+            <p>"Go back to correct the form and try again."</p>
+        </div>
+    }
+}
+
+/// Deserializes an empty form field as `None` instead of failing to parse it
+pub fn empty_as_none<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let value = Option::<String>::deserialize(deserializer)?;
+
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(de::Error::custom),
+    }
+}
+
+/// The 10 digits of a North American phone number, however it was formatted
+pub fn phone_number(value: &str) -> Option<String> {
+    let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+
+    match digits.len() {
+        10 => Some(digits),
+        11 if digits.starts_with('1') => Some(digits[1..].to_owned()),
+        _ => None,
+    }
+}
+
+/// Parses the value of a `datetime-local` input
+pub fn datetime_local(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalizes_phone_numbers() {
+        assert_eq!(
+            phone_number("(250) 555-0123").as_deref(),
+            Some("2505550123")
+        );
+        assert_eq!(
+            phone_number("+1 250.555.0123").as_deref(),
+            Some("2505550123")
+        );
+        assert_eq!(phone_number("555-0123"), None);
+        assert_eq!(phone_number("2 250 555 0123"), None);
+    }
+}
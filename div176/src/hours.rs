@@ -0,0 +1,393 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use chrono::{Datelike, NaiveDate};
+use components::Layout;
+use db::{EventType, HoursEntry, HoursStatus, LinkableShift, NewHours, PendingHours};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{
+    AppState,
+    auth::{AuthUser, Ctx},
+    events::DEFAULT_COLOUR,
+    forms::empty_as_none,
+};
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn hours_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let (drafts, entries): (Vec<_>, Vec<_>) = state
+        .db
+        .hours
+        .list_for_user(ctx.user_id)
+        .await?
+        .into_iter()
+        .partition(|entry| entry.draft);
+    let event_types = state.db.events.list_event_types().await?;
+    let shifts = state.db.hours.list_linkable_shifts(ctx.user_id).await?;
+    let to_review = state
+        .db
+        .hours
+        .count_pending_review(ctx.user_id, ctx.has(Permission::ApproveHours))
+        .await?;
+
+    let today = state.config.today();
+    let year_total: f64 = entries
+        .iter()
+        .filter(|entry| entry.status == HoursStatus::Approved)
+        .filter(|entry| entry.date.year() == today.year())
+        .map(|entry| entry.hours)
+        .sum();
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Hours</h1>
+            <p>{format!("{year_total:.2}")} " hours approved in " {today.year()}</p>
+            {(to_review > 0).then(|| rsx! {
+                <p>
+                    <a class="text-green font-medium" href="/hours/approvals">
+                        {to_review} {if to_review == 1 {" entry is"} else {" entries are"}} " waiting for your approval"
+                    </a>
+                </p>
+            })}
+            {(!drafts.is_empty()).then(|| DraftsSection(&drafts))}
+            {LogHoursForm(&event_types, &shifts, today)}
+            {HoursTable(&entries)}
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct LogHoursModel {
+    date: String,
+    etid: i32,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    sid: Option<i32>,
+    hours: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    description: Option<String>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn log_hours(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<LogHoursModel>,
+) -> Result<impl IntoResponse, Error> {
+    let date = NaiveDate::parse_from_str(&body.date, "%Y-%m-%d")
+        .map_err(|_| Error::unprocessable_entity([("date", "Enter a valid date")]))?;
+    let hours = body
+        .hours
+        .trim()
+        .parse()
+        .map_err(|_| Error::unprocessable_entity([("hours", "Enter a number of hours")]))?;
+
+    state
+        .db
+        .hours
+        .log(
+            ctx.user_id,
+            NewHours {
+                etid: body.etid,
+                sid: body.sid,
+                date,
+                hours,
+                description: body.description,
+            },
+        )
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/hours")]),
+    ))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ConfirmDraftModel {
+    hours: String,
+    #[serde(default, deserialize_with = "empty_as_none")]
+    description: Option<String>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn confirm_draft(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(hid): Path<i32>,
+    Form(body): Form<ConfirmDraftModel>,
+) -> Result<impl IntoResponse, Error> {
+    let hours = body
+        .hours
+        .trim()
+        .parse()
+        .map_err(|_| Error::unprocessable_entity([("hours", "Enter a number of hours")]))?;
+
+    state
+        .db
+        .hours
+        .confirm_draft(ctx.user_id, hid, hours, body.description)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/hours")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn reject_draft(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(hid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    state.db.hours.reject_draft(ctx.user_id, hid).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/hours")]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn approvals_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let pending = state
+        .db
+        .hours
+        .list_pending_review(ctx.user_id, ctx.has(Permission::ApproveHours))
+        .await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Approve Hours</h1>
+            <p>"Hours count towards members' totals and reports once they're approved. You can approve hours from shifts you're the duty lead of"
+                {ctx.has(Permission::ApproveHours).then_some(", and hours not linked to a shift with a duty lead")} "."
+            </p>
+            {ApprovalsList(&pending)}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn approve_hours(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(hid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    review(&ctx, &state, hid, HoursStatus::Approved).await
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn reject_hours(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(hid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    review(&ctx, &state, hid, HoursStatus::Rejected).await
+}
+
+async fn review(
+    ctx: &Ctx,
+    state: &AppState,
+    hid: i32,
+    status: HoursStatus,
+) -> Result<impl IntoResponse + use<>, Error> {
+    state
+        .db
+        .hours
+        .review(ctx.user_id, ctx.has(Permission::ApproveHours), hid, status)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/hours/approvals")]),
+    ))
+}
+
+fn DraftsSection(drafts: &[HoursEntry]) -> impl Renderable {
+    let input = "w-24 px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-yellow rounded-t-lg">Confirm Hours From Your Shifts</h2>
+            <ul class="px-4 rounded-b-lg border border-yellow divide-y divide-neutral">
+                {drafts.iter().map(|draft| rsx_move! {
+                    <li class="py-2">
+                        <form hx-post=format!("/hours/{}/confirm", draft.hid) class="flex flex-wrap items-end gap-2" data-errors>
+                            <div class="grow">
+                                <p class="font-medium">{draft.event_name.as_ref()}</p>
+                                <p class="text-sm text-neutral">{draft.date.format("%b %-d, %Y").to_string()} " · " {&draft.type_name}</p>
+                            </div>
+                            <label class="block">
+                                <span class="text-sm font-medium">Hours</span>
+                                <input type="number" name="hours" value=format!("{:.2}", draft.hours) min="0.25" max="24" step="0.25" class=input required>
+                            </label>
+                            <label class="block">
+                                <span class="text-sm font-medium">Description</span>
+                                <input type="text" name="description" class="px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green">
+                            </label>
+                            <input class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Confirm">
+                            <button type="button" hx-post=format!("/hours/{}/reject", draft.hid) class="bg-white-true text-red border border-red px-2 py-1 shadow-sm rounded hover:bg-red hover:text-white">Reject</button>
+                            <p class="w-full text-red text-sm" data-error-for="hours"></p>
+                        </form>
+                    </li>
+                }).render_all()}
+            </ul>
+        </section>
+    }
+}
+
+fn LogHoursForm<'a>(
+    event_types: &'a [EventType],
+    shifts: &'a [LinkableShift],
+    today: NaiveDate,
+) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Log Hours</h2>
+            <form hx-post="/hours" class="p-4 rounded-b-lg border border-green-light grid desktop:grid-cols-2 gap-3" data-errors>
+                <label class="block">
+                    <span class="text-sm font-medium">Date</span>
+                    <input type="date" name="date" value=today.format("%Y-%m-%d").to_string() max=today.format("%Y-%m-%d").to_string() class=input required>
+                    <p class="text-red text-sm" data-error-for="date"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Type</span>
+                    <select name="etid" class=input>
+                        {event_types.iter().map(|et| rsx_move! {
+                            <option value=et.etid>{&et.name}</option>
+                        }).render_all()}
+                    </select>
+                    <p class="text-red text-sm" data-error-for="etid"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Shift</span>
+                    <select name="sid" class=input>
+                        <option value="">"— Not linked to a shift —"</option>
+                        {shifts.iter().map(|shift| rsx_move! {
+                            <option value=shift.sid>
+                                {shift.start_time.format("%b %-d").to_string()} " · " {&shift.event_name}
+                            </option>
+                        }).render_all()}
+                    </select>
+                    <p class="text-red text-sm" data-error-for="sid"></p>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Hours</span>
+                    <input type="number" name="hours" min="0.25" max="24" step="0.25" class=input required>
+                    <p class="text-red text-sm" data-error-for="hours"></p>
+                </label>
+                <label class="block desktop:col-span-2">
+                    <span class="text-sm font-medium">Description</span>
+                    <input type="text" name="description" class=input>
+                </label>
+                <div>
+                    <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Log Hours">
+                </div>
+            </form>
+        </section>
+    }
+}
+
+fn HoursTable(entries: &[HoursEntry]) -> impl Renderable {
+    rsx_move! {
+        <section class="overflow-x-auto">
+            <table class="w-full text-left text-sm">
+                <thead class="border-b border-neutral">
+                    <tr>
+                        <th class="py-1 pr-2">Date</th>
+                        <th class="py-1 pr-2">Type</th>
+                        <th class="py-1 pr-2">Event</th>
+                        <th class="py-1 pr-2 text-right">Hours</th>
+                        <th class="py-1 pr-2">Status</th>
+                        <th class="py-1">Description</th>
+                    </tr>
+                </thead>
+                <tbody class="divide-y divide-neutral">
+                    {entries.iter().map(|entry| rsx_move! {
+                        <tr>
+                            <td class="py-1 pr-2 whitespace-nowrap">{entry.date.format("%b %-d, %Y").to_string()}</td>
+                            <td class="py-1 pr-2">
+                                <span class="inline-block w-2 h-2 mr-1 rounded-full" style=format!("background-color: #{}", entry.colour.as_deref().unwrap_or(DEFAULT_COLOUR))></span>
+                                {&entry.type_name}
+                            </td>
+                            <td class="py-1 pr-2">
+                                {entry.event_name.as_ref()}
+                            </td>
+                            <td class="py-1 pr-2 text-right">{format!("{:.2}", entry.hours)}</td>
+                            <td class="py-1 pr-2">{Status(entry.status)}</td>
+                            <td class="py-1">{entry.description.as_ref()}</td>
+                        </tr>
+                    }).render_all()}
+                </tbody>
+            </table>
+            {(entries.is_empty()).then(|| rsx! {
+                <p class="text-neutral mt-2">No hours have been logged yet</p>
+            })}
+        </section>
+    }
+}
+
+fn Status(status: HoursStatus) -> impl Renderable {
+    let (class, name) = match status {
+        HoursStatus::Pending => ("text-yellow", "Pending"),
+        HoursStatus::Approved => ("text-green", "Approved"),
+        HoursStatus::Rejected => ("text-red", "Rejected"),
+    };
+
+    rsx_move! {
+        <span class=class>{name}</span>
+    }
+}
+
+fn ApprovalsList(pending: &[PendingHours]) -> impl Renderable {
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Waiting for Approval</h2>
+            <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                {pending.iter().map(|entry| rsx_move! {
+                    <li class="py-2 flex flex-wrap items-center gap-2">
+                        <div class="grow">
+                            <p class="font-medium">
+                                {&entry.display_name} " · " {format!("{:.2}", entry.hours)} {if entry.hours == 1.0 {" hour"} else {" hours"}}
+                            </p>
+                            <p class="text-sm text-neutral">
+                                {entry.date.format("%b %-d, %Y").to_string()} " · "
+                                <span class="inline-block w-2 h-2 mr-1 rounded-full" style=format!("background-color: #{}", entry.colour.as_deref().unwrap_or(DEFAULT_COLOUR))></span>
+                                {&entry.type_name}
+                                {entry.event_name.as_ref().map(|event_name| rsx_move! {
+                                    " · " {event_name}
+                                    {entry.shift_start.map(|start| format!(" ({})", start.format("%H:%M")))}
+                                })}
+                            </p>
+                            {entry.description.as_ref().map(|description| rsx_move! {
+                                <p class="text-sm">{description}</p>
+                            })}
+                        </div>
+                        <button hx-post=format!("/hours/approvals/{}/approve", entry.hid) class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light">Approve</button>
+                        <button hx-post=format!("/hours/approvals/{}/reject", entry.hid) hx-confirm=format!("Reject {}'s hours?", entry.display_name) class="bg-white-true text-red border border-red px-2 py-1 shadow-sm rounded hover:bg-red hover:text-white">Reject</button>
+                    </li>
+                }).render_all()}
+            </ul>
+            {pending.is_empty().then(|| rsx! {
+                <p class="text-neutral mt-2">"There are no hours waiting for your approval"</p>
+            })}
+        </section>
+    }
+}
@@ -0,0 +1,387 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+use db::CalendarShift;
+
+const PRODID: &str = "-//St. John Ambulance//Division 176 Volunteer Portal//EN";
+
+/// An iCalendar (RFC 5545) calendar with an event for each shift. Shift times
+/// are in `zone`, and published in UTC so they show at the right time
+/// wherever the subscriber is.
+pub fn calendar(
+    name: &str,
+    shifts: &[CalendarShift],
+    base_url: &str,
+    zone: Tz,
+    now: DateTime<Utc>,
+) -> String {
+    let host = base_url
+        .split_once("://")
+        .map_or(base_url, |(_, host)| host)
+        .trim_end_matches('/');
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+
+    let mut lines = vec![
+        String::from("BEGIN:VCALENDAR"),
+        String::from("VERSION:2.0"),
+        format!("PRODID:{PRODID}"),
+        String::from("CALSCALE:GREGORIAN"),
+        String::from("METHOD:PUBLISH"),
+        format!("X-WR-CALNAME:{}", escape(name)),
+        String::from("REFRESH-INTERVAL;VALUE=DURATION:PT1H"),
+        String::from("X-PUBLISHED-TTL:PT1H"),
+    ];
+
+    for shift in shifts {
+        let mut description = vec![shift.type_name.clone()];
+        if let Some(duty_lead) = &shift.duty_lead_name {
+            description.push(format!("Duty lead: {duty_lead}"));
+        }
+        if let Some(notes) = &shift.notes {
+            description.push(notes.clone());
+        }
+
+        lines.extend([
+            String::from("BEGIN:VEVENT"),
+            format!("UID:shift-{}@{host}", shift.sid),
+            format!("DTSTAMP:{stamp}"),
+            format!("DTSTART:{}", utc_time(shift.start_time, zone)),
+            format!("DTEND:{}", utc_time(shift.end_time, zone)),
+            format!("SUMMARY:{}", escape(&shift.event_name)),
+            format!("DESCRIPTION:{}", escape(&description.join("\n"))),
+            format!("URL:{base_url}/events/{}", shift.eid),
+        ]);
+        if let Some(location) = &shift.location {
+            lines.push(format!("LOCATION:{}", escape(location)));
+        }
+        lines.push(String::from("END:VEVENT"));
+    }
+    lines.push(String::from("END:VCALENDAR"));
+
+    lines.iter().fold(String::new(), |mut output, line| {
+        let _ = write!(output, "{}\r\n", fold(line));
+        output
+    })
+}
+
+/// Times repeated when daylight saving ends are the first of the two. Ones it
+/// skips when it starts are given the offset around them.
+fn utc_time(time: NaiveDateTime, zone: Tz) -> String {
+    let utc = match zone.from_local_datetime(&time).earliest() {
+        Some(local) => local.with_timezone(&Utc),
+        None => (time - zone.offset_from_utc_datetime(&time).fix()).and_utc(),
+    };
+    utc.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escapes a TEXT value
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            ';' => escaped.push_str("\\;"),
+            ',' => escaped.push_str("\\,"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => (),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Splits lines longer than 75 octets, without splitting a character
+fn fold(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len());
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            folded.push_str("\r\n ");
+            // The leading space counts towards the continuation line
+            width = 1;
+        }
+        folded.push(c);
+        width += c.len_utf8();
+    }
+    folded
+}
+
+/// A VEVENT read from a calendar, along with the line it starts on
+#[derive(Debug)]
+pub struct VEvent {
+    pub line: usize,
+    properties: Vec<Property>,
+}
+
+#[derive(Debug)]
+pub struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl VEvent {
+    pub fn get(&self, name: &str) -> Option<&Property> {
+        self.properties
+            .iter()
+            .find(|property| property.name.eq_ignore_ascii_case(name))
+    }
+
+    /// A TEXT property, unescaped, if it isn't empty
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.get(name)
+            .map(|property| unescape(&property.value))
+            .filter(|text| !text.trim().is_empty())
+    }
+
+    /// A DATE-TIME property as local time in `zone`. UTC times and ones with
+    /// a `TZID` are converted, and floating times are taken as already local.
+    /// Dates without a time are `None`, and time zones which aren't IANA
+    /// names are an error.
+    pub fn date_time(&self, name: &str, zone: Tz) -> Result<Option<NaiveDateTime>, String> {
+        let Some(property) = self.get(name) else {
+            return Ok(None);
+        };
+        let value = property.value.trim();
+        let parse = |value| NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S").ok();
+
+        if let Some(utc) = value.strip_suffix('Z') {
+            return Ok(parse(utc).map(|utc| utc.and_utc().with_timezone(&zone).naive_local()));
+        }
+        let Some(time) = parse(value) else {
+            return Ok(None);
+        };
+        let Some(tzid) = property.param("TZID") else {
+            return Ok(Some(time));
+        };
+
+        let from: Tz = tzid
+            .trim_start_matches('/')
+            .parse()
+            .map_err(|_| format!("Unknown time zone {tzid} in {name}"))?;
+        let time = from
+            .from_local_datetime(&time)
+            .earliest()
+            .ok_or_else(|| format!("{name} is skipped by a daylight saving change"))?;
+
+        Ok(Some(time.with_timezone(&zone).naive_local()))
+    }
+}
+
+impl Property {
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params
+            .iter()
+            .find(|(param, _)| param.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+/// Reads the VEVENTs of a calendar. Lines which can't be read are skipped, so
+/// a VEvent may be missing properties.
+pub fn parse(calendar: &str) -> Vec<VEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<VEvent> = None;
+    // Alarms and other components nested in an event aren't needed
+    let mut nested: usize = 0;
+
+    for (line, content) in unfold(calendar) {
+        let Some(property) = property(&content) else {
+            continue;
+        };
+
+        let name = property.name.to_ascii_uppercase();
+        match (name.as_str(), property.value.trim(), &mut current) {
+            ("BEGIN", "VEVENT", None) => {
+                current = Some(VEvent {
+                    line,
+                    properties: Vec::new(),
+                });
+            }
+            ("BEGIN", _, Some(_)) => nested += 1,
+            ("END", "VEVENT", Some(_)) if nested == 0 => events.extend(current.take()),
+            ("END", _, Some(_)) => nested = nested.saturating_sub(1),
+            (_, _, Some(event)) if nested == 0 => event.properties.push(property),
+            _ => (),
+        }
+    }
+
+    events
+}
+
+/// Joins folded lines back together, keeping the number each starts on
+fn unfold(calendar: &str) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = Vec::new();
+
+    for (i, line) in calendar.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some((_, previous))) => previous.push_str(rest),
+            _ => lines.push((i + 1, line.to_owned())),
+        }
+    }
+
+    lines
+}
+
+/// Splits `NAME;PARAM=value:value`, allowing quoted parameter values to
+/// contain `:` and `;`
+fn property(line: &str) -> Option<Property> {
+    let (split, _) = unquoted(line).find(|(_, c)| *c == ':')?;
+    let (head, value) = (&line[..split], &line[split + 1..]);
+
+    let mut parts = split_unquoted(head, ';').into_iter();
+    let name = parts.next()?.trim();
+    if name.is_empty() {
+        return None;
+    }
+    let params = parts
+        .filter_map(|param| param.split_once('='))
+        .map(|(param, value)| (param.to_owned(), value.trim_matches('"').to_owned()))
+        .collect();
+
+    Some(Property {
+        name: name.to_owned(),
+        params,
+        value: value.to_owned(),
+    })
+}
+
+/// The characters of `value` outside double quotes, with their offsets
+fn unquoted(value: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quoted = false;
+    value.char_indices().filter(move |(_, c)| {
+        if *c == '"' {
+            quoted = !quoted;
+        }
+        !quoted && *c != '"'
+    })
+}
+
+fn split_unquoted(value: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for (i, _) in unquoted(value).filter(|(_, c)| *c == separator) {
+        parts.push(&value[start..i]);
+        start = i + 1;
+    }
+    parts.push(&value[start..]);
+    parts
+}
+
+fn unescape(value: &str) -> String {
+    let mut unescaped = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n' | 'N') => unescaped.push('\n'),
+            Some(c) => unescaped.push(c),
+            None => (),
+        }
+    }
+    unescaped
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    #[test]
+    fn escapes_text() {
+        assert_eq!(
+            escape("Bring water, snacks; and a\\ jacket\r\nThanks"),
+            "Bring water\\, snacks\\; and a\\\\ jacket\\nThanks"
+        );
+    }
+
+    #[test]
+    fn folds_long_lines_on_character_boundaries() {
+        let line = format!("SUMMARY:{}", "é".repeat(40));
+        let folded = fold(&line);
+
+        assert!(folded.split("\r\n").all(|line| line.len() <= 75));
+        assert_eq!(folded.replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn parses_folded_and_escaped_events() {
+        let calendar = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            SUMMARY:Canada Day\\, Inner\r\n  Harbour\r\n\
+            DTSTART;TZID=\"America/Vancouver\":20260701T090000\r\n\
+            DTEND;TZID=America/Vancouver:20260701T170000\r\n\
+            ORGANIZER;CN=\"Smith; Jane\":mailto:jane@example.com\r\n\
+            BEGIN:VALARM\r\n\
+            ACTION:DISPLAY\r\n\
+            END:VALARM\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let events = parse(calendar);
+
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.line, 2);
+        assert_eq!(
+            event.text("summary").as_deref(),
+            Some("Canada Day, Inner Harbour")
+        );
+        assert_eq!(
+            event.date_time("DTSTART", Tz::America__Vancouver),
+            Ok(NaiveDate::from_ymd_opt(2026, 7, 1).and_then(|date| date.and_hms_opt(9, 0, 0)))
+        );
+        assert_eq!(
+            event
+                .get("ORGANIZER")
+                .and_then(|organizer| organizer.param("CN")),
+            Some("Smith; Jane")
+        );
+        assert!(event.get("ACTION").is_none());
+    }
+
+    #[test]
+    fn converts_times_to_the_division_zone() {
+        let calendar = "BEGIN:VCALENDAR\r\n\
+            BEGIN:VEVENT\r\n\
+            DTSTART:20260701T160000Z\r\n\
+            DTEND;TZID=America/Toronto:20260701T120000\r\n\
+            DUE;TZID=Pacific Standard Time:20260701T120000\r\n\
+            DTSTAMP:20260701T090000\r\n\
+            END:VEVENT\r\n\
+            END:VCALENDAR\r\n";
+        let event = &parse(calendar)[0];
+        let nine = NaiveDate::from_ymd_opt(2026, 7, 1).and_then(|date| date.and_hms_opt(9, 0, 0));
+
+        assert_eq!(event.date_time("DTSTART", Tz::America__Vancouver), Ok(nine));
+        assert_eq!(event.date_time("DTEND", Tz::America__Vancouver), Ok(nine));
+        assert_eq!(event.date_time("DTSTAMP", Tz::America__Vancouver), Ok(nine));
+        assert!(event.date_time("DUE", Tz::America__Vancouver).is_err());
+    }
+
+    #[test]
+    fn publishes_shift_times_in_utc() {
+        let at = |month, day, hour| {
+            NaiveDate::from_ymd_opt(2026, month, day)
+                .and_then(|date| date.and_hms_opt(hour, 30, 0))
+                .unwrap()
+        };
+        let zone = Tz::America__Vancouver;
+
+        assert_eq!(utc_time(at(7, 1, 9), zone), "20260701T163000Z");
+        assert_eq!(utc_time(at(1, 15, 9), zone), "20260115T173000Z");
+        // Daylight saving ends at 2:00 on Nov 1, repeating 1:30
+        assert_eq!(utc_time(at(11, 1, 1), zone), "20261101T083000Z");
+        // and starts at 2:00 on Mar 8, skipping 2:30
+        assert_eq!(utc_time(at(3, 8, 2), zone), "20260308T103000Z");
+    }
+}
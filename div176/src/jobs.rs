@@ -0,0 +1,73 @@
+use std::time::Duration;
+
+use chrono::NaiveDate;
+use db::DB;
+use tracing::{error, info};
+
+use crate::{AppState, series};
+
+const INTERVAL: Duration = Duration::from_secs(60);
+/// How long login attempts are kept for review
+const LOGIN_ATTEMPTS_KEPT_FOR: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+
+/// Runs the periodic background jobs for as long as the server is up
+pub fn spawn(state: AppState) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(INTERVAL);
+
+        loop {
+            interval.tick().await;
+            materialise_series(&state.db, state.config.today()).await;
+            prefill_hours(&state.db).await;
+            cleanup_sessions(&state.db, state.config.session_idle_timeout).await;
+            cleanup_login_attempts(&state.db).await;
+        }
+    });
+}
+
+#[tracing::instrument(skip(db))]
+async fn materialise_series(db: &DB, today: NaiveDate) {
+    let due = match db.series.list_due().await {
+        Ok(due) => due,
+        Err(e) => return error!("listing due series failed: {:?}", e),
+    };
+
+    for series in due {
+        match series::materialise(db, &series, today).await {
+            Ok(0) => (),
+            Ok(events) => info!(series.esid, events, "created series occurrences"),
+            Err(e) => error!(series.esid, "creating series occurrences failed: {:?}", e),
+        }
+    }
+}
+
+#[tracing::instrument(skip(db))]
+async fn prefill_hours(db: &DB) {
+    match db.hours.prefill_completed_shifts().await {
+        Ok(0) => (),
+        Ok(drafts) => info!(drafts, "pre-filled hours from completed shifts"),
+        Err(e) => error!("pre-filling hours failed: {:?}", e),
+    }
+}
+
+#[tracing::instrument(skip(db))]
+async fn cleanup_sessions(db: &DB, idle_timeout: Duration) {
+    match db.sessions.delete_stale(idle_timeout).await {
+        Ok(0) => (),
+        Ok(sessions) => info!(sessions, "deleted expired sessions"),
+        Err(e) => error!("deleting expired sessions failed: {:?}", e),
+    }
+}
+
+#[tracing::instrument(skip(db))]
+async fn cleanup_login_attempts(db: &DB) {
+    match db
+        .login_attempts
+        .delete_older_than(LOGIN_ATTEMPTS_KEPT_FOR)
+        .await
+    {
+        Ok(0) => (),
+        Ok(attempts) => info!(attempts, "deleted old login attempts"),
+        Err(e) => error!("deleting old login attempts failed: {:?}", e),
+    }
+}
@@ -1,47 +1,282 @@
-use std::any::Any;
+use std::{any::Any, net::SocketAddr, sync::Arc};
 
 use axum::{
     Router,
     body::Body,
     extract::State,
     http::{Response, StatusCode},
+    middleware,
     response::IntoResponse,
-    routing::get,
+    routing::{get, post},
 };
 use components::Layout;
 use db::{DB, Database, embedded_db};
 use hypertext::*;
+use mail::Mailer;
 use telemetry::{otel_tracing, tracing_init};
 use tokio::signal;
 use tower_http::{catch_panic::CatchPanicLayer, compression::CompressionLayer};
-use tracing::{error, info};
+use tracing::{error, info, warn};
+use types::Role;
 
-use crate::{auth::AuthUser, r#static::static_handler};
+use crate::{auth::AuthUser, config::Config, r#static::static_handler};
 
+mod account;
+mod admin;
 mod auth;
+mod calendar;
+mod client;
+mod comments;
+mod config;
+mod credentials;
+mod events;
+mod forms;
 mod home;
+mod hours;
+mod ical;
+mod jobs;
+mod passwords;
+mod qualifications;
+mod recurrence;
+mod reports;
+mod scheduling;
+mod series;
+mod sessions;
 mod r#static;
+mod totp;
+mod two_factor;
+mod volunteers;
 
 #[derive(Clone)]
 struct AppState {
     db: DB,
+    config: Arc<Config>,
+    mailer: Mailer,
 }
 
 #[tokio::main]
 async fn main() {
     tracing_init(env!("CARGO_PKG_NAME"), env!("GIT_HASH"));
 
-    let (url, _pg) = embedded_db().await;
+    let config = Config::from_env().expect("valid configuration");
 
+    let (url, _pg) = match &config.database {
+        config::Database::Url(url) => (url.clone(), None),
+        config::Database::Embedded { data_dir } => {
+            let (url, pg) = embedded_db(data_dir.as_deref())
+                .await
+                .expect("embedded database to start");
+            (url, Some(pg))
+        }
+    };
+
+    let mailer = match &config.smtp_url {
+        Some(url) => Mailer::smtp(url, &config.mail_from),
+        None => {
+            if !cfg!(debug_assertions) {
+                warn!("SMTP_URL not set, outgoing mail will only be logged");
+            }
+            Mailer::log(config.mail_dir.clone(), &config.mail_from)
+        }
+    }
+    .expect("valid mail configuration");
+
+    let bind_address = config.bind_address;
     let state = AppState {
-        db: DB::init(&url).await.unwrap(),
+        db: DB::init(
+            &url,
+            config.db_max_connections,
+            config.time_zone.name(),
+            config.db_reset,
+        )
+        .await
+        .unwrap(),
+        config: Arc::new(config),
+        mailer,
     };
 
+    if let Some(username) = &state.config.admin_username {
+        match state.db.users.grant_role(username, Role::Admin).await {
+            Ok(Some(_)) => {}
+            Ok(None) => warn!(username, "ADMIN_USERNAME doesn't match an active member"),
+            Err(e) => error!("granting ADMIN_USERNAME admin failed: {:?}", e),
+        }
+    }
+
+    jobs::spawn(state.clone());
+
     let app = Router::new()
         .route("/", get(home::home))
         .route("/login", get(auth::login_page).post(auth::login))
-        .route("/static/*file", get(static_handler))
+        .route(
+            "/password-reset",
+            get(credentials::password_reset_page).post(credentials::request_password_reset),
+        )
+        .route(
+            "/password-reset/:token",
+            get(credentials::password_reset_token_page).post(credentials::reset_password),
+        )
+        .route(
+            "/invite/:token",
+            get(credentials::invitation_page).post(credentials::accept_invitation),
+        )
+        .route(
+            "/email-change/:token",
+            get(credentials::email_change_page).post(credentials::confirm_email_change),
+        )
+        .route(
+            "/login/two-factor",
+            get(two_factor::second_factor_page).post(two_factor::verify_second_factor),
+        )
+        .route("/login/two-factor/setup", post(two_factor::enrol_pending))
+        .route("/logout", post(auth::logout))
+        .route("/logout/all", post(auth::logout_everywhere))
+        .route("/account/sessions/:usid/revoke", post(auth::revoke_session))
+        .route("/account", get(account::account))
+        .route(
+            "/account/two-factor",
+            get(two_factor::two_factor_page).post(two_factor::enrol_account),
+        )
+        .route(
+            "/account/two-factor/recovery-codes",
+            post(two_factor::regenerate_recovery_codes),
+        )
+        .route("/account/two-factor/disable", post(two_factor::disable))
+        .route("/account/calendar", post(calendar::create_link))
+        .route("/account/calendar/revoke", post(calendar::revoke_link))
+        .route("/calendar/:token/shifts.ics", get(calendar::shifts_feed))
+        .route("/calendar/:token/events.ics", get(calendar::events_feed))
+        .route(
+            "/events",
+            get(events::events).post(scheduling::create_event),
+        )
+        .route("/events/new", get(scheduling::new_event_page))
+        .route(
+            "/events/:eid",
+            get(events::event).post(scheduling::update_event),
+        )
+        .route("/events/:eid/edit", get(scheduling::edit_event_page))
+        .route("/events/:eid/shifts", post(scheduling::add_shifts))
+        .route("/events/:eid/duplicate", post(scheduling::duplicate_event))
+        .route("/events/:eid/cancel", post(scheduling::cancel_event))
+        .route("/events/:eid/comments", post(comments::post_comment))
+        .route("/comments/:cid", post(comments::edit_comment))
+        .route("/comments/:cid/delete", post(comments::delete_comment))
+        .route("/hours", get(hours::hours_page).post(hours::log_hours))
+        .route("/hours/approvals", get(hours::approvals_page))
+        .route("/hours/approvals/:hid/approve", post(hours::approve_hours))
+        .route("/hours/approvals/:hid/reject", post(hours::reject_hours))
+        .route("/hours/:hid/confirm", post(hours::confirm_draft))
+        .route("/hours/:hid/reject", post(hours::reject_draft))
+        .route("/qualifications", get(qualifications::roster))
+        .route("/reports", get(reports::reports_page))
+        .route("/reports/:report/csv", get(reports::report_csv))
+        .route("/reports/:report/print", get(reports::report_print))
+        .route(
+            "/series",
+            get(series::series_page).post(series::create_series),
+        )
+        .route(
+            "/series/:esid",
+            get(series::series_detail).post(series::update_series),
+        )
+        .route("/series/:esid/archive", post(series::archive_series))
+        .route("/series/:esid/exceptions", post(series::add_exception))
+        .route(
+            "/series/:esid/exceptions/:date/delete",
+            post(series::remove_exception),
+        )
+        .route("/shifts/:sid", post(scheduling::update_shift))
+        .route("/shifts/:sid/delete", post(scheduling::delete_shift))
+        .route("/shifts/:sid/signup", post(events::signup))
+        .route("/shifts/:sid/withdraw", post(events::withdraw))
+        .route("/shifts/:sid/vehicles", post(events::attach_vehicle))
+        .route(
+            "/shifts/:sid/vehicles/:vid/detach",
+            post(events::detach_vehicle),
+        )
+        .route("/admin/audit-log", get(admin::audit_log::audit_log_page))
+        .route(
+            "/admin/event-types",
+            get(admin::event_types::event_types_page).post(admin::event_types::create_event_type),
+        )
+        .route(
+            "/admin/event-types/:etid",
+            post(admin::event_types::update_event_type),
+        )
+        .route(
+            "/admin/event-types/:etid/archive",
+            post(admin::event_types::archive_event_type),
+        )
+        .route(
+            "/admin/event-types/:etid/unarchive",
+            post(admin::event_types::unarchive_event_type),
+        )
+        .route(
+            "/admin/groups",
+            get(admin::groups::groups_page).post(admin::groups::create_group),
+        )
+        .route("/admin/groups/:gid", post(admin::groups::update_group))
+        .route(
+            "/admin/groups/:gid/archive",
+            post(admin::groups::archive_group),
+        )
+        .route(
+            "/admin/groups/:gid/unarchive",
+            post(admin::groups::unarchive_group),
+        )
+        .route(
+            "/admin/invitations",
+            get(admin::invitations::invitations_page).post(admin::invitations::invite),
+        )
+        .route(
+            "/admin/invitations/:uid/resend",
+            post(admin::invitations::resend),
+        )
+        .route(
+            "/admin/login-attempts",
+            get(admin::login_attempts::login_attempts_page),
+        )
+        .route(
+            "/admin/login-attempts/unlock",
+            post(admin::login_attempts::unlock),
+        )
+        .route(
+            "/admin/import",
+            get(admin::import::import_page).post(admin::import::import_events),
+        )
+        .route("/admin/import/preview", post(admin::import::preview_import))
+        .route("/admin/roles", get(admin::roles::roles_page))
+        .route("/admin/roles/:uid", post(admin::roles::set_roles))
+        .route(
+            "/admin/vehicles",
+            get(admin::vehicles::vehicles_page).post(admin::vehicles::create_vehicle),
+        )
+        .route(
+            "/admin/vehicles/:vid",
+            post(admin::vehicles::update_vehicle),
+        )
+        .route(
+            "/admin/vehicles/:vid/archive",
+            post(admin::vehicles::archive_vehicle),
+        )
+        .route(
+            "/admin/vehicles/:vid/unarchive",
+            post(admin::vehicles::unarchive_vehicle),
+        )
+        .route("/volunteers", get(volunteers::directory))
+        .route(
+            "/volunteers/:uid",
+            get(volunteers::profile).post(volunteers::update_profile),
+        )
+        .route("/volunteers/:uid/archive", post(volunteers::archive))
+        .route("/volunteers/:uid/unarchive", post(volunteers::unarchive))
         .route("/protected", get(protected))
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            sessions::middleware,
+        ))
+        .route("/static/*file", get(static_handler))
         .layer(otel_tracing())
         .route("/health", get(healthcheck))
         .route("/version", get(|| async { env!("GIT_HASH") }))
@@ -63,15 +298,16 @@ async fn main() {
         .layer(CompressionLayer::new())
         .layer(CatchPanicLayer::custom(handle_panic));
 
-    let listener = tokio::net::TcpListener::bind("127.0.0.1:3000")
-        .await
-        .unwrap();
+    let listener = tokio::net::TcpListener::bind(bind_address).await.unwrap();
 
     info!("listening on {}", listener.local_addr().unwrap());
-    axum::serve(listener, app)
-        .with_graceful_shutdown(shutdown_signal())
-        .await
-        .unwrap();
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .with_graceful_shutdown(shutdown_signal())
+    .await
+    .unwrap();
 }
 
 pub async fn fallback_404() -> impl IntoResponse {
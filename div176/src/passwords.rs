@@ -0,0 +1,117 @@
+use anyhow::anyhow;
+use argon2::{
+    Algorithm, Argon2, Params, Version,
+    password_hash::{self, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+};
+use types::Error;
+
+/// Shortest password accepted when one is set
+pub const MIN_LEN: usize = 10;
+/// Bounds the work a single hash can take
+pub const MAX_LEN: usize = 256;
+
+/// Hashes a new password with the configured parameters
+pub async fn hash(params: &Params, password: String) -> Result<String, Error> {
+    let params = params.clone();
+
+    tokio::task::spawn_blocking(move || hash_blocking(&params, &password))
+        .await
+        .map_err(|e| anyhow!("password hashing task failed: {}", e))?
+}
+
+/// Takes as long as `verify` does, for when there is no stored hash to check
+/// against, so response times don't reveal which usernames exist
+pub async fn verify_dummy(params: &Params, password: String) -> Result<(), Error> {
+    hash(params, password).await.map(drop)
+}
+
+/// Checks a password against its stored hash. If the hash was made with other
+/// parameters than the configured ones, returns a new hash to replace it with.
+pub async fn verify(
+    params: &Params,
+    password: String,
+    stored: String,
+) -> Result<Option<String>, Error> {
+    let params = params.clone();
+
+    tokio::task::spawn_blocking(move || {
+        let stored = PasswordHash::new(&stored)
+            .map_err(|e| anyhow!("stored password hash to be valid: {}", e))?;
+
+        // The algorithm and parameters are taken from the stored hash
+        Argon2::default()
+            .verify_password(password.as_bytes(), &stored)
+            .map_err(|e| match e {
+                password_hash::Error::Password => Error::Unauthorized,
+                _ => anyhow!("verifying password failed: {}", e).into(),
+            })?;
+
+        if outdated(&stored, &params) {
+            hash_blocking(&params, &password).map(Some)
+        } else {
+            Ok(None)
+        }
+    })
+    .await
+    .map_err(|e| anyhow!("password verification task failed: {}", e))?
+}
+
+fn hasher(params: &Params) -> Argon2<'static> {
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params.clone())
+}
+
+fn hash_blocking(params: &Params, password: &str) -> Result<String, Error> {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+        .map_err(|e| anyhow!("encoding salt failed: {}", e))?;
+
+    let hash = hasher(params)
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow!("hashing password failed: {}", e))?;
+
+    Ok(hash.to_string())
+}
+
+fn outdated(stored: &PasswordHash, params: &Params) -> bool {
+    let Ok(stored_params) = Params::try_from(stored) else {
+        return true;
+    };
+
+    stored.algorithm != Algorithm::Argon2id.ident()
+        || stored.version != Some(Version::V0x13.into())
+        || stored_params.m_cost() != params.m_cost()
+        || stored_params.t_cost() != params.t_cost()
+        || stored_params.p_cost() != params.p_cost()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn params(m_cost: u32, t_cost: u32) -> Params {
+        Params::new(m_cost, t_cost, 1, None).unwrap()
+    }
+
+    #[tokio::test]
+    async fn rehashes_outdated_params() {
+        let weak = hash_blocking(&params(1024, 1), "hunter2").unwrap();
+
+        let rehashed = verify(&params(2048, 2), "hunter2".into(), weak)
+            .await
+            .unwrap()
+            .expect("a new hash");
+        assert!(rehashed.contains("m=2048,t=2,p=1"));
+
+        let current = verify(&params(2048, 2), "hunter2".into(), rehashed)
+            .await
+            .unwrap();
+        assert_eq!(current, None);
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_password() {
+        let stored = hash_blocking(&params(1024, 1), "hunter2").unwrap();
+
+        let result = verify(&params(1024, 1), "hunter3".into(), stored).await;
+        assert!(matches!(result, Err(Error::Unauthorized)));
+    }
+}
@@ -0,0 +1,135 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    extract::{Query, State},
+    response::IntoResponse,
+};
+use chrono::{DateTime, Utc};
+use components::{Layout, SelectOption};
+use db::{Group, RosterEntry};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser};
+
+/// How far ahead an upcoming expiry is highlighted by default
+pub const EXPIRY_WARNING_DAYS: i32 = 30;
+
+#[derive(Deserialize, Debug)]
+pub struct RosterFilter {
+    gid: Option<i32>,
+    days: Option<i32>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn roster(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Query(filter): Query<RosterFilter>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ViewMembers)?;
+
+    let qualifications = state.db.groups.list_qualifications().await?;
+    let days = filter.days.unwrap_or(EXPIRY_WARNING_DAYS).clamp(0, 3650);
+    let selected = filter
+        .gid
+        .or_else(|| qualifications.first().map(|group| group.gid));
+
+    let entries = match selected {
+        Some(gid) => state.db.groups.roster(gid, days).await?,
+        None => Vec::new(),
+    };
+    let expiring = entries.iter().filter(|entry| entry.expiring).count();
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Qualification Roster</h1>
+            {RosterFilterForm(&qualifications, selected, days)}
+            {(qualifications.is_empty()).then(|| rsx! {
+                <p class="text-neutral">No qualifications have been set up</p>
+            })}
+            {selected.map(|_| rsx_move! {
+                <p>{expiring} " of " {entries.len()} " expired or expiring within " {days} " days"</p>
+                <table class="w-full text-left text-sm">
+                    <thead class="border-b border-neutral">
+                        <tr>
+                            <th class="py-1 pr-2">Volunteer</th>
+                            <th class="py-1 pr-2">Issued</th>
+                            <th class="py-1 pr-2">Expires</th>
+                            <th class="py-1">Status</th>
+                        </tr>
+                    </thead>
+                    <tbody class="divide-y divide-neutral">
+                        {entries.iter().map(|entry| RosterRow(entry, days)).render_all()}
+                    </tbody>
+                </table>
+            })}
+        </div>
+    })
+    .render())
+}
+
+fn RosterFilterForm(qualifications: &[Group], selected: Option<i32>, days: i32) -> impl Renderable {
+    let input = "px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <form method="get" action="/qualifications" class="flex flex-wrap items-end gap-2">
+            <label class="block">
+                <span class="text-sm font-medium">Qualification</span>
+                <select name="gid" class=input>
+                    {qualifications.iter().map(|group| {
+                        SelectOption(group.gid.to_string(), &group.name, Some(group.gid) == selected)
+                    }).render_all()}
+                </select>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Expiring within (days)</span>
+                <input type="number" name="days" value=days min="0" max="3650" class=input>
+            </label>
+            <input class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Show">
+        </form>
+    }
+}
+
+fn RosterRow(entry: &RosterEntry, days: i32) -> impl Renderable {
+    let class = if entry.expiring { "bg-yellow/20" } else { "" };
+
+    rsx_move! {
+        <tr class=class>
+            <td class="py-1 pr-2">{&entry.display_name}</td>
+            <td class="py-1 pr-2">{Date(entry.issued_at)}</td>
+            <td class="py-1 pr-2">{Date(entry.expires_at)}</td>
+            <td class="py-1">{QualificationStatus(entry.valid, entry.expires_at, days)}</td>
+        </tr>
+    }
+}
+
+pub fn QualificationStatus(
+    valid: bool,
+    expires_at: Option<DateTime<Utc>>,
+    warning_days: i32,
+) -> impl Renderable {
+    let days_left = expires_at.map(|expires_at| (expires_at - Utc::now()).num_days());
+
+    let (class, label) = match days_left {
+        _ if !valid => ("bg-red text-white", String::from("Expired")),
+        None => ("bg-green text-white", String::from("No expiry")),
+        Some(days) if days < warning_days.into() => (
+            "bg-yellow text-white",
+            format!(
+                "Expires in {days} {}",
+                if days == 1 { "day" } else { "days" }
+            ),
+        ),
+        Some(_) => ("bg-green text-white", String::from("Valid")),
+    };
+
+    rsx_move! {
+        <span class=format!("px-2 py-0.5 rounded-full text-xs whitespace-nowrap {class}")>{label}</span>
+    }
+}
+
+pub fn Date(date: Option<DateTime<Utc>>) -> impl Renderable {
+    date.map(|date| date.format("%b %-d, %Y").to_string())
+}
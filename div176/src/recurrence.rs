@@ -0,0 +1,138 @@
+use chrono::{Datelike, Days, Months, NaiveDate};
+use db::Frequency;
+
+/// The dates a series falls on from `from` through `until`, before exceptions
+pub fn occurrences(
+    frequency: Frequency,
+    starts_on: NaiveDate,
+    ends_on: Option<NaiveDate>,
+    from: NaiveDate,
+    until: NaiveDate,
+) -> Vec<NaiveDate> {
+    let until = ends_on.map_or(until, |ends_on| ends_on.min(until));
+    let from = from.max(starts_on);
+
+    let dates: Box<dyn Iterator<Item = NaiveDate>> = match frequency {
+        Frequency::Weekly => Box::new(every(starts_on, 7)),
+        Frequency::Biweekly => Box::new(every(starts_on, 14)),
+        Frequency::MonthlyNthWeekday => Box::new(monthly_nth_weekday(starts_on)),
+    };
+
+    dates
+        .skip_while(|date| *date < from)
+        .take_while(|date| *date <= until)
+        .collect()
+}
+
+/// When a series falls from today through its horizon, along with the last
+/// day that covers
+pub fn upcoming(
+    frequency: Frequency,
+    starts_on: NaiveDate,
+    ends_on: Option<NaiveDate>,
+    horizon_days: i32,
+    today: NaiveDate,
+) -> (Vec<NaiveDate>, NaiveDate) {
+    let until = today + Days::new(horizon_days.max(0) as u64);
+    let until = ends_on.map_or(until, |ends_on| ends_on.min(until));
+
+    (
+        occurrences(frequency, starts_on, ends_on, today, until),
+        until,
+    )
+}
+
+fn every(starts_on: NaiveDate, days: u64) -> impl Iterator<Item = NaiveDate> {
+    (0..).map_while(move |i| starts_on.checked_add_days(Days::new(i * days)))
+}
+
+/// The same week and weekday of each month as `starts_on`. Starting in the
+/// fifth week means the last one, since not every month has five.
+fn monthly_nth_weekday(starts_on: NaiveDate) -> impl Iterator<Item = NaiveDate> {
+    let weekday = starts_on.weekday();
+    let nth = (starts_on.day0() / 7 + 1) as u8;
+    let first_of_month = starts_on.with_day(1).expect("every month has a first day");
+
+    (0..)
+        .map_while(move |i| first_of_month.checked_add_months(Months::new(i)))
+        .map(move |month| {
+            NaiveDate::from_weekday_of_month_opt(month.year(), month.month(), weekday, nth)
+                .or_else(|| {
+                    NaiveDate::from_weekday_of_month_opt(month.year(), month.month(), weekday, 4)
+                })
+                .expect("every month has four of each weekday")
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> NaiveDate {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn weekly_and_biweekly_start_from_the_first_occurrence() {
+        assert_eq!(
+            occurrences(
+                Frequency::Weekly,
+                date("2026-01-06"),
+                None,
+                date("2026-01-10"),
+                date("2026-01-27"),
+            ),
+            [date("2026-01-13"), date("2026-01-20"), date("2026-01-27")]
+        );
+        assert_eq!(
+            occurrences(
+                Frequency::Biweekly,
+                date("2026-01-06"),
+                Some(date("2026-02-10")),
+                date("2026-01-01"),
+                date("2026-03-31"),
+            ),
+            [date("2026-01-06"), date("2026-01-20"), date("2026-02-03")]
+        );
+    }
+
+    #[test]
+    fn monthly_keeps_the_week_and_weekday() {
+        // Second Tuesday
+        assert_eq!(
+            occurrences(
+                Frequency::MonthlyNthWeekday,
+                date("2026-01-13"),
+                None,
+                date("2026-01-01"),
+                date("2026-04-30"),
+            ),
+            [
+                date("2026-01-13"),
+                date("2026-02-10"),
+                date("2026-03-10"),
+                date("2026-04-14"),
+            ]
+        );
+    }
+
+    #[test]
+    fn monthly_in_the_fifth_week_means_the_last() {
+        // Fifth Thursday of January, and the last Thursday after that
+        assert_eq!(
+            occurrences(
+                Frequency::MonthlyNthWeekday,
+                date("2026-01-29"),
+                None,
+                date("2026-01-01"),
+                date("2026-04-30"),
+            ),
+            [
+                date("2026-01-29"),
+                date("2026-02-26"),
+                date("2026-03-26"),
+                date("2026-04-30"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,560 @@
+#![allow(non_snake_case)]
+
+use std::borrow::Cow;
+
+use axum::{
+    extract::{Path, Query, State},
+    http::header,
+    response::IntoResponse,
+};
+use chrono::{Datelike, Months, NaiveDate};
+use components::Layout;
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser};
+
+/// The yearly hours members are expected to volunteer, unless the report asks
+/// for another
+const DEFAULT_MINIMUM_HOURS: f64 = 40.0;
+const TOP_CONTRIBUTORS: i64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Report {
+    Members,
+    Monthly,
+    TopContributors,
+    BelowMinimum,
+}
+
+impl Report {
+    const ALL: [Report; 4] = [
+        Report::Members,
+        Report::Monthly,
+        Report::TopContributors,
+        Report::BelowMinimum,
+    ];
+
+    fn slug(self) -> &'static str {
+        match self {
+            Report::Members => "members",
+            Report::Monthly => "monthly",
+            Report::TopContributors => "top-contributors",
+            Report::BelowMinimum => "below-minimum",
+        }
+    }
+
+    fn from_slug(slug: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|report| report.slug() == slug)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ReportFilter {
+    from: Option<String>,
+    to: Option<String>,
+    minimum: Option<String>,
+}
+
+/// The period and threshold a report covers, defaulting to the current
+/// quarter
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    from: NaiveDate,
+    to: NaiveDate,
+    minimum: f64,
+    /// In the division's time zone, which the preset periods count from
+    today: NaiveDate,
+}
+
+impl Options {
+    fn new(filter: &ReportFilter, today: NaiveDate) -> Self {
+        let date = |value: &Option<String>| {
+            value
+                .as_deref()
+                .and_then(|value| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok())
+        };
+        let (quarter_start, quarter_end) = quarter(today);
+        let from = date(&filter.from).unwrap_or(quarter_start);
+        let to = date(&filter.to).unwrap_or(quarter_end).max(from);
+        let minimum = filter
+            .minimum
+            .as_deref()
+            .and_then(|minimum| minimum.parse::<f64>().ok())
+            .filter(|minimum| minimum.is_finite() && *minimum >= 0.0)
+            .unwrap_or(DEFAULT_MINIMUM_HOURS);
+
+        Self {
+            from,
+            to,
+            minimum,
+            today,
+        }
+    }
+
+    fn query(&self) -> String {
+        format!(
+            "from={}&to={}&minimum={}",
+            self.from.format("%Y-%m-%d"),
+            self.to.format("%Y-%m-%d"),
+            self.minimum
+        )
+    }
+
+    /// The dates `report` covers, for showing
+    fn period(&self, report: Option<Report>) -> String {
+        let (from, to) = match report {
+            Some(Report::BelowMinimum) => self.year(),
+            _ => (self.from, self.to),
+        };
+        format!(
+            "{} – {}",
+            from.format("%b %-d, %Y"),
+            to.format("%b %-d, %Y")
+        )
+    }
+
+    /// The minimum is yearly, so it's checked over the calendar year the
+    /// period ends in
+    fn year(&self) -> (NaiveDate, NaiveDate) {
+        let year = self.to.year();
+        (
+            NaiveDate::from_ymd_opt(year, 1, 1).expect("January 1st to exist"),
+            NaiveDate::from_ymd_opt(year, 12, 31).expect("December 31st to exist"),
+        )
+    }
+}
+
+/// A report as rows of text, so it can be shown, printed or exported the same
+/// way. Every column after the first is a number.
+struct Table {
+    title: String,
+    columns: Vec<String>,
+    rows: Vec<Vec<String>>,
+    totals: Option<Vec<String>>,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn reports_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Query(filter): Query<ReportFilter>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ViewMembers)?;
+
+    let options = Options::new(&filter, state.config.today());
+    let mut tables = Vec::new();
+    for report in Report::ALL {
+        tables.push((report, table(&state, report, options).await?));
+    }
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Reports</h1>
+            {FilterForm(options)}
+            <p>"Approved hours from " {options.period(None)}</p>
+            {tables.iter().map(|(report, table)| ReportSection(*report, table, options)).render_all()}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn report_csv(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(report): Path<String>,
+    Query(filter): Query<ReportFilter>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ViewMembers)?;
+
+    let report = Report::from_slug(&report).ok_or(Error::NotFound)?;
+    let options = Options::new(&filter, state.config.today());
+    let table = table(&state, report, options).await?;
+
+    let csv = std::iter::once(&table.columns)
+        .chain(&table.rows)
+        .chain(&table.totals)
+        .map(|row| {
+            row.iter()
+                .map(|field| csv_field(field))
+                .collect::<Vec<_>>()
+                .join(",")
+        })
+        .collect::<Vec<_>>()
+        .join("\r\n");
+    let file_name = format!(
+        "attachment; filename=\"{}-{}-{}.csv\"",
+        report.slug(),
+        options.from.format("%Y%m%d"),
+        options.to.format("%Y%m%d")
+    );
+
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                String::from("text/csv; charset=utf-8"),
+            ),
+            (header::CONTENT_DISPOSITION, file_name),
+        ],
+        csv + "\r\n",
+    ))
+}
+
+/// The report on its own, without the site's navigation, for printing
+#[tracing::instrument(skip(ctx, state))]
+pub async fn report_print(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(report): Path<String>,
+    Query(filter): Query<ReportFilter>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ViewMembers)?;
+
+    let report = Report::from_slug(&report).ok_or(Error::NotFound)?;
+    let options = Options::new(&filter, state.config.today());
+    let table = table(&state, report, options).await?;
+
+    Ok(rsx! {
+        {Raw("<!DOCTYPE html>")}
+        <html>
+            <head>
+                <meta charset="UTF-8">
+                <title>{&table.title}</title>
+                <style>{Raw("
+                    body { font-family: sans-serif; margin: 2rem; color: #252525; }
+                    table { border-collapse: collapse; width: 100%; font-size: 0.875rem; }
+                    th, td { border-bottom: 1px solid #a3a3a3; padding: 0.25rem 0.5rem; text-align: left; }
+                    th:not(:first-child), td:not(:first-child) { text-align: right; }
+                    tfoot { font-weight: bold; }
+                ")}</style>
+            </head>
+            <body>
+                <h1>"Division 176 · " {&table.title}</h1>
+                <p>{options.period(Some(report))}</p>
+                {ReportTable(&table)}
+            </body>
+        </html>
+    }
+    .render())
+}
+
+async fn table(state: &AppState, report: Report, options: Options) -> Result<Table, Error> {
+    let Options {
+        from, to, minimum, ..
+    } = options;
+    let hours = |hours: f64| format!("{hours:.2}");
+
+    let table = match report {
+        Report::Members => {
+            let totals = state.db.reports.member_totals(from, to).await?;
+
+            let mut types: Vec<(i32, &str)> = totals
+                .iter()
+                .map(|total| (total.etid, total.type_name.as_str()))
+                .collect();
+            types.sort();
+            types.dedup();
+
+            let mut members: Vec<(i32, &str, Vec<f64>)> = Vec::new();
+            for total in &totals {
+                let column = types
+                    .iter()
+                    .position(|(etid, _)| *etid == total.etid)
+                    .expect("every type to have a column");
+                match members.last_mut() {
+                    Some((uid, _, row)) if *uid == total.uid => row[column] += total.hours,
+                    _ => {
+                        let mut row = vec![0.0; types.len()];
+                        row[column] = total.hours;
+                        members.push((total.uid, &total.display_name, row));
+                    }
+                }
+            }
+
+            let column_totals = (0..types.len())
+                .map(|column| members.iter().map(|(.., row)| row[column]).sum::<f64>())
+                .collect::<Vec<_>>();
+
+            Table {
+                title: String::from("Hours by Member and Type"),
+                columns: std::iter::once("Member")
+                    .chain(types.iter().map(|(_, name)| *name))
+                    .chain(["Total"])
+                    .map(str::to_owned)
+                    .collect(),
+                rows: members
+                    .iter()
+                    .map(|(_, name, row)| {
+                        std::iter::once(name.to_string())
+                            .chain(row.iter().map(|value| hours(*value)))
+                            .chain([hours(row.iter().sum())])
+                            .collect()
+                    })
+                    .collect(),
+                totals: (!members.is_empty()).then(|| {
+                    std::iter::once(String::from("Total"))
+                        .chain(column_totals.iter().map(|value| hours(*value)))
+                        .chain([hours(column_totals.iter().sum())])
+                        .collect()
+                }),
+            }
+        }
+        Report::Monthly => {
+            let months = state.db.reports.monthly_totals(from, to).await?;
+
+            Table {
+                title: String::from("Division Hours by Month"),
+                columns: ["Month", "Hours", "Volunteers", "Entries"]
+                    .map(str::to_owned)
+                    .to_vec(),
+                rows: months
+                    .iter()
+                    .map(|month| {
+                        vec![
+                            month.month.format("%B %Y").to_string(),
+                            hours(month.hours),
+                            month.volunteers.to_string(),
+                            month.entries.to_string(),
+                        ]
+                    })
+                    .collect(),
+                totals: (!months.is_empty()).then(|| {
+                    vec![
+                        String::from("Total"),
+                        hours(months.iter().map(|month| month.hours).sum()),
+                        String::new(),
+                        months
+                            .iter()
+                            .map(|month| month.entries)
+                            .sum::<i64>()
+                            .to_string(),
+                    ]
+                }),
+            }
+        }
+        Report::TopContributors => {
+            let members = state
+                .db
+                .reports
+                .top_contributors(from, to, TOP_CONTRIBUTORS)
+                .await?;
+
+            Table {
+                title: String::from("Top Contributors"),
+                columns: ["Member", "Hours", "Entries"].map(str::to_owned).to_vec(),
+                rows: members
+                    .iter()
+                    .map(|member| {
+                        vec![
+                            member.display_name.clone(),
+                            hours(member.hours),
+                            member.entries.to_string(),
+                        ]
+                    })
+                    .collect(),
+                totals: None,
+            }
+        }
+        Report::BelowMinimum => {
+            let (year_start, year_end) = options.year();
+            let members = state
+                .db
+                .reports
+                .below_minimum(year_start, year_end, minimum)
+                .await?;
+
+            Table {
+                title: format!("Below {minimum} Hours in {}", year_start.year()),
+                columns: ["Member", "Hours", "Short by"].map(str::to_owned).to_vec(),
+                rows: members
+                    .iter()
+                    .map(|member| {
+                        vec![
+                            member.display_name.clone(),
+                            hours(member.hours),
+                            hours(minimum - member.hours),
+                        ]
+                    })
+                    .collect(),
+                totals: None,
+            }
+        }
+    };
+
+    Ok(table)
+}
+
+/// The quarter of the year `date` falls in
+fn quarter(date: NaiveDate) -> (NaiveDate, NaiveDate) {
+    let first_month = (date.month0() / 3) * 3 + 1;
+    let start =
+        NaiveDate::from_ymd_opt(date.year(), first_month, 1).expect("quarters to start on the 1st");
+    let end = start + Months::new(3) - chrono::Days::new(1);
+
+    (start, end)
+}
+
+/// Quotes fields where needed, and keeps spreadsheets from running names which
+/// look like formulas
+fn csv_field(field: &str) -> Cow<'_, str> {
+    let field: Cow<str> = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        Cow::Owned(format!("'{field}"))
+    } else {
+        Cow::Borrowed(field)
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        field
+    }
+}
+
+fn FilterForm(options: Options) -> impl Renderable {
+    let input = "px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let today = options.today;
+    let (this_quarter, _) = quarter(today);
+    let (last_quarter, last_quarter_end) = quarter(this_quarter - chrono::Days::new(1));
+    let this_year = NaiveDate::from_ymd_opt(today.year(), 1, 1).expect("January 1st to exist");
+    let periods = [
+        ("This quarter", quarter(today)),
+        ("Last quarter", (last_quarter, last_quarter_end)),
+        (
+            "This year",
+            (
+                this_year,
+                this_year + Months::new(12) - chrono::Days::new(1),
+            ),
+        ),
+        (
+            "Last year",
+            (
+                this_year - Months::new(12),
+                this_year - chrono::Days::new(1),
+            ),
+        ),
+    ];
+
+    rsx_move! {
+        <div class="space-y-2">
+            <form method="get" action="/reports" class="flex flex-wrap items-end gap-2">
+                <label class="block">
+                    <span class="text-sm font-medium">From</span>
+                    <input type="date" name="from" value=options.from.format("%Y-%m-%d").to_string() class=input required>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">To</span>
+                    <input type="date" name="to" value=options.to.format("%Y-%m-%d").to_string() class=input required>
+                </label>
+                <label class="block">
+                    <span class="text-sm font-medium">Yearly minimum (hours)</span>
+                    <input type="number" name="minimum" value=options.minimum.to_string() min="0" step="0.25" class=input>
+                </label>
+                <input class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Show">
+            </form>
+            <p class="flex flex-wrap gap-3 text-sm">
+                {periods.into_iter().map(|(label, (from, to))| {
+                    let options = Options { from, to, ..options };
+                    rsx_move! {
+                        <a class="text-green font-medium" href=format!("/reports?{}", options.query())>{label}</a>
+                    }
+                }).render_all()}
+            </p>
+        </div>
+    }
+}
+
+fn ReportSection<'a>(report: Report, table: &'a Table, options: Options) -> impl Renderable + 'a {
+    let path = format!("/reports/{}", report.slug());
+    let query = options.query();
+
+    rsx_move! {
+        <section>
+            <div class="flex items-center justify-between gap-2 p-2 bg-green-light rounded-t-lg">
+                <h2 class="text-white font-medium">{&table.title}</h2>
+                <div class="flex gap-3 text-sm">
+                    <a class="text-white underline" href=format!("{path}/csv?{query}")>CSV</a>
+                    <a class="text-white underline" href=format!("{path}/print?{query}") target="_blank">Print</a>
+                </div>
+            </div>
+            <div class="p-4 rounded-b-lg border border-green-light overflow-x-auto">
+                {ReportTable(table)}
+                {table.rows.is_empty().then(|| rsx! {
+                    <p class="text-neutral mt-2">No hours in this period</p>
+                })}
+            </div>
+        </section>
+    }
+}
+
+fn ReportTable(table: &Table) -> impl Renderable + '_ {
+    let class = |column: usize| {
+        if column == 0 {
+            "py-1 pr-2"
+        } else {
+            "py-1 pr-2 text-right"
+        }
+    };
+
+    rsx_move! {
+        <table class="w-full text-left text-sm">
+            <thead class="border-b border-neutral">
+                <tr>
+                    {table.columns.iter().enumerate().map(|(i, column)| rsx_move! {
+                        <th class=class(i)>{column}</th>
+                    }).render_all()}
+                </tr>
+            </thead>
+            <tbody class="divide-y divide-neutral">
+                {table.rows.iter().map(|row| rsx_move! {
+                    <tr>
+                        {row.iter().enumerate().map(|(i, field)| rsx_move! {
+                            <td class=class(i)>{field}</td>
+                        }).render_all()}
+                    </tr>
+                }).render_all()}
+            </tbody>
+            {table.totals.as_ref().map(|totals| rsx_move! {
+                <tfoot class="border-t border-neutral font-medium">
+                    <tr>
+                        {totals.iter().enumerate().map(|(i, field)| rsx_move! {
+                            <td class=class(i)>{field}</td>
+                        }).render_all()}
+                    </tr>
+                </tfoot>
+            })}
+        </table>
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quarters() {
+        let date = |s: &str| s.parse::<NaiveDate>().unwrap();
+
+        assert_eq!(
+            quarter(date("2026-02-14")),
+            (date("2026-01-01"), date("2026-03-31"))
+        );
+        assert_eq!(
+            quarter(date("2026-12-31")),
+            (date("2026-10-01"), date("2026-12-31"))
+        );
+    }
+
+    #[test]
+    fn csv_fields_are_quoted_and_defused() {
+        assert_eq!(csv_field("Smith, Jane"), "\"Smith, Jane\"");
+        assert_eq!(csv_field("Say \"hi\""), "\"Say \"\"hi\"\"\"");
+        assert_eq!(csv_field("=SUM(A1)"), "'=SUM(A1)");
+        assert_eq!(csv_field("\t=SUM(A1)"), "'\t=SUM(A1)");
+        assert_eq!(csv_field("\r=SUM(A1)"), "\"'\r=SUM(A1)\"");
+        assert_eq!(csv_field("12.50"), "12.50");
+    }
+}
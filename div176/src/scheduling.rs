@@ -0,0 +1,552 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use chrono::{Days, NaiveDate, NaiveDateTime};
+use components::{Layout, SelectOption};
+use db::{DutyLeadCandidate, Event, EventType, NewEvent, NewShift, Shift};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{
+    AppState,
+    auth::AuthUser,
+    events::TimeRange,
+    forms::{HxRequest, datetime_local},
+};
+
+/// Most shifts one recurring add can create, a year of weekly meetings
+const MAX_OCCURRENCES: u32 = 52;
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn new_event_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let event_types = state.db.events.list_event_types().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">New Event</h1>
+            <p class="text-sm text-neutral">"Shifts are added once the event is created."</p>
+            <div class="p-4 rounded-lg border border-green-light">
+                {EventForm(None, &event_types)}
+            </div>
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct EventModel {
+    etid: String,
+    event_name: String,
+    organizer_details: String,
+    location: String,
+    notes: String,
+}
+
+impl EventModel {
+    pub(crate) fn validate(self) -> Result<NewEvent, Error> {
+        let etid = self
+            .etid
+            .parse()
+            .map_err(|_| Error::unprocessable_entity([("etid", "Choose an event type")]))?;
+        let event_name = self.event_name.trim();
+        if event_name.is_empty() {
+            return Err(Error::unprocessable_entity([(
+                "event_name",
+                "Name is required",
+            )]));
+        }
+
+        Ok(NewEvent {
+            etid,
+            event_name: event_name.to_owned(),
+            organizer_details: optional(&self.organizer_details),
+            location: optional(&self.location),
+            notes: optional(&self.notes),
+        })
+    }
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn create_event(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Form(body): Form<EventModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let eid = state.db.events.create_event(&body.validate()?).await?;
+        Ok(format!("/events/{eid}/edit"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn edit_event_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let event = state
+        .db
+        .events
+        .get_event(eid)
+        .await?
+        .ok_or(Error::NotFound)?;
+    let event_types = state.db.events.list_event_types().await?;
+    let shifts = state.db.events.list_shifts(eid).await?;
+    let candidates = state
+        .db
+        .events
+        .list_duty_lead_candidates(event.etid)
+        .await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <div class="flex items-center justify-between gap-2">
+                <h1 class="text-2xl font-medium">"Edit " {&event.event_name}</h1>
+                <a class="text-green font-medium" href=format!("/events/{eid}")>View event</a>
+            </div>
+            {event.esid.map(|esid| rsx_move! {
+                <p class="p-2 rounded-lg border border-green-light text-sm">
+                    "This event is part of a "
+                    <a class="text-green font-medium" href=format!("/series/{esid}")>recurring series</a>
+                    ". Changes here apply to this date only."
+                </p>
+            })}
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Details</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {EventForm(Some(&event), &event_types)}
+                </div>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Shifts</h2>
+                <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                    {shifts.iter().map(|shift| ShiftRow(shift, &candidates)).render_all()}
+                    {shifts.is_empty().then(|| rsx! {
+                        <li class="py-2 text-sm text-neutral">No shifts have been scheduled</li>
+                    })}
+                </ul>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Add Shifts</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {AddShiftsForm(eid, &candidates)}
+                </div>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Duplicate</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {DuplicateForm(eid)}
+                </div>
+            </section>
+            <form method="post" action=format!("/events/{eid}/cancel") hx-post=format!("/events/{eid}/cancel") hx-confirm="Cancel this event?" data-errors>
+                <input class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white cursor-pointer" type="submit" value="Cancel event">
+                <p class="text-red text-sm" data-error-for="eid"></p>
+            </form>
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn update_event(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+    Form(body): Form<EventModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        state.db.events.update_event(eid, &body.validate()?).await?;
+        Ok(format!("/events/{eid}/edit"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ShiftModel {
+    start_time: String,
+    end_time: String,
+    duty_lead: String,
+}
+
+impl ShiftModel {
+    fn validate(&self) -> Result<NewShift, Error> {
+        let start_time = datetime_local(&self.start_time).ok_or_else(|| {
+            Error::unprocessable_entity([("start_time", "Enter when the shift starts")])
+        })?;
+        let end_time = datetime_local(&self.end_time).ok_or_else(|| {
+            Error::unprocessable_entity([("end_time", "Enter when the shift ends")])
+        })?;
+        let duty_lead = match self.duty_lead.as_str() {
+            "" => None,
+            uid => Some(
+                uid.parse()
+                    .map_err(|_| Error::unprocessable_entity([("duty_lead", "Unknown member")]))?,
+            ),
+        };
+
+        Ok(NewShift {
+            start_time,
+            end_time,
+            duty_lead,
+        })
+    }
+}
+
+#[derive(Deserialize, Debug)]
+pub struct AddShiftsModel {
+    #[serde(flatten)]
+    shift: ShiftModel,
+    repeat_weeks: String,
+    occurrences: String,
+}
+
+/// Adds a shift, or a shift repeated every few weeks such as a weekly meeting
+/// night
+#[tracing::instrument(skip(ctx, state))]
+pub async fn add_shifts(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+    Form(body): Form<AddShiftsModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let first = body.shift.validate()?;
+        let repeat_weeks: u64 = match body.repeat_weeks.as_str() {
+            "" => 0,
+            weeks => weeks
+                .parse()
+                .ok()
+                .filter(|weeks| *weeks <= 4)
+                .ok_or_else(|| {
+                    Error::unprocessable_entity([("repeat_weeks", "Choose how often it repeats")])
+                })?,
+        };
+        let occurrences = if repeat_weeks == 0 {
+            1
+        } else {
+            body.occurrences
+                .trim()
+                .parse()
+                .ok()
+                .filter(|count| (1..=MAX_OCCURRENCES).contains(count))
+                .ok_or_else(|| {
+                    Error::unprocessable_entity([(
+                        "occurrences",
+                        format!("Enter a number of shifts from 1 to {MAX_OCCURRENCES}"),
+                    )])
+                })?
+        };
+
+        let shifts: Vec<NewShift> = (0..u64::from(occurrences))
+            .map(|i| {
+                let offset = Days::new(i * repeat_weeks * 7);
+                NewShift {
+                    start_time: first.start_time + offset,
+                    end_time: first.end_time + offset,
+                    duty_lead: first.duty_lead,
+                }
+            })
+            .collect();
+
+        state.db.events.add_shifts(eid, &shifts).await?;
+
+        Ok(format!("/events/{eid}/edit"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn update_shift(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(sid): Path<i32>,
+    Form(body): Form<ShiftModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        state.db.events.update_shift(sid, &body.validate()?).await?;
+        edit_path(&state, sid).await
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn delete_shift(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(sid): Path<i32>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let path = edit_path(&state, sid).await?;
+        state.db.events.delete_shift(sid).await?;
+        Ok(path)
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct DuplicateModel {
+    date: String,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn duplicate_event(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+    Form(body): Form<DuplicateModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let date = NaiveDate::parse_from_str(body.date.trim(), "%Y-%m-%d")
+            .map_err(|_| Error::unprocessable_entity([("date", "Enter a date")]))?;
+        let eid = state.db.events.duplicate_event(eid, date).await?;
+        Ok(format!("/events/{eid}/edit"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+/// Cancelling an occurrence of a series also skips its date, so the series
+/// doesn't recreate it
+#[tracing::instrument(skip(ctx, state))]
+pub async fn cancel_event(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(eid): Path<i32>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        state.db.events.cancel(eid).await?;
+        Ok(String::from("/events"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+async fn edit_path(state: &AppState, sid: i32) -> Result<String, Error> {
+    let shift = state
+        .db
+        .events
+        .get_shift(sid)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    Ok(format!("/events/{}/edit", shift.eid))
+}
+
+pub(crate) fn optional(value: &str) -> Option<String> {
+    let value = value.trim();
+
+    (!value.is_empty()).then(|| value.to_owned())
+}
+
+fn EventForm<'a>(event: Option<&'a Event>, event_types: &'a [EventType]) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = match event {
+        Some(event) => format!("/events/{}", event.eid),
+        None => String::from("/events"),
+    };
+    let etid = event.map(|event| event.etid);
+    let field = move |value: fn(&Event) -> Option<&String>| {
+        event.and_then(value).map_or("", String::as_str)
+    };
+
+    rsx_move! {
+        <form method="post" action=&action hx-post=&action class="grid desktop:grid-cols-2 gap-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">Name</span>
+                <input type="text" name="event_name" value=event.map_or("", |event| event.event_name.as_str()) class=input required>
+                <p class="text-red text-sm" data-error-for="event_name"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Type</span>
+                <select name="etid" class=input required>
+                    {event_types.iter().map(|event_type| SelectOption(event_type.etid.to_string(), &event_type.name, Some(event_type.etid) == etid)).render_all()}
+                </select>
+                <p class="text-red text-sm" data-error-for="etid"></p>
+                <p class="text-red text-sm" data-error-for="duty_lead"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Location</span>
+                <input type="text" name="location" value=field(|event| event.location.as_ref()) class=input>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Organizer</span>
+                <textarea name="organizer_details" rows="2" placeholder="Name and contact details" class=input>{field(|event| event.organizer_details.as_ref())}</textarea>
+            </label>
+            <label class="block desktop:col-span-2">
+                <span class="text-sm font-medium">Notes</span>
+                <textarea name="notes" rows="3" class=input>{field(|event| event.notes.as_ref())}</textarea>
+            </label>
+            <div class="desktop:col-span-2">
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value=if event.is_some() { "Save" } else { "Create" }>
+            </div>
+        </form>
+    }
+}
+
+fn ShiftRow<'a>(shift: &'a Shift, candidates: &'a [DutyLeadCandidate]) -> impl Renderable + 'a {
+    let action = format!("/shifts/{}", shift.sid);
+    let delete = format!("/shifts/{}/delete", shift.sid);
+
+    rsx_move! {
+        <li class="py-2 space-y-2" data-errors>
+            <div class="flex items-center justify-between gap-2">
+                <div>
+                    <p class="font-medium">{shift.start_time.format("%a, %b %-d").to_string()}</p>
+                    <p class="text-sm">{TimeRange(shift.start_time, shift.end_time)}</p>
+                    <p class="text-sm text-neutral">"Duty lead: " {shift.duty_lead_name.as_deref().unwrap_or("none")}</p>
+                </div>
+                <form method="post" action=&delete hx-post=&delete hx-confirm="Delete this shift?">
+                    <input class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white cursor-pointer" type="submit" value="Delete">
+                </form>
+            </div>
+            <p class="text-red text-sm" data-error-for="sid"></p>
+            <details>
+                <summary class="text-green font-medium cursor-pointer">Edit</summary>
+                <form method="post" action=&action hx-post=&action class="pt-2 grid desktop:grid-cols-3 gap-3">
+                    {ShiftFields(Some(shift), candidates)}
+                    <div class="desktop:col-span-3">
+                        <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Save">
+                    </div>
+                </form>
+            </details>
+        </li>
+    }
+}
+
+fn AddShiftsForm(eid: i32, candidates: &[DutyLeadCandidate]) -> impl Renderable + '_ {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = format!("/events/{eid}/shifts");
+
+    rsx_move! {
+        <form method="post" action=&action hx-post=&action class="grid desktop:grid-cols-3 gap-3" data-errors>
+            {ShiftFields(None, candidates)}
+            <label class="block">
+                <span class="text-sm font-medium">Repeats</span>
+                <select name="repeat_weeks" class=input>
+                    <option value="">"Doesn't repeat"</option>
+                    <option value="1">Every week</option>
+                    <option value="2">Every 2 weeks</option>
+                    <option value="4">Every 4 weeks</option>
+                </select>
+                <p class="text-red text-sm" data-error-for="repeat_weeks"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Number of shifts if repeating</span>
+                <input type="number" name="occurrences" min="1" max=MAX_OCCURRENCES value="1" class=input>
+                <p class="text-red text-sm" data-error-for="occurrences"></p>
+            </label>
+            <div class="desktop:col-span-3">
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Add">
+            </div>
+        </form>
+    }
+}
+
+/// Inputs for a shift's times and duty lead, with their errors
+fn ShiftFields<'a>(
+    shift: Option<&'a Shift>,
+    candidates: &'a [DutyLeadCandidate],
+) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let value = |time: NaiveDateTime| time.format("%Y-%m-%dT%H:%M").to_string();
+    let start_time = shift
+        .map(|shift| value(shift.start_time))
+        .unwrap_or_default();
+    let end_time = shift.map(|shift| value(shift.end_time)).unwrap_or_default();
+    let duty_lead = shift.and_then(|shift| shift.duty_lead);
+    // A lead whose qualification has since lapsed stays selected until changed
+    let lapsed = shift.filter(|shift| {
+        shift
+            .duty_lead
+            .is_some_and(|uid| !candidates.iter().any(|c| c.uid == uid))
+    });
+
+    rsx_move! {
+        <label class="block">
+            <span class="text-sm font-medium">Starts</span>
+            <input type="datetime-local" name="start_time" value=start_time class=input required>
+            <p class="text-red text-sm" data-error-for="start_time"></p>
+        </label>
+        <label class="block">
+            <span class="text-sm font-medium">Ends</span>
+            <input type="datetime-local" name="end_time" value=end_time class=input required>
+            <p class="text-red text-sm" data-error-for="end_time"></p>
+        </label>
+        <label class="block">
+            <span class="text-sm font-medium">Duty lead</span>
+            <select name="duty_lead" class=input>
+                {SelectOption(String::new(), "None", duty_lead.is_none())}
+                {lapsed.map(|shift| SelectOption(
+                    shift.duty_lead.unwrap_or_default().to_string(),
+                    shift.duty_lead_name.as_deref().unwrap_or_default(),
+                    true,
+                ))}
+                {candidates.iter().map(|candidate| SelectOption(candidate.uid.to_string(), &candidate.display_name, Some(candidate.uid) == duty_lead)).render_all()}
+            </select>
+            <p class="text-red text-sm" data-error-for="duty_lead"></p>
+        </label>
+    }
+}
+
+fn DuplicateForm(eid: i32) -> impl Renderable {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = format!("/events/{eid}/duplicate");
+
+    rsx_move! {
+        <form method="post" action=&action hx-post=&action class="flex flex-wrap items-end gap-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">First day of the copy</span>
+                <input type="date" name="date" class=input required>
+                <p class="text-red text-sm" data-error-for="date"></p>
+            </label>
+            <p class="text-sm text-neutral grow">"Shifts keep their times and spacing. Duty leads aren't copied."</p>
+            <input class="bg-white-true text-green border border-green px-2 py-0.5 shadow-sm rounded hover:bg-green hover:text-white cursor-pointer" type="submit" value="Duplicate">
+        </form>
+    }
+}
@@ -0,0 +1,520 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, State},
+    response::{IntoResponse, Response},
+};
+use chrono::{Datelike, NaiveDate, NaiveTime};
+use components::{Layout, SelectOption};
+use db::{DB, DutyLeadCandidate, EventSeries, EventType, Frequency, NewSeries, Occurrence};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{AppState, auth::AuthUser, forms::HxRequest, recurrence, scheduling::EventModel};
+
+const DEFAULT_HORIZON_DAYS: i32 = 90;
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn series_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let series = state.db.series.list().await?;
+    let event_types = state.db.events.list_event_types().await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Recurring Events</h1>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Series</h2>
+                <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                    {series.iter().map(|series| rsx_move! {
+                        <li class="py-2">
+                            <a class="font-medium text-green" href=format!("/series/{}", series.esid)>{&series.event_name}</a>
+                            {series.archived.then(|| rsx! { <span class="text-sm text-neutral">" (ended)"</span> })}
+                            <p class="text-sm text-neutral">{&series.type_name} " · " {describe(series)}</p>
+                        </li>
+                    }).render_all()}
+                    {series.is_empty().then(|| rsx! {
+                        <li class="py-2 text-sm text-neutral">No recurring events</li>
+                    })}
+                </ul>
+            </section>
+            <section>
+                <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">New Series</h2>
+                <div class="p-4 rounded-b-lg border border-green-light">
+                    {SeriesForm(None, &event_types, &[])}
+                </div>
+            </section>
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct SeriesModel {
+    #[serde(flatten)]
+    event: EventModel,
+    frequency: String,
+    starts_on: String,
+    ends_on: String,
+    start_time: String,
+    end_time: String,
+    #[serde(default)]
+    duty_lead: String,
+    horizon_days: String,
+}
+
+impl SeriesModel {
+    fn validate(self) -> Result<NewSeries, Error> {
+        let frequency = match self.frequency.as_str() {
+            "weekly" => Frequency::Weekly,
+            "biweekly" => Frequency::Biweekly,
+            "monthly_nth_weekday" => Frequency::MonthlyNthWeekday,
+            _ => {
+                return Err(Error::unprocessable_entity([(
+                    "frequency",
+                    "Choose how often it repeats",
+                )]));
+            }
+        };
+        let starts_on = NaiveDate::parse_from_str(self.starts_on.trim(), "%Y-%m-%d")
+            .map_err(|_| Error::unprocessable_entity([("starts_on", "Enter the first date")]))?;
+        let ends_on = match self.ends_on.trim() {
+            "" => None,
+            date => Some(
+                NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                    .ok()
+                    .filter(|date| *date >= starts_on)
+                    .ok_or_else(|| {
+                        Error::unprocessable_entity([(
+                            "ends_on",
+                            "Enter a date on or after the first date, or leave it empty",
+                        )])
+                    })?,
+            ),
+        };
+        let time = |value: &str, field: &'static str| {
+            NaiveTime::parse_from_str(value.trim(), "%H:%M")
+                .map_err(|_| Error::unprocessable_entity([(field, "Enter a time")]))
+        };
+        let start_time = time(&self.start_time, "start_time")?;
+        let end_time = time(&self.end_time, "end_time")?;
+        if start_time == end_time {
+            return Err(Error::unprocessable_entity([(
+                "end_time",
+                "The shift must end after it starts",
+            )]));
+        }
+        let duty_lead = match self.duty_lead.as_str() {
+            "" => None,
+            uid => Some(
+                uid.parse()
+                    .map_err(|_| Error::unprocessable_entity([("duty_lead", "Unknown member")]))?,
+            ),
+        };
+        let horizon_days = match self.horizon_days.trim() {
+            "" => DEFAULT_HORIZON_DAYS,
+            days => days
+                .parse()
+                .ok()
+                .filter(|days| (1..=365).contains(days))
+                .ok_or_else(|| {
+                    Error::unprocessable_entity([(
+                        "horizon_days",
+                        "Enter a number of days from 1 to 365",
+                    )])
+                })?,
+        };
+
+        Ok(NewSeries {
+            event: self.event.validate()?,
+            frequency,
+            starts_on,
+            ends_on,
+            start_time,
+            end_time,
+            duty_lead,
+            horizon_days,
+        })
+    }
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn create_series(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Form(body): Form<SeriesModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let esid = state.db.series.create(&body.validate()?).await?;
+        let series = state.db.series.get(esid).await?.ok_or(Error::NotFound)?;
+        materialise(&state.db, &series, state.config.today()).await?;
+
+        Ok(format!("/series/{esid}"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn series_detail(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(esid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let series = state.db.series.get(esid).await?.ok_or(Error::NotFound)?;
+    let event_types = state.db.events.list_event_types().await?;
+    let candidates = state
+        .db
+        .events
+        .list_duty_lead_candidates(series.etid)
+        .await?;
+    let occurrences = state.db.series.list_occurrences(esid).await?;
+    let exceptions = state.db.series.list_exceptions(esid).await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <div>
+                <h1 class="text-2xl font-medium">{&series.event_name}</h1>
+                <p class="text-sm text-neutral">
+                    {describe(&series)}
+                    {series.archived.then(|| rsx! { " · ended" })}
+                </p>
+            </div>
+            {(!series.archived).then(|| rsx! {
+                <section>
+                    <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Edit the Series</h2>
+                    <div class="p-4 rounded-b-lg border border-green-light space-y-2">
+                        <p class="text-sm text-neutral">"Changes apply to every upcoming occurrence except those edited on their own."</p>
+                        {SeriesForm(Some(&series), &event_types, &candidates)}
+                    </div>
+                </section>
+            })}
+            {OccurrencesSection(&occurrences)}
+            {ExceptionsSection(&series, &exceptions)}
+            {(!series.archived).then(|| rsx_move! {
+                <form method="post" action=format!("/series/{esid}/archive") hx-post=format!("/series/{esid}/archive") hx-confirm="End this series and cancel its upcoming occurrences?">
+                    <input class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white cursor-pointer" type="submit" value="End series">
+                </form>
+            })}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn update_series(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(esid): Path<i32>,
+    Form(body): Form<SeriesModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let series = body.validate()?;
+        let (dates, until) = recurrence::upcoming(
+            series.frequency,
+            series.starts_on,
+            series.ends_on,
+            series.horizon_days,
+            state.config.today(),
+        );
+        state.db.series.update(esid, &series, &dates, until).await?;
+
+        Ok(format!("/series/{esid}"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ExceptionModel {
+    date: String,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn add_exception(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(esid): Path<i32>,
+    Form(body): Form<ExceptionModel>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        let date = NaiveDate::parse_from_str(body.date.trim(), "%Y-%m-%d")
+            .map_err(|_| Error::unprocessable_entity([("date", "Enter a date")]))?;
+        state.db.series.add_exception(esid, date).await?;
+
+        Ok(format!("/series/{esid}"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn remove_exception(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path((esid, date)): Path<(i32, String)>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let date = NaiveDate::parse_from_str(&date, "%Y-%m-%d").map_err(|_| Error::NotFound)?;
+    let result = async {
+        state.db.series.remove_exception(esid, date).await?;
+        let series = state.db.series.get(esid).await?.ok_or(Error::NotFound)?;
+        materialise(&state.db, &series, state.config.today()).await?;
+
+        Ok(format!("/series/{esid}"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn archive_series(
+    AuthUser(ctx): AuthUser,
+    hx: HxRequest,
+    State(state): State<AppState>,
+    Path(esid): Path<i32>,
+) -> Result<Response, Error> {
+    ctx.require(Permission::ManageEvents)?;
+
+    let result = async {
+        state.db.series.archive(esid).await?;
+        Ok(format!("/series/{esid}"))
+    }
+    .await;
+
+    Ok(hx.finish(result))
+}
+
+/// Creates the series' missing occurrences through its horizon
+pub async fn materialise(db: &DB, series: &EventSeries, today: NaiveDate) -> Result<u64, Error> {
+    let (dates, until) = recurrence::upcoming(
+        series.frequency,
+        series.starts_on,
+        series.ends_on,
+        series.horizon_days,
+        today,
+    );
+
+    db.series.materialise(series.esid, &dates, until).await
+}
+
+fn describe(series: &EventSeries) -> String {
+    let weekday = series.starts_on.format("%A");
+    let schedule = match series.frequency {
+        Frequency::Weekly => format!("Every {weekday}"),
+        Frequency::Biweekly => format!("Every other {weekday}"),
+        Frequency::MonthlyNthWeekday => {
+            let nth = ["First", "Second", "Third", "Fourth", "Last"]
+                [series.starts_on.day0() as usize / 7];
+            format!("{nth} {weekday} of each month")
+        }
+    };
+    let ends = series
+        .ends_on
+        .map(|date| format!(" until {}", date.format("%b %-d, %Y")))
+        .unwrap_or_default();
+
+    format!(
+        "{schedule}, {}–{}, from {}{ends}",
+        series.start_time.format("%H:%M"),
+        series.end_time.format("%H:%M"),
+        series.starts_on.format("%b %-d, %Y"),
+    )
+}
+
+fn SeriesForm<'a>(
+    series: Option<&'a EventSeries>,
+    event_types: &'a [EventType],
+    candidates: &'a [DutyLeadCandidate],
+) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = match series {
+        Some(series) => format!("/series/{}", series.esid),
+        None => String::from("/series"),
+    };
+    let etid = series.map(|series| series.etid);
+    let text = move |value: fn(&EventSeries) -> Option<&String>| {
+        series.and_then(value).map_or("", String::as_str)
+    };
+    let frequency = series.map(|series| series.frequency);
+    let date = |date: Option<NaiveDate>| {
+        date.map(|date| date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default()
+    };
+    let time = |time: Option<NaiveTime>| {
+        time.map(|time| time.format("%H:%M").to_string())
+            .unwrap_or_default()
+    };
+    let starts_on = date(series.map(|series| series.starts_on));
+    let ends_on = date(series.and_then(|series| series.ends_on));
+    let start_time = time(series.map(|series| series.start_time));
+    let end_time = time(series.map(|series| series.end_time));
+    let horizon_days = series
+        .map_or(DEFAULT_HORIZON_DAYS, |series| series.horizon_days)
+        .to_string();
+    let duty_lead = series.and_then(|series| series.duty_lead);
+
+    rsx_move! {
+        <form method="post" action=&action hx-post=&action class="grid desktop:grid-cols-2 gap-3" data-errors>
+            <label class="block">
+                <span class="text-sm font-medium">Name</span>
+                <input type="text" name="event_name" value=series.map_or("", |series| series.event_name.as_str()) class=input required>
+                <p class="text-red text-sm" data-error-for="event_name"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Type</span>
+                <select name="etid" class=input required>
+                    {event_types.iter().map(|event_type| SelectOption(event_type.etid.to_string(), &event_type.name, Some(event_type.etid) == etid)).render_all()}
+                </select>
+                <p class="text-red text-sm" data-error-for="etid"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Repeats</span>
+                <select name="frequency" class=input>
+                    {SelectOption(String::from("weekly"), "Every week", frequency == Some(Frequency::Weekly))}
+                    {SelectOption(String::from("biweekly"), "Every 2 weeks", frequency == Some(Frequency::Biweekly))}
+                    {SelectOption(String::from("monthly_nth_weekday"), "Monthly on the same week and weekday", frequency == Some(Frequency::MonthlyNthWeekday))}
+                </select>
+                <p class="text-red text-sm" data-error-for="frequency"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Create occurrences this many days ahead</span>
+                <input type="number" name="horizon_days" min="1" max="365" value=horizon_days class=input>
+                <p class="text-red text-sm" data-error-for="horizon_days"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">First date</span>
+                <input type="date" name="starts_on" value=starts_on class=input required>
+                <p class="text-red text-sm" data-error-for="starts_on"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Last date</span>
+                <input type="date" name="ends_on" value=ends_on class=input>
+                <p class="text-red text-sm" data-error-for="ends_on"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Starts</span>
+                <input type="time" name="start_time" value=start_time class=input required>
+                <p class="text-red text-sm" data-error-for="start_time"></p>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Ends</span>
+                <input type="time" name="end_time" value=end_time class=input required>
+                <p class="text-red text-sm" data-error-for="end_time"></p>
+            </label>
+            {series.map(|_| rsx_move! {
+                <label class="block">
+                    <span class="text-sm font-medium">Duty lead</span>
+                    <select name="duty_lead" class=input>
+                        {SelectOption(String::new(), "None", duty_lead.is_none())}
+                        {candidates.iter().map(|candidate| SelectOption(candidate.uid.to_string(), &candidate.display_name, Some(candidate.uid) == duty_lead)).render_all()}
+                    </select>
+                    <p class="text-red text-sm" data-error-for="duty_lead"></p>
+                </label>
+            })}
+            <label class="block">
+                <span class="text-sm font-medium">Location</span>
+                <input type="text" name="location" value=text(|series| series.location.as_ref()) class=input>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Organizer</span>
+                <textarea name="organizer_details" rows="2" class=input>{text(|series| series.organizer_details.as_ref())}</textarea>
+            </label>
+            <label class="block">
+                <span class="text-sm font-medium">Notes</span>
+                <textarea name="notes" rows="2" class=input>{text(|series| series.notes.as_ref())}</textarea>
+            </label>
+            <div class="desktop:col-span-2">
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value=if series.is_some() { "Save" } else { "Create" }>
+            </div>
+        </form>
+    }
+}
+
+fn OccurrencesSection(occurrences: &[Occurrence]) -> impl Renderable + '_ {
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Upcoming Occurrences</h2>
+            <ul class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                {occurrences.iter().map(|occurrence| rsx_move! {
+                    <li class="py-2 flex items-center justify-between gap-2">
+                        <a class="text-green font-medium" href=format!("/events/{}", occurrence.eid)>{occurrence.occurrence_date.format("%a, %b %-d, %Y").to_string()}</a>
+                        <span class="text-sm text-neutral">
+                            {occurrence.signups} {if occurrence.signups == 1 { " volunteer" } else { " volunteers" }}
+                            {occurrence.detached.then(|| rsx! { " · edited separately" })}
+                        </span>
+                    </li>
+                }).render_all()}
+                {occurrences.is_empty().then(|| rsx! {
+                    <li class="py-2 text-sm text-neutral">No upcoming occurrences</li>
+                })}
+            </ul>
+        </section>
+    }
+}
+
+fn ExceptionsSection<'a>(
+    series: &'a EventSeries,
+    exceptions: &'a [NaiveDate],
+) -> impl Renderable + 'a {
+    let input = "px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let action = format!("/series/{}/exceptions", series.esid);
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Skipped Dates</h2>
+            <div class="px-4 rounded-b-lg border border-green-light divide-y divide-neutral">
+                <ul class="divide-y divide-neutral">
+                    {exceptions.iter().map(|date| {
+                        let remove = format!("/series/{}/exceptions/{}/delete", series.esid, date.format("%Y-%m-%d"));
+                        rsx_move! {
+                            <li class="py-2 flex items-center justify-between gap-2">
+                                {date.format("%a, %b %-d, %Y").to_string()}
+                                {(!series.archived).then(|| rsx_move! {
+                                    <form method="post" action=&remove hx-post=&remove>
+                                        <input class="text-sm text-green cursor-pointer" type="submit" value="Restore">
+                                    </form>
+                                })}
+                            </li>
+                        }
+                    }).render_all()}
+                    {exceptions.is_empty().then(|| rsx! {
+                        <li class="py-2 text-sm text-neutral">No dates are skipped</li>
+                    })}
+                </ul>
+                {(!series.archived).then(|| rsx_move! {
+                    <form method="post" action=&action hx-post=&action class="py-2 flex flex-wrap items-start gap-2" data-errors>
+                        <div>
+                            <input type="date" name="date" class=input required>
+                            <p class="text-red text-sm" data-error-for="date"></p>
+                        </div>
+                        <input class="bg-white-true text-green border border-green px-2 py-0.5 shadow-sm rounded hover:bg-green hover:text-white cursor-pointer" type="submit" value="Skip date">
+                    </form>
+                })}
+            </div>
+        </section>
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::extract::{
+    CookieJar,
+    cookie::{Cookie, SameSite},
+};
+use db::Session;
+use hmac::{Hmac, Mac};
+use rand::{Rng, distr::Alphanumeric};
+use sha2::Sha256;
+use tracing::{Instrument, info_span};
+
+use crate::{AppState, client::Client};
+
+pub const AUTH_COOKIE: &str = "authorization";
+
+/// How long a session token is used before it is replaced
+const ROTATE_AFTER: Duration = Duration::from_secs(15 * 60);
+
+pub fn new_token() -> String {
+    rand::rng()
+        .sample_iter(&Alphanumeric)
+        .take(32)
+        .map(char::from)
+        .collect()
+}
+
+/// The keyed hash session, invitation and password reset tokens and recovery
+/// codes are stored as
+pub fn token_hash(state: &AppState, token: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&state.config.session_secret)
+        .expect("HMAC to accept any key length");
+    mac.update(token.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+pub fn session_cookie(state: &AppState, token: String) -> Cookie<'static> {
+    // No Max-Age, so closing the browser on a shared device ends the session
+    // even before it expires server side
+    Cookie::build((AUTH_COOKIE, token))
+        .path("/")
+        .http_only(true)
+        .secure(state.config.cookie_secure)
+        .same_site(SameSite::Lax)
+        .build()
+}
+
+/// Resolves the session cookie into a [`Session`] request extension for
+/// `AuthUser`, rotating the token when it is due
+pub async fn middleware(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: Client,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let Some(token) = jar.get(AUTH_COOKIE).map(|c| c.value_trimmed().to_string()) else {
+        return next.run(request).await;
+    };
+
+    let resolved = async {
+        let hash = token_hash(&state, &token);
+        let Some(session) = state
+            .db
+            .sessions
+            .get(
+                &hash,
+                state.config.session_idle_timeout,
+                ROTATE_AFTER,
+                client.user_agent.as_deref(),
+                client.ip.map(|ip| ip.to_string()).as_deref(),
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+
+        let rotated = if session.rotation_due {
+            let new_token = new_token();
+            let new_hash = token_hash(&state, &new_token);
+            state
+                .db
+                .sessions
+                .rotate(session.usid, &hash, &new_hash)
+                .await?
+                .then_some(new_token)
+        } else {
+            None
+        };
+
+        Ok::<_, types::Error>(Some((session, rotated)))
+    }
+    .instrument(info_span!("Session"))
+    .await;
+
+    match resolved {
+        Ok(Some((session, rotated))) => {
+            let uid = session.uid;
+            request.extensions_mut().insert::<Session>(session);
+            // Changes made while handling the request are audited as the user's
+            let response = db::acting_as(uid, next.run(request)).await;
+
+            match rotated {
+                Some(token) => (jar.add(session_cookie(&state, token)), response).into_response(),
+                None => response,
+            }
+        }
+        Ok(None) => next.run(request).await,
+        Err(e) => e.into_response(),
+    }
+}
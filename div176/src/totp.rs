@@ -0,0 +1,98 @@
+use qrcode::{QrCode, render::svg};
+use rand::{Rng, distr::Alphanumeric};
+use totp_rs::{Algorithm, TOTP};
+
+const ISSUER: &str = "Division 176";
+const STEP: u64 = 30;
+const RECOVERY_CODES: usize = 10;
+
+/// 160 bits, as recommended by RFC 4226
+pub fn new_secret() -> Vec<u8> {
+    rand::random::<[u8; 20]>().to_vec()
+}
+
+fn totp(secret: &[u8], username: &str) -> TOTP {
+    TOTP::new_unchecked(
+        Algorithm::SHA1,
+        6,
+        0,
+        STEP,
+        secret.to_vec(),
+        Some(ISSUER.to_owned()),
+        username.to_owned(),
+    )
+}
+
+/// The time step `code` is valid for at `now`, allowing a step of clock drift
+/// either way
+pub fn verify(secret: &[u8], code: &str, now: u64) -> Option<i64> {
+    let totp = totp(secret, "");
+    let step = now / STEP;
+
+    [step, step - 1, step + 1]
+        .into_iter()
+        .find(|step| totp.check(code, step * STEP))
+        .map(|step| step as i64)
+}
+
+/// The secret for typing into an authenticator app
+pub fn encoded_secret(secret: &[u8]) -> String {
+    totp(secret, "").get_secret_base32()
+}
+
+/// A QR code of the `otpauth://` URL for scanning into an authenticator app
+pub fn qr_code(secret: &[u8], username: &str) -> String {
+    QrCode::new(totp(secret, username).get_url())
+        .expect("otpauth URL to fit in a QR code")
+        .render::<svg::Color>()
+        .min_dimensions(200, 200)
+        .build()
+}
+
+pub fn new_recovery_codes() -> Vec<String> {
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let code: String = rand::rng()
+                .sample_iter(&Alphanumeric)
+                .take(10)
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .collect();
+            format!("{}-{}", &code[..5], &code[5..])
+        })
+        .collect()
+}
+
+/// Recovery codes are accepted regardless of case, spacing and dashes
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(char::is_ascii_alphanumeric)
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_adjacent_steps_only() {
+        let secret = new_secret();
+        let now = 1_800_000_015;
+        let code_at = |time| totp(&secret, "").generate(time);
+
+        assert_eq!(verify(&secret, &code_at(now), now), Some(60_000_000));
+        assert_eq!(verify(&secret, &code_at(now - 30), now), Some(59_999_999));
+        assert_eq!(verify(&secret, &code_at(now + 30), now), Some(60_000_001));
+        assert_eq!(verify(&secret, &code_at(now - 60), now), None);
+    }
+
+    #[test]
+    fn normalizes_recovery_codes() {
+        let code = &new_recovery_codes()[0];
+
+        assert_eq!(
+            normalize_recovery_code(&code.to_uppercase().replace('-', " ")),
+            code.replace('-', "")
+        );
+    }
+}
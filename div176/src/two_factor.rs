@@ -0,0 +1,364 @@
+#![allow(non_snake_case)]
+
+use std::time::Duration;
+
+use axum::{
+    Form,
+    extract::State,
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use chrono::Utc;
+use components::Layout;
+use db::{LoginOutcome, Session, TwoFactorStatus};
+use hypertext::*;
+use serde::Deserialize;
+use types::Error;
+
+use crate::{
+    AppState,
+    auth::{AuthUser, PendingUser},
+    client::Client,
+    sessions::token_hash,
+    totp,
+};
+
+/// How long a session has to pass the second factor after the password
+pub const PENDING_MAX_AGE: Duration = Duration::from_secs(10 * 60);
+/// Wrong codes before the password has to be entered again
+const MAX_FAILURES: i32 = 5;
+
+#[derive(Deserialize)]
+pub struct CodeModel {
+    code: String,
+}
+
+#[tracing::instrument(skip(session, state))]
+pub async fn second_factor_page(
+    PendingUser(session): PendingUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    let status = state.db.two_factor.get(session.uid).await?;
+
+    if status.enabled {
+        return Ok(Layout(rsx! {
+            <div class="max-w-sm mx-auto space-y-4">
+                <h1 class="text-2xl font-medium">Two-Factor Authentication</h1>
+                <form hx-post="/login/two-factor" class="space-y-3" data-errors>
+                    <label class="block">
+                        <span class="text-sm font-medium">"Code from your authenticator app, or a recovery code"</span>
+                        <input type="text" name="code" autocomplete="one-time-code" autofocus class="w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green" required>
+                        <p class="text-red text-sm" data-error-for="code"></p>
+                    </label>
+                    <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Verify">
+                </form>
+            </div>
+        })
+        .render());
+    }
+
+    // Only privileged roles are sent here without two-factor set up
+    let secret = state
+        .db
+        .two_factor
+        .start_enrolment(session.uid, &totp::new_secret())
+        .await?;
+    let username = username(&state, session.uid).await?;
+
+    Ok(Layout(rsx_move! {
+        <div class="max-w-md mx-auto space-y-4">
+            <h1 class="text-2xl font-medium">Set Up Two-Factor Authentication</h1>
+            <p>"Your role gives you access to members' personal details, so signing in needs a code from an authenticator app as well as your password."</p>
+            {EnrolForm(&secret, &username, "/login/two-factor/setup")}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(session, state, client, body))]
+pub async fn verify_second_factor(
+    PendingUser(session): PendingUser,
+    State(state): State<AppState>,
+    client: Client,
+    Form(body): Form<CodeModel>,
+) -> Result<impl IntoResponse, Error> {
+    let status = state.db.two_factor.get(session.uid).await?;
+    if !status.enabled {
+        return Err(Error::NotFound);
+    }
+
+    if !check_code(&state, session.uid, &status, &body.code).await? {
+        return Err(failed(&state, &client, &session).await?);
+    }
+
+    record(&state, &client, session.uid, LoginOutcome::Succeeded).await?;
+    state
+        .db
+        .sessions
+        .complete_second_factor(session.usid, state.config.session_max_age)
+        .await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/")]),
+    ))
+}
+
+/// Enrols a privileged member who was made to set up two-factor when signing
+/// in, which also finishes signing them in
+#[tracing::instrument(skip(session, state, client, body))]
+pub async fn enrol_pending(
+    PendingUser(session): PendingUser,
+    State(state): State<AppState>,
+    client: Client,
+    Form(body): Form<CodeModel>,
+) -> Result<impl IntoResponse, Error> {
+    let codes = match enrol(&state, session.uid, &body.code).await {
+        Err(Error::UnprocessableEntity { .. }) => {
+            return Err(failed(&state, &client, &session).await?);
+        }
+        codes => codes?,
+    };
+
+    record(&state, &client, session.uid, LoginOutcome::Succeeded).await?;
+    state
+        .db
+        .sessions
+        .complete_second_factor(session.usid, state.config.session_max_age)
+        .await?;
+
+    Ok(RecoveryCodes(codes, "/").render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn two_factor_page(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    if state.db.two_factor.get(ctx.user_id).await?.enabled {
+        return Err(Error::NotFound);
+    }
+
+    let secret = state
+        .db
+        .two_factor
+        .start_enrolment(ctx.user_id, &totp::new_secret())
+        .await?;
+    let username = username(&state, ctx.user_id).await?;
+
+    Ok(Layout(rsx_move! {
+        <div class="max-w-md mx-auto space-y-4">
+            <h1 class="text-2xl font-medium">Set Up Two-Factor Authentication</h1>
+            <p>"Signing in will need a code from an authenticator app as well as your password."</p>
+            {EnrolForm(&secret, &username, "/account/two-factor")}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state, body))]
+pub async fn enrol_account(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<CodeModel>,
+) -> Result<impl IntoResponse, Error> {
+    let codes = enrol(&state, ctx.user_id, &body.code).await?;
+
+    Ok(RecoveryCodes(codes, "/account").render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn regenerate_recovery_codes(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+) -> Result<impl IntoResponse, Error> {
+    if !state.db.two_factor.get(ctx.user_id).await?.enabled {
+        return Err(Error::NotFound);
+    }
+
+    let (codes, hashes) = new_recovery_codes(&state);
+    state
+        .db
+        .two_factor
+        .replace_recovery_codes(ctx.user_id, &hashes)
+        .await?;
+
+    Ok(RecoveryCodes(codes, "/account").render())
+}
+
+/// Needs a current code, so a session left signed in can't be used to turn it
+/// off
+#[tracing::instrument(skip(ctx, state, body))]
+pub async fn disable(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Form(body): Form<CodeModel>,
+) -> Result<impl IntoResponse, Error> {
+    if ctx.roles.iter().any(|role| role.requires_second_factor()) {
+        return Err(Error::unprocessable_entity([(
+            "two_factor",
+            "Your role requires two-factor authentication",
+        )]));
+    }
+
+    let status = state.db.two_factor.get(ctx.user_id).await?;
+    if !status.enabled {
+        return Err(Error::NotFound);
+    }
+
+    if !check_code(&state, ctx.user_id, &status, &body.code).await? {
+        return Err(Error::unprocessable_entity([(
+            "code",
+            "That code didn't work",
+        )]));
+    }
+
+    state.db.two_factor.disable(ctx.user_id).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(HeaderName::from_static("hx-redirect"), "/account")]),
+    ))
+}
+
+/// Checks an authenticator code, or failing that a recovery code, using it up
+async fn check_code(
+    state: &AppState,
+    uid: i32,
+    status: &TwoFactorStatus,
+    code: &str,
+) -> Result<bool, Error> {
+    let code = code.trim();
+
+    if code.len() == 6 && code.chars().all(|c| c.is_ascii_digit()) {
+        let Some(step) = status
+            .secret
+            .as_deref()
+            .and_then(|secret| totp::verify(secret, code, Utc::now().timestamp() as u64))
+        else {
+            return Ok(false);
+        };
+
+        return state.db.two_factor.accept_step(uid, step).await;
+    }
+
+    let hash = token_hash(state, &totp::normalize_recovery_code(code));
+    state.db.two_factor.use_recovery_code(uid, &hash).await
+}
+
+/// Records a second factor attempt alongside the password ones, against the
+/// member's username and the address it came from
+async fn record(
+    state: &AppState,
+    client: &Client,
+    uid: i32,
+    outcome: LoginOutcome,
+) -> Result<(), Error> {
+    let username = username(state, uid).await?;
+    let ip_address = client.ip.map(|ip| ip.to_string());
+
+    state
+        .db
+        .login_attempts
+        .record(
+            &username,
+            Some(uid),
+            outcome,
+            ip_address.as_deref(),
+            client.user_agent.as_deref(),
+        )
+        .await
+}
+
+/// Counts a wrong code against a pending session, ending it after too many,
+/// and towards the login lockouts
+async fn failed(state: &AppState, client: &Client, session: &Session) -> Result<Error, Error> {
+    record(state, client, session.uid, LoginOutcome::BadSecondFactor).await?;
+
+    if state
+        .db
+        .sessions
+        .record_second_factor_failure(session.usid, MAX_FAILURES)
+        .await?
+    {
+        Ok(Error::unprocessable_entity([(
+            "code",
+            "That code didn't work",
+        )]))
+    } else {
+        Ok(Error::Unauthorized)
+    }
+}
+
+/// Finishes enrolment if the code matches the secret being enrolled, returning
+/// the new recovery codes
+async fn enrol(state: &AppState, uid: i32, code: &str) -> Result<Vec<String>, Error> {
+    let status = state.db.two_factor.get(uid).await?;
+    let secret = status.secret.filter(|_| !status.enabled).ok_or_else(|| {
+        Error::unprocessable_entity([("code", "Two-factor authentication is already set up")])
+    })?;
+
+    let step =
+        totp::verify(&secret, code.trim(), Utc::now().timestamp() as u64).ok_or_else(|| {
+            Error::unprocessable_entity([(
+                "code",
+                "That code didn't work, check the time on your device is correct",
+            )])
+        })?;
+
+    let (codes, hashes) = new_recovery_codes(state);
+    state
+        .db
+        .two_factor
+        .enable(uid, &secret, step, &hashes)
+        .await?;
+
+    Ok(codes)
+}
+
+fn new_recovery_codes(state: &AppState) -> (Vec<String>, Vec<Vec<u8>>) {
+    let codes = totp::new_recovery_codes();
+    let hashes = codes
+        .iter()
+        .map(|code| token_hash(state, &totp::normalize_recovery_code(code)))
+        .collect();
+
+    (codes, hashes)
+}
+
+async fn username(state: &AppState, uid: i32) -> Result<String, Error> {
+    let user = state.db.users.get_user(uid).await?.ok_or(Error::NotFound)?;
+
+    Ok(user.username)
+}
+
+fn EnrolForm<'a>(secret: &'a [u8], username: &'a str, action: &'a str) -> impl Renderable + 'a {
+    rsx_move! {
+        <div id="two-factor" class="space-y-3">
+            <p>"Scan this QR code with an authenticator app, or enter the key by hand."</p>
+            <div class="w-52">{Raw(totp::qr_code(secret, username))}</div>
+            <p class="font-mono text-sm break-all">{totp::encoded_secret(secret)}</p>
+            <form hx-post=action hx-target="#two-factor" hx-swap="outerHTML" class="space-y-3" data-errors>
+                <label class="block">
+                    <span class="text-sm font-medium">Code from the app</span>
+                    <input type="text" name="code" inputmode="numeric" autocomplete="one-time-code" class="w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green" required>
+                    <p class="text-red text-sm" data-error-for="code"></p>
+                </label>
+                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Turn on">
+            </form>
+        </div>
+    }
+}
+
+fn RecoveryCodes(codes: Vec<String>, next: &'static str) -> impl Renderable {
+    rsx_move! {
+        <div id="two-factor" class="space-y-3">
+            <h2 class="text-lg font-medium">Recovery Codes</h2>
+            <p>"Keep these somewhere safe. Each one can be used once to sign in if you lose your authenticator, and they won't be shown again."</p>
+            <ul class="grid grid-cols-2 gap-1 font-mono p-2 border border-neutral rounded">
+                {codes.iter().map(|code| rsx_move! { <li>{code}</li> }).render_all()}
+            </ul>
+            <a class="inline-block bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light" href=next>Continue</a>
+        </div>
+    }
+}
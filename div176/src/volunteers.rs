@@ -0,0 +1,428 @@
+#![allow(non_snake_case)]
+
+use axum::{
+    Form,
+    extract::{Path, Query, State},
+    http::{HeaderName, StatusCode},
+    response::{AppendHeaders, IntoResponse},
+};
+use components::Layout;
+use db::{HoursTotal, MemberSummary, Profile, ProfileUpdate};
+use hypertext::*;
+use serde::Deserialize;
+use types::{Error, Permission};
+
+use crate::{
+    AppState,
+    account::QualificationsSection,
+    auth::{AuthUser, Ctx},
+    credentials,
+    events::DEFAULT_COLOUR,
+    forms::{self, empty_as_none},
+};
+
+#[derive(Deserialize, Debug)]
+pub struct DirectoryFilter {
+    #[serde(default, deserialize_with = "empty_as_none")]
+    q: Option<String>,
+    #[serde(default)]
+    archived: bool,
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn directory(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Query(filter): Query<DirectoryFilter>,
+) -> Result<impl IntoResponse, Error> {
+    let private = ctx.has(Permission::ViewMembers);
+    let archived = filter.archived && ctx.has(Permission::ManageMembers);
+
+    let members = state
+        .db
+        .users
+        .search(filter.q.as_deref(), private, archived)
+        .await?;
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <h1 class="text-2xl font-medium">Volunteers</h1>
+            {DirectoryFilterForm(filter.q.as_deref(), archived, &ctx)}
+            <table class="w-full text-left text-sm">
+                <thead class="border-b border-neutral">
+                    <tr>
+                        <th class="py-1 pr-2">Name</th>
+                        {private.then(|| rsx! {
+                            <th class="py-1 pr-2">Email</th>
+                            <th class="py-1">Phone</th>
+                        })}
+                    </tr>
+                </thead>
+                <tbody class="divide-y divide-neutral">
+                    {members.iter().map(|member| MemberRow(member, private)).render_all()}
+                </tbody>
+            </table>
+            {members.is_empty().then(|| rsx! {
+                <p class="text-neutral">No volunteers found</p>
+            })}
+        </div>
+    })
+    .render())
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn profile(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(uid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    let profile = state
+        .db
+        .users
+        .get_profile(uid)
+        .await?
+        .filter(|profile| !profile.archived || ctx.has(Permission::ManageMembers))
+        .ok_or(Error::NotFound)?;
+    let qualifications = state.db.groups.qualifications_for_user(uid).await?;
+    let totals = state.db.hours.totals_for_user(uid).await?;
+
+    let own = uid == ctx.user_id;
+    let private = own || ctx.has(Permission::ViewMembers);
+    let editable = own || ctx.has(Permission::ManageMembers);
+
+    Ok(Layout(rsx! {
+        <div class="space-y-4">
+            <div class="flex flex-wrap items-center justify-between gap-2">
+                <div>
+                    <h1 class="text-2xl font-medium">{&profile.display_name}</h1>
+                    <p class="text-sm text-neutral">
+                        {&profile.username} " · Member since " {profile.created_at.format("%B %Y").to_string()}
+                    </p>
+                </div>
+                <div class="flex gap-2">
+                    {profile.invited.then(|| rsx! {
+                        <span class="text-xs px-2 py-0.5 rounded-full border border-yellow text-yellow">Invited</span>
+                    })}
+                    {profile.archived.then(|| rsx! {
+                        <span class="text-xs px-2 py-0.5 rounded-full border border-red text-red">Archived</span>
+                    })}
+                </div>
+            </div>
+            {private.then(|| ContactSection(&profile, editable, &ctx))}
+            {QualificationsSection(&qualifications)}
+            {HoursTotals(&totals)}
+            {(ctx.has(Permission::ManageMembers) && !own).then(|| ArchiveSection(&profile))}
+        </div>
+    })
+    .render())
+}
+
+#[derive(Deserialize, Debug)]
+pub struct ProfileModel {
+    display_name: String,
+    email: String,
+    phone_number: String,
+    legal_name: Option<String>,
+}
+
+/// Members edit their own contact details, and admins anyone's including their
+/// legal name. A new email is only used once it's confirmed from that inbox.
+#[tracing::instrument(skip(ctx, state, body))]
+pub async fn update_profile(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(uid): Path<i32>,
+    Form(body): Form<ProfileModel>,
+) -> Result<impl IntoResponse, Error> {
+    if uid != ctx.user_id {
+        ctx.require(Permission::ManageMembers)?;
+    }
+    let profile = state
+        .db
+        .users
+        .get_profile(uid)
+        .await?
+        .ok_or(Error::NotFound)?;
+
+    let (display_name, email, phone_number) =
+        validate_contact(&body.display_name, &body.email, &body.phone_number)?;
+    let legal_name = body
+        .legal_name
+        .filter(|_| ctx.has(Permission::ManageMembers))
+        .map(|name| name.trim().to_owned());
+    if legal_name.as_deref() == Some("") {
+        return Err(Error::unprocessable_entity([(
+            "legal_name",
+            "Enter a legal name",
+        )]));
+    }
+
+    state
+        .db
+        .users
+        .update_profile(
+            uid,
+            &ProfileUpdate {
+                display_name,
+                phone_number,
+                legal_name,
+            },
+        )
+        .await?;
+
+    if email != profile.email && profile.pending_email.as_ref() != Some(&email) {
+        credentials::send_email_change(&state, uid, &email).await?;
+    }
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(
+            HeaderName::from_static("hx-redirect"),
+            format!("/volunteers/{uid}"),
+        )]),
+    ))
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn archive(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(uid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    set_archived(ctx, state, uid, true).await
+}
+
+#[tracing::instrument(skip(ctx, state))]
+pub async fn unarchive(
+    AuthUser(ctx): AuthUser,
+    State(state): State<AppState>,
+    Path(uid): Path<i32>,
+) -> Result<impl IntoResponse, Error> {
+    set_archived(ctx, state, uid, false).await
+}
+
+async fn set_archived(
+    ctx: Ctx,
+    state: AppState,
+    uid: i32,
+    archived: bool,
+) -> Result<impl IntoResponse, Error> {
+    ctx.require(Permission::ManageMembers)?;
+
+    if uid == ctx.user_id {
+        return Err(Error::unprocessable_entity([(
+            "archived",
+            "You can't archive yourself",
+        )]));
+    }
+
+    state.db.users.set_archived(uid, archived).await?;
+
+    Ok((
+        StatusCode::OK,
+        AppendHeaders([(
+            HeaderName::from_static("hx-redirect"),
+            format!("/volunteers/{uid}"),
+        )]),
+    ))
+}
+
+/// Trims the contact details and checks they're usable, returning the phone
+/// number as just its digits
+pub fn validate_contact(
+    display_name: &str,
+    email: &str,
+    phone_number: &str,
+) -> Result<(String, String, String), Error> {
+    let display_name = display_name.trim();
+    let email = email.trim();
+    let phone_number = forms::phone_number(phone_number);
+
+    let mut errors = Vec::new();
+    if display_name.is_empty() || display_name.chars().count() > 100 {
+        errors.push(("display_name", "Enter a name of at most 100 characters"));
+    }
+    if !email.contains('@') || email.contains(char::is_whitespace) {
+        errors.push(("email", "Enter an email address"));
+    }
+    if phone_number.is_none() {
+        errors.push(("phone_number", "Enter a 10 digit phone number"));
+    }
+
+    match phone_number {
+        Some(phone_number) if errors.is_empty() => {
+            Ok((display_name.to_owned(), email.to_owned(), phone_number))
+        }
+        _ => Err(Error::unprocessable_entity(errors)),
+    }
+}
+
+fn DirectoryFilterForm<'a>(
+    query: Option<&'a str>,
+    archived: bool,
+    ctx: &'a Ctx,
+) -> impl Renderable + 'a {
+    let input = "px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+
+    rsx_move! {
+        <form method="get" action="/volunteers" class="flex flex-wrap items-end gap-2">
+            <label class="block grow">
+                <span class="text-sm font-medium">Search</span>
+                <input type="search" name="q" value=query.unwrap_or_default() placeholder="Name or username" class=format!("w-full {input}")>
+            </label>
+            {ctx.has(Permission::ManageMembers).then(|| rsx_move! {
+                <label class="flex items-center gap-1 py-1">
+                    {maud_move! { input type="checkbox" name="archived" value="true" checked[archived]; }}
+                    Archived
+                </label>
+            })}
+            <input class="bg-green text-white px-2 py-1 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Search">
+        </form>
+    }
+}
+
+fn MemberRow(member: &MemberSummary, private: bool) -> impl Renderable {
+    rsx_move! {
+        <tr>
+            <td class="py-1 pr-2">
+                <a class="text-green font-medium" href=format!("/volunteers/{}", member.uid)>{&member.display_name}</a>
+                {private.then(|| rsx_move! {
+                    <span class="text-neutral">" · " {&member.legal_name}</span>
+                })}
+            </td>
+            {private.then(|| rsx_move! {
+                <td class="py-1 pr-2"><a class="hover:underline" href=format!("mailto:{}", member.email)>{&member.email}</a></td>
+                <td class="py-1 whitespace-nowrap"><a class="hover:underline" href=format!("tel:{}", member.phone_number)>{PhoneNumber(&member.phone_number)}</a></td>
+            })}
+        </tr>
+    }
+}
+
+fn ContactSection<'a>(profile: &'a Profile, editable: bool, ctx: &'a Ctx) -> impl Renderable + 'a {
+    let input = "w-full px-2 py-1 border border-neutral rounded shadow-sm bg-white-true focus:border-green focus:outline-none focus:ring-1 focus:ring-green";
+    let admin = ctx.has(Permission::ManageMembers);
+
+    rsx_move! {
+        <section>
+            <h2 class="text-white font-medium p-2 bg-green-light rounded-t-lg">Contact</h2>
+            <div class="p-4 rounded-b-lg border border-green-light space-y-2">
+                <dl class="grid grid-cols-[auto_1fr] gap-x-4 gap-y-1">
+                    <dt class="text-neutral">Legal name</dt>
+                    <dd>{&profile.legal_name}</dd>
+                    <dt class="text-neutral">Email</dt>
+                    <dd>
+                        <a class="hover:underline" href=format!("mailto:{}", profile.email)>{&profile.email}</a>
+                        {profile.pending_email.as_ref().map(|email| rsx_move! {
+                            <p class="text-sm text-neutral">"Changing to " {email} " once it's confirmed from the link emailed there"</p>
+                        })}
+                    </dd>
+                    <dt class="text-neutral">Phone</dt>
+                    <dd><a class="hover:underline" href=format!("tel:{}", profile.phone_number)>{PhoneNumber(&profile.phone_number)}</a></dd>
+                </dl>
+                {editable.then(|| rsx_move! {
+                    <details>
+                        <summary class="text-green font-medium cursor-pointer">Edit</summary>
+                        <form hx-post=format!("/volunteers/{}", profile.uid) class="mt-2 grid desktop:grid-cols-2 gap-3" data-errors>
+                            <label class="block">
+                                <span class="text-sm font-medium">Display name</span>
+                                <input type="text" name="display_name" value=&profile.display_name class=input required>
+                                <p class="text-red text-sm" data-error-for="display_name"></p>
+                            </label>
+                            {admin.then(|| rsx_move! {
+                                <label class="block">
+                                    <span class="text-sm font-medium">Legal name</span>
+                                    <input type="text" name="legal_name" value=&profile.legal_name class=input required>
+                                    <p class="text-red text-sm" data-error-for="legal_name"></p>
+                                </label>
+                            })}
+                            <label class="block">
+                                <span class="text-sm font-medium">Email</span>
+                                <input type="email" name="email" value=&profile.email class=input required>
+                                <p class="text-red text-sm" data-error-for="email"></p>
+                            </label>
+                            <label class="block">
+                                <span class="text-sm font-medium">Phone number</span>
+                                <input type="tel" name="phone_number" value=&profile.phone_number class=input required>
+                                <p class="text-red text-sm" data-error-for="phone_number"></p>
+                            </label>
+                            <div class="desktop:col-span-2">
+                                <input class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light cursor-pointer" type="submit" value="Save">
+                            </div>
+                        </form>
+                    </details>
+                })}
+            </div>
+        </section>
+    }
+}
+
+fn HoursTotals(totals: &[HoursTotal]) -> impl Renderable {
+    let hours: f64 = totals.iter().map(|total| total.hours).sum();
+    let recent_hours: f64 = totals.iter().map(|total| total.recent_hours).sum();
+
+    rsx_move! {
+        <section class="overflow-x-auto">
+            <h2 class="font-medium mb-1">Hours</h2>
+            <table class="w-full text-left text-sm">
+                <thead class="border-b border-neutral">
+                    <tr>
+                        <th class="py-1 pr-2">Type</th>
+                        <th class="py-1 pr-2 text-right">Last 12 months</th>
+                        <th class="py-1 text-right">All time</th>
+                    </tr>
+                </thead>
+                <tbody class="divide-y divide-neutral">
+                    {totals.iter().map(|total| rsx_move! {
+                        <tr>
+                            <td class="py-1 pr-2">
+                                <span class="inline-block w-2 h-2 mr-1 rounded-full" style=format!("background-color: #{}", total.colour.as_deref().unwrap_or(DEFAULT_COLOUR))></span>
+                                {&total.type_name}
+                            </td>
+                            <td class="py-1 pr-2 text-right">{format!("{:.2}", total.recent_hours)}</td>
+                            <td class="py-1 text-right">{format!("{:.2}", total.hours)}</td>
+                        </tr>
+                    }).render_all()}
+                </tbody>
+                <tfoot class="border-t border-neutral font-medium">
+                    <tr>
+                        <td class="py-1 pr-2">Total</td>
+                        <td class="py-1 pr-2 text-right">{format!("{recent_hours:.2}")}</td>
+                        <td class="py-1 text-right">{format!("{hours:.2}")}</td>
+                    </tr>
+                </tfoot>
+            </table>
+        </section>
+    }
+}
+
+fn ArchiveSection(profile: &Profile) -> impl Renderable {
+    let button = move |output: &mut String| {
+        if profile.archived {
+            rsx_move! {
+                <button hx-post=format!("/volunteers/{}/unarchive", profile.uid) class="bg-green text-white px-2 py-0.5 shadow-sm rounded hover:bg-green-light">Unarchive</button>
+            }
+            .render_to(output)
+        } else {
+            rsx_move! {
+                <button hx-post=format!("/volunteers/{}/archive", profile.uid) hx-confirm="Archive this member? They'll be signed out and can't sign in until unarchived." class="bg-white-true text-red border border-red px-2 py-0.5 shadow-sm rounded hover:bg-red hover:text-white">Archive</button>
+            }
+            .render_to(output)
+        }
+    };
+
+    rsx_move! {
+        <section class="space-y-1" data-errors>
+            {button}
+            <p class="text-red text-sm" data-error-for="archived"></p>
+        </section>
+    }
+}
+
+fn PhoneNumber(digits: &str) -> String {
+    match (digits.get(..3), digits.get(3..6), digits.get(6..)) {
+        (Some(area), Some(exchange), Some(line)) if digits.len() == 10 => {
+            format!("({area}) {exchange}-{line}")
+        }
+        _ => digits.to_owned(),
+    }
+}